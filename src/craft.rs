@@ -7,7 +7,9 @@ use bevy_rapier3d::prelude::*;
 
 pub mod arms;
 pub mod attire;
+pub mod countermeasures;
 pub mod engine;
+pub mod stealth;
 
 pub struct CraftsPlugin;
 
@@ -17,11 +19,18 @@ impl Plugin for CraftsPlugin {
             .add_system(engine::linear_pid_driver.before(engine::apply_flames_simple_accel))
             .add_system(engine::angular_pid_driver.before(engine::apply_flames_simple_accel))
             .add_system(engine::apply_flames_simple_accel)
+            .add_system(stealth::update_emissions.after(engine::linear_pid_driver))
             .add_plugin(attire::AttirePlugin)
             .add_plugin(arms::ArmsPlugin)
+            .add_plugin(countermeasures::CountermeasuresPlugin)
             .register_inspectable::<engine::LinearEngineState>()
             .register_inspectable::<engine::AngularEngineState>()
-            .register_inspectable::<engine::EngineConfig>();
+            .register_inspectable::<engine::EngineConfig>()
+            .register_inspectable::<countermeasures::Seeker>()
+            .register_inspectable::<countermeasures::PointDefence>()
+            .register_inspectable::<arms::Turret>()
+            .register_inspectable::<Faction>()
+            .register_inspectable::<stealth::RadarSignature>();
     }
 }
 
@@ -50,6 +59,9 @@ pub struct CraftBundle {
     pub angular_state: engine::AngularEngineState,
     // pub linear_pid: engine::LinearDriverPid,
     pub angular_pid: engine::AngularDriverPid,
+    pub weapon_groups: arms::WeaponGroups,
+    pub signature: stealth::RadarSignature,
+    pub faction: Faction,
 
     pub name: Name,
 }
@@ -84,6 +96,9 @@ impl CraftBundle {
             ccd: Ccd::enabled(),
             collider: default(),
             collision_damage_tag: attire::CollisionDamageEnabledRb,
+            weapon_groups: default(),
+            signature: stealth::RadarSignature::for_dimensions(dimensions),
+            faction: default(),
             name: Self::DEFAULT_NAME.into(),
             colliders: default(),
             velocity: default(),
@@ -93,6 +108,10 @@ impl CraftBundle {
     }
 }
 
+/// Which side the craft's on. Crafts don't pick targets from their own side.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Component, Reflect, Inspectable)]
+pub struct Faction(pub u32);
+
 /// The dimensions of the craft.
 #[derive(Debug, Clone, Copy, Component, Reflect, Inspectable, educe::Educe)]
 #[educe(Deref, DerefMut)]
@@ -1,6 +1,7 @@
 use deps::*;
 
 use bevy::prelude::*;
+use bevy_inspector_egui::Inspectable;
 use bevy_rapier3d::prelude::*;
 use bevy_rapier3d::rapier::prelude::SharedShape;
 
@@ -14,7 +15,8 @@ impl Plugin for ArmsPlugin {
         app.add_system(handle_activate_weapon_events_projectile)
             .add_system(cull_old_colliding_projectiles)
             .add_event::<ActivateWeaponEvent>()
-            .add_event::<ProjectileIxnEvent>();
+            .add_event::<ProjectileIxnEvent>()
+            .add_event::<BlastEvent>();
     }
 }
 /// A generic bundle for craft strategies.
@@ -88,6 +90,100 @@ impl CraftWeapon {
     }
 }
 
+/// Index into [`WeaponGroups::groups`].
+pub type WeaponGroupId = usize;
+
+/// Groups the craft's weapons by [`WeaponClass`] so that they can be fired selectively.
+/// A class can be in more than one group. Crafts without any groups configured have all
+/// their weapons in [`WeaponGroups::PRIMARY`].
+/// Craft component.
+#[derive(Debug, Clone, Component, Default)]
+pub struct WeaponGroups {
+    pub groups: SVec<[SVec<[WeaponClass; 2]>; 2]>,
+}
+
+impl WeaponGroups {
+    pub const PRIMARY: WeaponGroupId = 0;
+    pub const SECONDARY: WeaponGroupId = 1;
+    /// Limited by the width of [`FireOrders`].
+    pub const MAX_GROUPS: usize = 8;
+
+    /// Adds the class to the group, creating any groups required up to it.
+    pub fn with(mut self, group: WeaponGroupId, class: WeaponClass) -> Self {
+        assert!(group < Self::MAX_GROUPS, "WeaponGroupId out of bounds");
+        if self.groups.len() <= group {
+            self.groups.resize(group + 1, default());
+        }
+        if !self.groups[group].contains(&class) {
+            self.groups[group].push(class);
+        }
+        self
+    }
+
+    /// Whether no group's been set up in which case [`WeaponGroups::PRIMARY`] has everything.
+    #[inline]
+    pub fn is_unconfigured(&self) -> bool {
+        self.groups.iter().all(|classes| classes.is_empty())
+    }
+
+    #[inline]
+    pub fn classes(&self, group: WeaponGroupId) -> &[WeaponClass] {
+        self.groups.get(group).map(|v| &v[..]).unwrap_or(&[])
+    }
+}
+
+/// Puts a weapon under automatic control, swivelling it to track the craft's current target
+/// and firing when it's got a solution. Keep the weapon's class out of the craft's
+/// [`WeaponGroups`].
+#[derive(Debug, Clone, Component, Inspectable)]
+pub struct Turret {
+    pub traverse_rate_radians: TReal,
+}
+
+/// Set of [`WeaponGroupId`]s that are ordered to fire.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Inspectable)]
+pub struct FireOrders {
+    mask: u8,
+}
+
+impl FireOrders {
+    pub const NONE: Self = Self { mask: 0 };
+
+    #[inline]
+    pub fn single(group: WeaponGroupId) -> Self {
+        let mut orders = Self::NONE;
+        orders.set(group, true);
+        orders
+    }
+
+    #[inline]
+    pub fn set(&mut self, group: WeaponGroupId, fire: bool) {
+        debug_assert!(
+            group < WeaponGroups::MAX_GROUPS,
+            "WeaponGroupId out of bounds"
+        );
+        if fire {
+            self.mask |= 1 << group;
+        } else {
+            self.mask &= !(1 << group);
+        }
+    }
+
+    #[inline]
+    pub fn contains(self, group: WeaponGroupId) -> bool {
+        group < WeaponGroups::MAX_GROUPS && self.mask & (1 << group) != 0
+    }
+
+    #[inline]
+    pub fn is_empty(self) -> bool {
+        self.mask == 0
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = WeaponGroupId> {
+        (0..WeaponGroups::MAX_GROUPS).filter(move |group| self.contains(*group))
+    }
+}
+
 pub struct ActivateWeaponEvent {
     pub weapon_id: Entity,
 }
@@ -123,10 +219,16 @@ pub struct ProjectileWeapon {
     pub proj_mesh: Handle<Mesh>,
     pub proj_mtr: Handle<StandardMaterial>,
     pub proj_velocity: TVec3, // TODO: replace with speed
+    /// Add the craft's velocity onto the projectile's.
+    pub proj_inherit_velocity: bool,
     pub proj_shape: SharedShape,
     pub proj_mass: ColliderMassProperties,
     pub proj_lifespan_secs: f64,
     pub proj_spawn_offset: TVec3,
+    /// Makes the projectiles home in on [`super::stealth::RadarSignature`]s.
+    pub proj_seeker: Option<super::countermeasures::Seeker>,
+    /// Makes the projectiles explode when they hit something or expire.
+    pub proj_blast: Option<Blast>,
 }
 
 #[derive(Debug, Clone, Component)]
@@ -135,6 +237,27 @@ pub struct Projectile {
     pub source_wpn: Entity,
     pub emit_instant_secs: f64,
     pub lifespan_secs: f64,
+    pub blast: Option<Blast>,
+}
+
+/// Area damage dealt around a point. Falls off linearly to nothing at the `radius`.
+#[derive(Debug, Clone, Copy)]
+pub struct Blast {
+    pub radius: TReal,
+    pub damage: Damage,
+}
+
+impl Blast {
+    /// The damage dealt to something `dst` away from the center, if any.
+    pub fn damage_at(&self, dst: TReal) -> Option<Damage> {
+        if dst >= self.radius {
+            return None;
+        }
+        Some(Damage {
+            value: self.damage.value * (1. - (dst / self.radius)),
+            damage_type: self.damage.damage_type,
+        })
+    }
 }
 
 fn handle_activate_weapon_events_projectile(
@@ -142,16 +265,18 @@ fn handle_activate_weapon_events_projectile(
     mut commands: Commands,
     mut weapons: Query<(
         &ProjectileWeapon,
+        &CraftWeapon,
         &mut WeaponActivationState,
         &GlobalTransform,
     )>,
+    crafts: Query<&Velocity>,
     mut fire_events: EventReader<ActivateWeaponEvent>,
     //mut lines: ResMut<bevy_prototype_debug_lines::DebugLines>,
     time: Res<Time>,
 ) {
     for event in fire_events.iter() {
         match weapons.get_mut(event.weapon_id) {
-            Ok((proj_wpn, mut firing_state, xform)) => {
+            Ok((proj_wpn, wpn, mut firing_state, xform)) => {
                 let xform = xform.compute_transform();
                 /* tracing::info!(
                     "\n{:?}\n{:?}",
@@ -166,14 +291,20 @@ fn handle_activate_weapon_events_projectile(
                         last_firing_time, ..
                     } => *last_firing_time = time.seconds_since_startup(),
                 }
-                commands
-                    .spawn()
-                    .insert(Name::new("projectile"))
+                let mut proj = commands.spawn();
+                if let Some(seeker) = &proj_wpn.proj_seeker {
+                    proj.insert(super::countermeasures::Seeker {
+                        ignore: Some(wpn.boid_entt()),
+                        ..seeker.clone()
+                    });
+                }
+                proj.insert(Name::new("projectile"))
                     .insert(Projectile {
                         damage: proj_wpn.proj_damage,
                         lifespan_secs: proj_wpn.proj_lifespan_secs,
                         source_wpn: event.weapon_id,
                         emit_instant_secs: time.seconds_since_startup(),
+                        blast: proj_wpn.proj_blast,
                     })
                     .insert_bundle(PbrBundle {
                         mesh: proj_wpn.proj_mesh.clone(),
@@ -185,13 +316,18 @@ fn handle_activate_weapon_events_projectile(
                         ..default()
                     })
                     .insert(RigidBody::Dynamic)
-                    .insert(
-                        // TODO: inherit craft velocity too
-                        Velocity {
-                            linvel: (xform.rotation * proj_wpn.proj_velocity),
-                            ..default()
-                        },
-                    )
+                    .insert(Velocity {
+                        linvel: (xform.rotation * proj_wpn.proj_velocity)
+                            + if proj_wpn.proj_inherit_velocity {
+                                crafts
+                                    .get(wpn.boid_entt())
+                                    .map(|vel| vel.linvel)
+                                    .unwrap_or_default()
+                            } else {
+                                TVec3::ZERO
+                            },
+                        ..default()
+                    })
                     .insert(Ccd::enabled())
                     .insert(TransformInterpolation::default())
                     /* ccd_thickness: proj_wpn.proj_shape.ccd_thickness(),
@@ -219,13 +355,22 @@ pub struct ProjectileIxnEvent {
     pub collider: Entity,
 }
 
+/// Sent when a projectile with a [`Blast`] goes off.
+#[derive(Debug, Clone)]
+pub struct BlastEvent {
+    pub blast: Blast,
+    pub position: TVec3,
+    pub source_wpn: Entity,
+}
+
 fn cull_old_colliding_projectiles(
     mut commands: Commands,
-    projectiles: Query<(Entity, &Projectile)>,
+    projectiles: Query<(Entity, &Projectile, &GlobalTransform)>,
     // FIXME: consider using RapierCtx
     mut collision_events: EventReader<CollisionEvent>,
     time: Res<Time>,
     mut ixn_events: EventWriter<ProjectileIxnEvent>,
+    mut blast_events: EventWriter<BlastEvent>,
     mut despawn_set: Local<bevy::utils::HashSet<Entity>>,
 ) {
     for collision_event in collision_events.iter() {
@@ -233,7 +378,7 @@ fn cull_old_colliding_projectiles(
             // if flags == CollisionEventFlags::SENSOR {}
 
             // if any of our collider is a projectile
-            if let Ok((proj_coll, proj)) =
+            if let Ok((proj_coll, proj, _)) =
                 projectiles.get(coll1).or_else(|_| projectiles.get(coll2))
             {
                 ixn_events.send(ProjectileIxnEvent {
@@ -244,7 +389,7 @@ fn cull_old_colliding_projectiles(
             }
         };
     }
-    for (entt, proj) in projectiles.iter() {
+    for (entt, proj, _) in projectiles.iter() {
         // test expired items
         if (time.seconds_since_startup() - proj.emit_instant_secs) > proj.lifespan_secs {
             despawn_set.insert(entt);
@@ -252,6 +397,37 @@ fn cull_old_colliding_projectiles(
     }
     for entt in despawn_set.drain() {
         tracing::trace!("projectile {:?} despawned", entt);
+        if let Ok((
+            _,
+            Projectile {
+                blast: Some(blast),
+                source_wpn,
+                ..
+            },
+            xform,
+        )) = projectiles.get(entt)
+        {
+            blast_events.send(BlastEvent {
+                blast: *blast,
+                position: xform.translation(),
+                source_wpn: *source_wpn,
+            });
+        }
         commands.entity(entt).despawn_recursive();
     }
 }
+
+#[test]
+fn blast_falloff_test() {
+    let blast = Blast {
+        radius: 10.,
+        damage: Damage {
+            value: 100.,
+            damage_type: DamageType::Explosion,
+        },
+    };
+    assert_eq!(blast.damage_at(0.).unwrap().value, 100.);
+    assert_eq!(blast.damage_at(5.).unwrap().value, 50.);
+    assert!(blast.damage_at(10.).is_none());
+    assert!(blast.damage_at(20.).is_none());
+}
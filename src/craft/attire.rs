@@ -13,13 +13,16 @@ impl Plugin for AttirePlugin {
     fn build(&self, app: &mut App) {
         app.add_system(handle_collision_damage_events)
             .add_system(handle_projectile_ixn_events)
+            .add_system(handle_blast_events)
             .add_system(
                 log_damage_events
                     .after(handle_collision_damage_events)
-                    .after(handle_projectile_ixn_events),
+                    .after(handle_projectile_ixn_events)
+                    .after(handle_blast_events),
             )
             .add_event::<CollisionDamageEvent>()
-            .add_event::<ProjectileDamageEvent>();
+            .add_event::<ProjectileDamageEvent>()
+            .add_event::<BlastDamageEvent>();
     }
 }
 
@@ -471,9 +474,52 @@ fn handle_projectile_ixn_events(
     }
 }
 
+use crate::craft::arms::BlastEvent;
+
+pub struct BlastDamageEvent {
+    pub blast_event: BlastEvent,
+    pub damage: Damage,
+    pub attire_entt: Entity,
+}
+
+/// Consumes [`BlastEvent`]s and damages the [`AttireProfile`]s in range that aren't
+/// behind any obstacles.
+fn handle_blast_events(
+    mut attires: Query<(Entity, &mut AttireProfile, &GlobalTransform)>,
+    mut blast_events: EventReader<BlastEvent>,
+    mut bd_events: EventWriter<BlastDamageEvent>,
+    los: crate::mind::sensors::los::LineOfSight,
+) {
+    for event in blast_events.iter() {
+        for (attire_entt, mut attire, xform) in attires.iter_mut() {
+            let attire_pos = xform.translation();
+            let damage = match event.blast.damage_at(event.position.distance(attire_pos)) {
+                Some(damage) => damage,
+                None => continue,
+            };
+            if !los.exposed_to(event.position, attire_entt, attire_pos) {
+                continue;
+            }
+            if attire.damage(damage).is_some() {
+                tracing::info!("Attire {attire_entt:?} destroyed by Blast damage");
+                // reset health
+                for member in attire.members.iter_mut() {
+                    member.remaining_integrity = member.factory_integrity;
+                }
+            }
+            bd_events.send(BlastDamageEvent {
+                blast_event: event.clone(),
+                damage,
+                attire_entt,
+            });
+        }
+    }
+}
+
 fn log_damage_events(
     mut coll_dmg_events: EventReader<CollisionDamageEvent>,
     mut proj_dmg_events: EventReader<ProjectileDamageEvent>,
+    mut blast_dmg_events: EventReader<BlastDamageEvent>,
     names: Query<Option<&Name>>,
 ) {
     for event in coll_dmg_events.iter() {
@@ -490,4 +536,7 @@ fn log_damage_events(
             event.attire_entt
         );
     }
+    for event in blast_dmg_events.iter() {
+        tracing::info!("Blast {:?} | Attire: {:?}", event.damage, event.attire_entt);
+    }
 }
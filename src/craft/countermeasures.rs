@@ -0,0 +1,277 @@
+use deps::*;
+
+use bevy::prelude::*;
+use bevy_inspector_egui::Inspectable;
+use bevy_rapier3d::prelude::*;
+use rand::Rng;
+
+use crate::craft::{arms::*, stealth::RadarSignature};
+use crate::math::*;
+
+pub struct CountermeasuresPlugin;
+
+impl Plugin for CountermeasuresPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(handle_deploy_countermeasures_events)
+            .add_system(cull_old_decoys)
+            .add_system(seeker_guidance)
+            .add_event::<DeployCountermeasuresEvent>();
+    }
+}
+
+/// Picks the candidate with the loudest apparent [`RadarSignature`] inside the cone.
+/// Candidates are `(entity, position, signature)`.
+pub fn loudest_signature(
+    origin: TVec3,
+    fwd: TVec3,
+    cone_half_angle_radians: TReal,
+    range: TReal,
+    candidates: impl Iterator<Item = (Entity, TVec3, RadarSignature)>,
+) -> Option<Entity> {
+    let range_squared = range * range;
+    candidates
+        .filter_map(|(entt, pos, sig)| {
+            let offset = pos - origin;
+            let dst_squared = offset.length_squared();
+            if dst_squared > range_squared
+                || dst_squared < TReal::EPSILON
+                || fwd.angle_between(offset) > cone_half_angle_radians
+            {
+                return None;
+            }
+            Some((entt, sig.apparent(dst_squared)))
+        })
+        .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+        .map(|(entt, _)| entt)
+}
+
+/// Homing logic for projectiles. Locks on to whatever's loudest in its cone every frame
+/// which is what makes decoys work.
+#[derive(Debug, Clone, Component, Inspectable)]
+pub struct Seeker {
+    pub cone_half_angle_radians: TReal,
+    pub range: TReal,
+    pub turn_rate_radians: TReal,
+    #[inspectable(ignore)]
+    pub lock: Option<Entity>,
+    /// Usually the launching craft.
+    #[inspectable(ignore)]
+    pub ignore: Option<Entity>,
+}
+
+fn seeker_guidance(
+    mut seekers: Query<(&mut Seeker, &GlobalTransform, &mut Velocity, &mut Transform)>,
+    emitters: Query<(Entity, &GlobalTransform, &RadarSignature)>,
+    time: Res<Time>,
+) {
+    for (mut seeker, xform, mut vel, mut local_xform) in seekers.iter_mut() {
+        let speed = vel.linvel.length();
+        if speed < TReal::EPSILON {
+            continue;
+        }
+        let pos = xform.translation();
+        let cur_dir = vel.linvel / speed;
+        let ignore = seeker.ignore;
+        seeker.lock = loudest_signature(
+            pos,
+            cur_dir,
+            seeker.cone_half_angle_radians,
+            seeker.range,
+            emitters
+                .iter()
+                .filter(|(entt, ..)| Some(*entt) != ignore)
+                .map(|(entt, xform, sig)| (entt, xform.translation(), *sig)),
+        );
+        let target_xform = match seeker.lock.map(|e| emitters.get(e)) {
+            Some(Ok((_, target_xform, _))) => target_xform,
+            _ => continue,
+        };
+        let desired_dir = (target_xform.translation() - pos).normalize();
+        let angle = cur_dir.angle_between(desired_dir);
+        if angle < TReal::EPSILON {
+            continue;
+        }
+        let turn = TQuat::IDENTITY.slerp(
+            TQuat::from_rotation_arc(cur_dir, desired_dir),
+            (seeker.turn_rate_radians * time.delta_seconds() / angle).min(1.),
+        );
+        vel.linvel = turn * vel.linvel;
+        local_xform.rotation = turn * local_xform.rotation;
+    }
+}
+
+/// Ejects decoys that pull [`Seeker`]s away from the craft.
+/// Use a large [`RadarSignature::hot`] [`Self::decoy_signature`] for flares and a large
+/// [`Self::decoy_count`] for chaff.
+#[derive(Component)]
+pub struct CountermeasureDispenser {
+    pub boid_entt: Entity,
+    pub decoy_signature: RadarSignature,
+    pub decoy_lifespan_secs: f64,
+    pub decoy_count: usize,
+    /// In the dispenser's space. Added onto the craft's velocity.
+    pub eject_velocity: TVec3,
+    /// How far apart the decoys in a single deployment are ejected.
+    pub eject_spread_radians: TReal,
+    pub decoy_mesh: Handle<Mesh>,
+    pub decoy_mtr: Handle<StandardMaterial>,
+    /// Number of deployments left.
+    pub charges: u32,
+}
+
+#[derive(Bundle)]
+pub struct CountermeasureBundle {
+    pub dispenser: CountermeasureDispenser,
+    pub activation_state: WeaponActivationState,
+    pub name: Name,
+}
+
+impl CountermeasureBundle {
+    pub const DEFAULT_NAME: &'static str = "countermeasure_dispenser";
+    pub fn new(
+        dispenser: CountermeasureDispenser,
+        activation_state: WeaponActivationState,
+    ) -> Self {
+        Self {
+            dispenser,
+            activation_state,
+            name: Self::DEFAULT_NAME.into(),
+        }
+    }
+}
+
+/// Fires all the ready dispensers on the craft.
+pub struct DeployCountermeasuresEvent {
+    pub boid_entt: Entity,
+}
+
+#[derive(Debug, Clone, Component)]
+pub struct Decoy {
+    pub source_dispenser: Entity,
+    pub emit_instant_secs: f64,
+    pub lifespan_secs: f64,
+}
+
+fn handle_deploy_countermeasures_events(
+    mut commands: Commands,
+    mut dispensers: Query<(
+        Entity,
+        &mut CountermeasureDispenser,
+        &mut WeaponActivationState,
+        &GlobalTransform,
+    )>,
+    crafts: Query<&Velocity>,
+    mut deploy_events: EventReader<DeployCountermeasuresEvent>,
+    time: Res<Time>,
+) {
+    let mut rng = rand::thread_rng();
+    for event in deploy_events.iter() {
+        let craft_vel = crafts
+            .get(event.boid_entt)
+            .map(|v| v.linvel)
+            .unwrap_or_default();
+        for (entt, mut dispenser, mut firing_state, xform) in dispensers.iter_mut() {
+            if dispenser.boid_entt != event.boid_entt
+                || dispenser.charges == 0
+                || !firing_state.can_activate(&time)
+            {
+                continue;
+            }
+            match firing_state.as_mut() {
+                WeaponActivationState::Discrete {
+                    last_firing_time, ..
+                } => *last_firing_time = time.seconds_since_startup(),
+            }
+            dispenser.charges -= 1;
+
+            let xform = xform.compute_transform();
+            for _ in 0..dispenser.decoy_count {
+                let spread = TQuat::from_euler(
+                    EulerRot::YXZ,
+                    rng.gen_range(-1.0..=1.0) * dispenser.eject_spread_radians,
+                    rng.gen_range(-1.0..=1.0) * dispenser.eject_spread_radians,
+                    0.,
+                );
+                commands
+                    .spawn()
+                    .insert(Name::new("decoy"))
+                    .insert(Decoy {
+                        source_dispenser: entt,
+                        emit_instant_secs: time.seconds_since_startup(),
+                        lifespan_secs: dispenser.decoy_lifespan_secs,
+                    })
+                    .insert(dispenser.decoy_signature)
+                    .insert_bundle(PbrBundle {
+                        mesh: dispenser.decoy_mesh.clone(),
+                        material: dispenser.decoy_mtr.clone(),
+                        transform: Transform::from_translation(xform.translation),
+                        ..default()
+                    })
+                    .insert(RigidBody::KinematicVelocityBased)
+                    .insert(Velocity {
+                        linvel: craft_vel + (xform.rotation * spread * dispenser.eject_velocity),
+                        ..default()
+                    });
+            }
+        }
+    }
+}
+
+fn cull_old_decoys(mut commands: Commands, decoys: Query<(Entity, &Decoy)>, time: Res<Time>) {
+    for (entt, decoy) in decoys.iter() {
+        if (time.seconds_since_startup() - decoy.emit_instant_secs) > decoy.lifespan_secs {
+            commands.entity(entt).despawn_recursive();
+        }
+    }
+}
+
+/// Puts a [`ProjectileWeapon`] turret under automatic control, shooting down incoming
+/// [`Projectile`]s. Keep the weapon's class out of the craft's [`WeaponGroups`].
+#[derive(Debug, Clone, Component, Inspectable)]
+pub struct PointDefence {
+    /// How fast the turret can swivel around.
+    pub traverse_rate_radians: TReal,
+    pub range: TReal,
+    #[inspectable(ignore)]
+    pub target: Option<Entity>,
+}
+
+#[test]
+fn loudest_signature_test() {
+    let (near, far, loud, behind) = (
+        Entity::from_raw(0),
+        Entity::from_raw(1),
+        Entity::from_raw(2),
+        Entity::from_raw(3),
+    );
+    let half_angle = 30. * (real::consts::PI / 180.);
+    let candidates = || {
+        [
+            (near, -TVec3::Z * 10., RadarSignature::hot(1.)),
+            (far, -TVec3::Z * 50., RadarSignature::hot(1.)),
+            (behind, TVec3::Z * 5., RadarSignature::hot(100.)),
+        ]
+        .into_iter()
+    };
+    // the closer of two equally loud emitters, nothing outside the cone
+    assert_eq!(
+        loudest_signature(TVec3::ZERO, -TVec3::Z, half_angle, 100., candidates()),
+        Some(near)
+    );
+    // a flare outshines it despite being further off
+    assert_eq!(
+        loudest_signature(
+            TVec3::ZERO,
+            -TVec3::Z,
+            half_angle,
+            100.,
+            candidates().chain([(loud, -TVec3::Z * 40., RadarSignature::hot(100.))]),
+        ),
+        Some(loud)
+    );
+    // out of range
+    assert_eq!(
+        loudest_signature(TVec3::ZERO, -TVec3::Z, half_angle, 5., candidates()),
+        None
+    );
+}
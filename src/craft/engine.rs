@@ -241,6 +241,36 @@ pub fn linear_pid_driver(
     }
 }
 
+/// What the angular thrusters are capable of, clamped to the artificial limit if
+/// [`EngineConfig::limit_acceleration`] is set.
+/// In rad/s/s.
+pub fn angular_accel_limit(
+    config: &EngineConfig,
+    derived_config: &DerivedEngineConfig,
+    mass_props: &ReadMassProperties,
+) -> TVec3 {
+    let max_torque = derived_config.thruster_torque * config.thruster_force_multiplier;
+    // TODO: work out if this is actually the inertia tensor
+    // NOTICE: difference here
+    // torque = Inertial_tensor * rotational acceleration
+    // accel = torque / Inertial_tensor
+
+    let inv_inertial_tensor = mass_props
+        .0
+        .into_rapier(1.)
+        .reconstruct_inverse_inertia_matrix();
+    let accel_limit: TVec3 =
+        (inv_inertial_tensor * bevy_rapier3d::na::Vector3::from(max_torque)).into();
+    // let accel_limit = max_torque * TVec3::from(mass_props.0.into_rapier(1.).inv_principal_inertia_sqrt);
+    if config.limit_acceleration {
+        // clamp the actual limit to the artifical limit
+        let artificial_accel_limit = derived_config.angular_acceleration_limit;
+        accel_limit.clamp(-artificial_accel_limit, artificial_accel_limit)
+    } else {
+        accel_limit
+    }
+}
+
 // Currently assumes the inputs are acceleration
 pub fn angular_pid_driver(
     mut crafts: Query<(
@@ -254,32 +284,11 @@ pub fn angular_pid_driver(
 ) {
     for (mut state, config, derived_config, mut pid, mass_props) in crafts.iter_mut() {
         {
-            let accel_limit = {
-                let max_torque = derived_config.thruster_torque * config.thruster_force_multiplier;
-                // TODO: work out if this is actually the inertia tensor
-                // NOTICE: difference here
-                // torque = Inertial_tensor * rotational acceleration
-                // accel = torque / Inertial_tensor
-
-                let inv_inertial_tensor = mass_props
-                    .0
-                    .into_rapier(1.)
-                    .reconstruct_inverse_inertia_matrix();
-                let accel_limit: TVec3 =
-                    (inv_inertial_tensor * bevy_rapier3d::na::Vector3::from(max_torque)).into();
-                // let accel_limit = max_torque * TVec3::from(mass_props.0.into_rapier(1.).inv_principal_inertia_sqrt);
-                if config.limit_acceleration {
-                    let artificial_accel_limit = derived_config.angular_acceleration_limit;
-                    pid.0.integrat_max = accel_limit.min(artificial_accel_limit);
-                    pid.0.integrat_min = -pid.0.integrat_max;
-
-                    // clamp the actual limit to the artifical limit
-
-                    accel_limit.clamp(-artificial_accel_limit, artificial_accel_limit)
-                } else {
-                    accel_limit
-                }
-            };
+            let accel_limit = angular_accel_limit(config, derived_config, mass_props);
+            if config.limit_acceleration {
+                pid.0.integrat_max = accel_limit;
+                pid.0.integrat_min = -pid.0.integrat_max;
+            }
 
             let desired_accel = state.input.clamp(-accel_limit, accel_limit);
 
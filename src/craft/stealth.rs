@@ -0,0 +1,137 @@
+use deps::*;
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_inspector_egui::Inspectable;
+
+use crate::craft::{arms::*, engine::*, CraftDimensions};
+use crate::math::*;
+
+/// How visible the craft is to radars and [`super::countermeasures::Seeker`]s. The bigger and
+/// louder it is, the further away it can be picked up from and the harder it pulls seekers.
+/// A craft coasting with its engines off and weapons quiet is at its hardest to see.
+/// Craft and decoy component
+#[derive(Debug, Clone, Copy, Component, Inspectable)]
+pub struct RadarSignature {
+    /// Apparent size. Derived from the [`CraftDimensions`].
+    /// In m^2.
+    pub cross_section: TReal,
+    /// Zero to one. How loud the engines and weapons are being currently.
+    #[inspectable(ignore)]
+    pub emissions: TReal,
+    /// How much firing a weapon adds to the emissions.
+    pub weapon_fire_emission: TReal,
+    /// How long weapon fire lingers in the emissions.
+    pub weapon_fire_decay_secs: f64,
+}
+
+impl RadarSignature {
+    /// Cross section of a craft that's visible at the full radar range when loud.
+    pub const REFERENCE_CROSS_SECTION: TReal = 64.;
+    /// Fraction of the range a craft's visible from with no emissions.
+    pub const COLD_FACTOR: TReal = 0.35;
+
+    pub fn for_dimensions(dimensions: CraftDimensions) -> Self {
+        let dim = dimensions.0;
+        Self {
+            // the mean area of the faces of the bounding box
+            cross_section: ((dim.x * dim.y) + (dim.y * dim.z) + (dim.z * dim.x)) / 3.,
+            emissions: 0.,
+            weapon_fire_emission: 1.,
+            weapon_fire_decay_secs: 2.,
+        }
+    }
+
+    /// A decoy burning at full emissions. Its emissions aren't updated since it's got no
+    /// engines.
+    pub fn hot(cross_section: TReal) -> Self {
+        Self {
+            cross_section,
+            emissions: 1.,
+            weapon_fire_emission: 0.,
+            weapon_fire_decay_secs: 0.,
+        }
+    }
+
+    #[inline]
+    fn loudness(&self) -> TReal {
+        Self::COLD_FACTOR + ((1. - Self::COLD_FACTOR) * self.emissions)
+    }
+
+    /// The fraction of a radar's range this can be detected at.
+    #[inline]
+    pub fn detection_range_multiplier(&self) -> TReal {
+        // a la the radar equation
+        let size = (self.cross_section / Self::REFERENCE_CROSS_SECTION)
+            .max(0.)
+            .powf(0.25);
+        (size * self.loudness()).clamp(0., 1.)
+    }
+
+    /// How strongly this pulls seekers, before falloff.
+    #[inline]
+    pub fn intensity(&self) -> TReal {
+        self.cross_section.max(0.) * self.loudness()
+    }
+
+    /// The intensity as perceived from `dst_squared` away. Falls off with the square of the
+    /// distance.
+    #[inline]
+    pub fn apparent(&self, dst_squared: TReal) -> TReal {
+        self.intensity() / dst_squared.max(1.)
+    }
+}
+
+/// Keeps the [`RadarSignature::emissions`] up to date with the engine flames and weapon fire.
+pub(super) fn update_emissions(
+    mut crafts: Query<(
+        Entity,
+        &LinearEngineState,
+        &EngineConfig,
+        &mut RadarSignature,
+    )>,
+    weapons: Query<(&CraftWeapon, &WeaponActivationState)>,
+    time: Res<Time>,
+    mut last_fired: Local<HashMap<Entity, f64>>,
+) {
+    last_fired.clear();
+    for (wpn, activation_state) in weapons.iter() {
+        let WeaponActivationState::Discrete {
+            last_firing_time, ..
+        } = activation_state;
+        let latest = last_fired
+            .entry(wpn.boid_entt())
+            .or_insert(f64::NEG_INFINITY);
+        *latest = latest.max(*last_firing_time);
+    }
+    let now_secs = time.seconds_since_startup();
+    for (entt, lin_state, config, mut signature) in crafts.iter_mut() {
+        let accel_limit = config.acceleration_limit * config.acceleration_limit_multiplier;
+        let engine =
+            (lin_state.flame.abs() / accel_limit.max(TVec3::splat(TReal::EPSILON))).max_element();
+        let weapons = last_fired
+            .get(&entt)
+            .map(|fired_secs| {
+                (1. - ((now_secs - fired_secs) / signature.weapon_fire_decay_secs)).max(0.) as TReal
+            })
+            .unwrap_or_default();
+        let emissions = (engine + (weapons * signature.weapon_fire_emission)).clamp(0., 1.);
+        if (signature.emissions - emissions).abs() > TReal::EPSILON {
+            signature.emissions = emissions;
+        }
+    }
+}
+
+#[test]
+fn radar_signature_test() {
+    let mut sig = RadarSignature::for_dimensions((TVec3::ONE * 8.).into());
+    assert!((sig.cross_section - RadarSignature::REFERENCE_CROSS_SECTION).abs() < 1e-3);
+    let cold = sig.detection_range_multiplier();
+    assert!((cold - RadarSignature::COLD_FACTOR).abs() < 1e-3);
+    sig.emissions = 1.;
+    assert!((sig.detection_range_multiplier() - 1.).abs() < 1e-3);
+    let small = RadarSignature::for_dimensions((TVec3::ONE * 2.).into());
+    assert!(small.detection_range_multiplier() < cold);
+    // seekers prefer the loud one
+    assert!(sig.intensity() > RadarSignature::for_dimensions((TVec3::ONE * 8.).into()).intensity());
+    assert!(sig.apparent(100.) < sig.apparent(25.));
+}
@@ -71,6 +71,9 @@ fn main() {
     let mut inspect_registry = bevy_inspector_egui::InspectableRegistry::default();
     inspect_registry
         .register_debug::<mind::sensors::CraftWeaponsIndex>()
+        .register_debug::<mind::sensors::Contacts>()
+        .register_debug::<mind::blackboard::Blackboard>()
+        .register_debug::<craft::arms::WeaponGroups>()
         .register_debug::<mind::player::CraftCamera>()
         .register_debug::<mind::flock::FlockMembers>()
         .register_debug::<mind::boid::steering::compose::Compose>()
@@ -369,7 +372,7 @@ fn setup_world(
                 mass / (4. * math::real::consts::PI * radius * radius),
             ) */
         };
-        commands
+        let obstacle_entt = commands
             .spawn()
             .insert(Name::new("single_ball"))
             .insert_bundle(PbrBundle {
@@ -391,7 +394,25 @@ fn setup_world(
             .insert(*craft::attire::OBSTACLE_COLLIDER_IGROUP)
             .insert(ColliderMassProperties::Density(density))
             // .insert(ColliderDebugRender::default())
-            .insert_bundle(bevy_mod_picking::PickableBundle::default());
+            .insert_bundle(bevy_mod_picking::PickableBundle::default())
+            // docked crafts are jointed to it
+            .insert(RigidBody::Fixed)
+            .id();
+        // on the face towards the origin
+        commands.entity(obstacle_entt).add_children(|parent| {
+            parent
+                .spawn()
+                .insert(Name::new("docking_port"))
+                .insert_bundle(SpatialBundle {
+                    transform: Transform::from_xyz(0., 0., -60.)
+                        .looking_at(TVec3::new(0., 0., 60.), TVec3::Y),
+                    ..default()
+                })
+                .insert(mind::boid::strategy::dock::DockingPort::new(
+                    obstacle_entt,
+                    100.,
+                ));
+        });
     }
 
     // spawn the box cage
@@ -515,8 +536,11 @@ fn setup_world(
                     proj_mtr: proj_mtr.clone(),
                     proj_shape: SharedShape::ball(0.5),
                     proj_velocity: TVec3::Z * -500.,
+                    proj_inherit_velocity: false,
                     proj_lifespan_secs: 3.,
                     proj_spawn_offset: TVec3::Z * -5.,
+                    proj_seeker: None,
+                    proj_blast: None,
                     proj_mass: ColliderMassProperties::Density(
                         0.25 / (4. * math::real::consts::PI * 0.5 * 0.5),
                     ),
@@ -528,6 +552,140 @@ fn setup_world(
         })
     };
 
+    let new_flare_dispenser: &dyn Fn(_) -> _ = {
+        let decoy_mesh = meshes.add(
+            shape::Icosphere {
+                radius: 0.5,
+                ..default()
+            }
+            .into(),
+        );
+        let decoy_mtr = materials.add(StandardMaterial {
+            base_color: Color::WHITE,
+            emissive: Color::ORANGE_RED * 20.,
+            unlit: true,
+            ..default()
+        });
+        &(move |boid_entt| {
+            craft::countermeasures::CountermeasureBundle::new(
+                craft::countermeasures::CountermeasureDispenser {
+                    boid_entt,
+                    decoy_signature: craft::stealth::RadarSignature::hot(640.),
+                    decoy_lifespan_secs: 4.,
+                    decoy_count: 3,
+                    eject_velocity: TVec3::Z * 30.,
+                    eject_spread_radians: math::real::consts::FRAC_PI_4,
+                    decoy_mesh: decoy_mesh.clone(),
+                    decoy_mtr: decoy_mtr.clone(),
+                    charges: 20,
+                },
+                craft::arms::WeaponActivationState::new_discrete(1.),
+            )
+        })
+    };
+
+    let new_point_defence_gun: &dyn Fn(_) -> _ = {
+        let proj_mesh = meshes.add(
+            shape::Icosphere {
+                radius: 0.25,
+                ..default()
+            }
+            .into(),
+        );
+        let proj_mtr = materials.add(StandardMaterial {
+            base_color: Color::WHITE,
+            emissive: Color::CYAN * 20.,
+            unlit: true,
+            ..default()
+        });
+        &(move |boid_entt| {
+            (
+                craft::arms::WeaponBundle::new(
+                    craft::arms::ProjectileWeapon {
+                        proj_damage: craft::attire::Damage {
+                            value: 10.,
+                            damage_type: craft::attire::DamageType::Kinetic,
+                        },
+                        proj_mesh: proj_mesh.clone(),
+                        proj_mtr: proj_mtr.clone(),
+                        proj_shape: SharedShape::ball(0.25),
+                        proj_velocity: TVec3::Z * -800.,
+                        proj_inherit_velocity: true,
+                        proj_lifespan_secs: 0.5,
+                        proj_spawn_offset: TVec3::Z * -2.,
+                        proj_seeker: None,
+                        proj_blast: None,
+                        proj_mass: ColliderMassProperties::Density(
+                            0.01 / (4. * math::real::consts::PI * 0.25 * 0.25),
+                        ),
+                    },
+                    boid_entt,
+                    // kept out of the weapon groups
+                    "point_defence_gun",
+                    craft::arms::WeaponActivationState::new_discrete(20.),
+                ),
+                craft::countermeasures::PointDefence {
+                    traverse_rate_radians: math::real::consts::TAU,
+                    range: 300.,
+                    target: None,
+                },
+            )
+        })
+    };
+
+    let new_turret_gun: &dyn Fn(_) -> _ = {
+        let proj_mesh = meshes.add(
+            shape::Icosphere {
+                radius: 0.5,
+                ..default()
+            }
+            .into(),
+        );
+        let proj_mtr = materials.add(StandardMaterial {
+            base_color: Color::WHITE,
+            emissive: Color::RED * 20.,
+            unlit: true,
+            ..default()
+        });
+        &(move |boid_entt| {
+            (
+                craft::arms::WeaponBundle::new(
+                    craft::arms::ProjectileWeapon {
+                        proj_damage: craft::attire::Damage {
+                            value: 50.,
+                            damage_type: craft::attire::DamageType::Kinetic,
+                        },
+                        proj_mesh: proj_mesh.clone(),
+                        proj_mtr: proj_mtr.clone(),
+                        proj_shape: SharedShape::ball(0.5),
+                        proj_velocity: TVec3::Z * -400.,
+                        proj_inherit_velocity: true,
+                        proj_lifespan_secs: 2.,
+                        proj_spawn_offset: TVec3::Z * -3.,
+                        proj_seeker: None,
+                        proj_blast: Some(craft::arms::Blast {
+                            radius: 10.,
+                            damage: craft::attire::Damage {
+                                value: 50.,
+                                damage_type: craft::attire::DamageType::Explosion,
+                            },
+                        }),
+                        proj_mass: ColliderMassProperties::Density(
+                            0.25 / (4. * math::real::consts::PI * 0.5 * 0.5),
+                        ),
+                    },
+                    boid_entt,
+                    // kept out of the weapon groups
+                    "turret_gun",
+                    craft::arms::WeaponActivationState::new_discrete(2.),
+                ),
+                craft::arms::Turret {
+                    traverse_rate_radians: math::real::consts::FRAC_PI_2,
+                },
+            )
+        })
+    };
+
     use mind::*;
     // spawn the player craft
     let _player_craft_id = {
@@ -546,6 +704,8 @@ fn setup_world(
                     ),
                     ..default()
                 },
+                weapon_groups: craft::arms::WeaponGroups::default()
+                    .with(craft::arms::WeaponGroups::PRIMARY, "kinetic_cannon"),
                 ..craft::CraftBundle::new(
                     craft::engine::EngineConfig { ..default() },
                     (TVec3::ONE * 8.).into(),
@@ -641,6 +801,10 @@ fn setup_world(
                             ),
                             ..default()
                         },
+                        weapon_groups: craft::arms::WeaponGroups::default()
+                            .with(craft::arms::WeaponGroups::PRIMARY, "kinetic_cannon"),
+                        // hostile to the player's
+                        faction: craft::Faction(1),
                         ..craft::CraftBundle::new(
                             craft::engine::EngineConfig { ..default() },
                             (TVec3::ONE * 8.).into(),
@@ -654,7 +818,7 @@ fn setup_world(
                         /*directive: boid::BoidMindDirective::AttackPresue {
                             param: boid::strategy::attack_persue::AttackPersue {
                                 attacking_range: 300.,
-                                quarry_rb: _player_craft_id.handle()
+                                quarry_rb: Some(_player_craft_id.handle())
                             }
                         },*/
                         ..default()
@@ -678,6 +842,32 @@ fn setup_world(
                             ..default()
                         });
 
+                        parent
+                            .spawn()
+                            .insert_bundle(new_flare_dispenser(parent_entt))
+                            .insert_bundle(SpatialBundle::default());
+
+                        {
+                            let (gun, turret) = new_turret_gun(parent_entt);
+                            parent
+                                .spawn()
+                                .insert_bundle(gun)
+                                .insert(turret)
+                                .insert_bundle(SpatialBundle {
+                                    transform: Transform::from_translation(TVec3::Y * -4.),
+                                    ..default()
+                                });
+                        }
+                        {
+                            let (gun, pd) = new_point_defence_gun(parent_entt);
+                            parent.spawn().insert_bundle(gun).insert(pd).insert_bundle(
+                                SpatialBundle {
+                                    transform: Transform::from_translation(TVec3::Y * 4.),
+                                    ..default()
+                                },
+                            );
+                        }
+
                         parent
                             .spawn()
                             .insert_bundle(new_kinetic_cannon(parent_entt))
@@ -3,10 +3,15 @@ use deps::*;
 use bevy::prelude::*;
 use bevy_inspector_egui::RegisterInspectable;
 
+use registry::MindAppExt;
+
+pub mod blackboard;
 pub mod boid;
 pub mod flock;
 pub mod guy;
+pub mod navigation;
 pub mod player;
+pub mod registry;
 pub mod sensors;
 
 /* pub mod tribe {
@@ -48,7 +53,32 @@ impl Plugin for MindPlugin {
             .init_resource::<sensors::SteeringRoutineCrossRefIndex>()
             .add_system_to_stage(
                 CoreStage::PreUpdate,
-                sensors::craft_routine_index_butler.after(ComposeButler),
+                sensors::craft_routine_index_butler
+                    .before(ComposeButler)
+                    .before(BoidStrategyButler),
+            )
+            .init_resource::<sensors::spatial::SpatialIndex>()
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                sensors::spatial::rebuild.before(BoidStrategyButler),
+            )
+            .init_resource::<sensors::los::LineOfSightCache>()
+            .add_system_to_stage(CoreStage::PreUpdate, sensors::los::line_of_sight_janitor)
+            .init_resource::<navigation::NavigationGrid>()
+            .add_system_to_stage(CoreStage::PreUpdate, navigation::navigation_grid_janitor)
+            // blackboard systems
+            .add_system_to_stage(CoreStage::PreUpdate, blackboard::blackboard_janitor)
+            .add_system(blackboard::damage_stimuli.before(BoidStrategy))
+            .add_system(
+                blackboard::flock_blackboard_relay
+                    .after(blackboard::damage_stimuli)
+                    .before(FlockStrategy),
+            )
+            .add_system(
+                sensors::radar_sweep
+                    .before(BoidStrategy)
+                    .before(SteeringRoutine)
+                    .before(CraftBoidStrategyOutputMgr),
             )
             // flock formation systems
             .add_system_to_stage(
@@ -77,23 +107,47 @@ impl Plugin for MindPlugin {
                     .with_system(flock::strategy::cas::update),
             )
             // boid strategy systems
-            .add_system_set_to_stage(
-                CoreStage::PreUpdate,
-                SystemSet::new()
-                    .label(BoidStrategyButler)
-                    .after(FlockChangeListener)
-                    .with_system(boid::strategy::attack_persue::butler)
-                    .with_system(boid::strategy::run_circuit::butler)
-                    .with_system(boid::strategy::form::butler)
-                    .with_system(boid::strategy::custom::butler),
+            .register_boid_strategy::<boid::strategy::attack_persue::AttackPersue, _, _>(
+                "attack_persue",
+                boid::strategy::attack_persue::butler,
+                boid::strategy::attack_persue::update,
             )
-            .add_system_set(
-                SystemSet::new()
-                    .label(BoidStrategy)
-                    .with_system(boid::strategy::attack_persue::update)
-                    .with_system(boid::strategy::form::update)
-                    .with_system(boid::strategy::run_circuit::update),
+            .register_boid_strategy::<boid::strategy::run_circuit::RunCircuit, _, _>(
+                "run_circuit",
+                boid::strategy::run_circuit::butler,
+                boid::strategy::run_circuit::update,
+            )
+            .register_boid_strategy::<boid::strategy::form::Form, _, _>(
+                "form",
+                boid::strategy::form::butler,
+                boid::strategy::form::update,
+            )
+            .register_boid_strategy::<boid::strategy::dock::Dock, _, _>(
+                "dock",
+                boid::strategy::dock::butler,
+                boid::strategy::dock::update,
+            )
+            .register_boid_strategy::<boid::strategy::behavior_tree::BehaviorTree, _, _>(
+                "behavior_tree",
+                boid::strategy::behavior_tree::butler,
+                boid::strategy::behavior_tree::update,
+            )
+            .register_boid_strategy_butler::<boid::strategy::custom::Custom, _>(
+                "custom",
+                boid::strategy::custom::butler,
             )
+            .add_system(
+                boid::targeting::target_selection
+                    .after(sensors::radar_sweep)
+                    .before(BoidStrategy),
+            )
+            .add_system(boid::defence::incoming_projectile_sensor.after(sensors::radar_sweep))
+            .add_system(
+                boid::defence::deploy_countermeasures
+                    .after(boid::defence::incoming_projectile_sensor),
+            )
+            .add_system(boid::defence::point_defence.after(sensors::radar_sweep))
+            .add_system(boid::targeting::turret_mind.after(boid::targeting::target_selection))
             .add_system(
                 boid::strategy::craft_boid_strategy_output_mgr
                     .label(CraftBoidStrategyOutputMgr)
@@ -104,17 +158,62 @@ impl Plugin for MindPlugin {
                 CoreStage::PreUpdate,
                 boid::steering::compose::butler.label(ComposeButler), // .before(SteeringRoutineButler),
             )
-            .add_system_set(
-                SystemSet::new()
-                    .label(SteeringRoutine)
-                    .with_system(boid::steering::intercept::update)
-                    .with_system(boid::steering::fly_with_flock::update)
-                    .with_system(boid::steering::avoid_collision::update)
-                    .with_system(boid::steering::arrive::update)
-                    .with_system(boid::steering::player::update)
-                    .with_system(boid::steering::face::update)
-                    .with_system(boid::steering::closure::update)
-                    .with_system(boid::steering::seek::update),
+            // steering routines
+            .register_steering_routine::<boid::steering::intercept::Intercept, _>(
+                "intercept",
+                boid::steering::intercept::update,
+            )
+            .register_steering_routine::<boid::steering::fly_with_flock::FlyWithFlock, _>(
+                "fly_with_flock",
+                boid::steering::fly_with_flock::update,
+            )
+            .register_steering_routine::<boid::steering::avoid_collision::AvoidCollision, _>(
+                "avoid_collision",
+                boid::steering::avoid_collision::update,
+            )
+            .register_steering_routine::<boid::steering::arrive::Arrive, _>(
+                "arrive",
+                boid::steering::arrive::update,
+            )
+            .register_steering_routine::<boid::steering::player::Player, _>(
+                "player",
+                boid::steering::player::update,
+            )
+            .register_steering_routine::<boid::steering::face::Face, _>(
+                "face",
+                boid::steering::face::update,
+            )
+            .register_steering_routine::<boid::steering::closure::Closure, _>(
+                "closure",
+                boid::steering::closure::update,
+            )
+            .register_steering_routine::<boid::steering::seek::Seek, _>(
+                "seek",
+                boid::steering::seek::update,
+            )
+            .register_steering_routine::<boid::steering::follow_path::FollowPath, _>(
+                "follow_path",
+                boid::steering::follow_path::update,
+            )
+            .register_steering_routine::<boid::steering::avoid_crafts::AvoidCrafts, _>(
+                "avoid_crafts",
+                boid::steering::avoid_crafts::update,
+            )
+            .register_steering_routine::<boid::steering::orbit::Orbit, _>(
+                "orbit",
+                boid::steering::orbit::update,
+            )
+            .register_steering_routine::<boid::steering::evade::Evade, _>(
+                "evade",
+                boid::steering::evade::update,
+            )
+            .register_steering_routine::<boid::steering::jink::Jink, _>(
+                "jink",
+                boid::steering::jink::update,
+            )
+            .register_steering_routine::<boid::steering::follow_spline::FollowSpline, _>(
+                "follow_spline",
+                boid::steering::follow_spline::update,
             )
             .add_system(boid::steering::compose::update.after(SteeringRoutine))
             .add_system_to_stage(
@@ -143,6 +242,25 @@ impl Plugin for MindPlugin {
                 player::player_mind.before(boid::boid_mind),
             )
             .add_system_to_stage(CoreStage::PostUpdate, boid::boid_mind)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                boid::steering::routine_garbage_collector.after(boid::boid_mind),
+            )
+            // strategies despawned in PostUpdate only show up as removed after it
+            .add_system_to_stage(CoreStage::Last, boid::strategy::dock::janitor)
+            .add_asset::<boid::strategy::custom::description::CompositionAsset>()
+            .init_asset_loader::<boid::strategy::custom::description::CompositionLoader>()
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                boid::strategy::custom::description::composition_reloader.before(boid::boid_mind),
+            )
+            .add_asset::<boid::strategy::behavior_tree::description::BehaviorTreeAsset>()
+            .init_asset_loader::<boid::strategy::behavior_tree::description::BehaviorTreeLoader>()
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                boid::strategy::behavior_tree::description::behavior_tree_reloader
+                    .before(boid::boid_mind),
+            )
             // types
             .register_inspectable::<boid::strategy::CurrentBoidStrategy>()
             .register_inspectable::<flock::strategy::CurrentFlockStrategy>()
@@ -151,6 +269,9 @@ impl Plugin for MindPlugin {
             .register_inspectable::<player::CraftCamera>()
             .register_inspectable::<flock::strategy::cas::CASState>()
             .register_inspectable::<boid::BoidMindConfig>()
+            .register_inspectable::<sensors::Radar>()
+            .register_inspectable::<boid::targeting::TargetingConfig>()
+            .register_inspectable::<boid::targeting::CurrentTarget>()
             .register_inspectable::<boid::steering::LinearRoutineOutput>()
             .register_inspectable::<boid::steering::AngularRoutineOutput>();
     }
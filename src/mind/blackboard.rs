@@ -0,0 +1,335 @@
+use deps::*;
+
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashMap};
+use std::any::{Any, TypeId};
+
+use crate::craft::{arms::CraftWeapon, attire::ProjectileDamageEvent};
+use crate::mind::flock::FlockMembers;
+
+/// A piece of knowledge that can be put up on a [`Blackboard`].
+/// Equal facts overwrite each other.
+pub trait Fact: Any + Send + Sync + std::fmt::Debug + PartialEq {}
+
+trait DynFact: Any + Send + Sync + std::fmt::Debug {
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: Fact> DynFact for T {
+    #[inline]
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Where a fact came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StimulusSource {
+    Sensor,
+    Damage,
+    Order,
+    /// Relayed from another blackboard.
+    Relay,
+}
+
+#[derive(Debug)]
+pub struct BlackboardEntry {
+    fact: Box<dyn DynFact>,
+    pub source: StimulusSource,
+    pub written_secs: f64,
+    pub expires_secs: f64,
+}
+
+impl BlackboardEntry {
+    #[inline]
+    pub fn fact<F: Fact>(&self) -> Option<&F> {
+        self.fact.as_any().downcast_ref()
+    }
+}
+
+/// Facts shared amongst all the decision makers of a boid or a flock.
+/// Entries expire after their time to live. Reads skip expired entries even if
+/// the janitor hasn't gotten around to them yet.
+/// Boid and flock mind component
+#[derive(Debug, Component, Default)]
+pub struct Blackboard {
+    entries: HashMap<TypeId, SVec<[BlackboardEntry; 2]>>,
+}
+
+impl Blackboard {
+    /// Time to live for entries that don't expire.
+    pub const FOREVER: f64 = f64::INFINITY;
+
+    pub fn write<F: Fact>(
+        &mut self,
+        fact: F,
+        source: StimulusSource,
+        now_secs: f64,
+        ttl_secs: f64,
+    ) {
+        let entries = self.entries.entry(TypeId::of::<F>()).or_default();
+        let existing = entries
+            .iter()
+            .position(|entry| entry.fact::<F>() == Some(&fact));
+        let entry = BlackboardEntry {
+            fact: Box::new(fact),
+            source,
+            written_secs: now_secs,
+            expires_secs: now_secs + ttl_secs,
+        };
+        match existing {
+            Some(ii) => entries[ii] = entry,
+            None => entries.push(entry),
+        }
+    }
+
+    /// The unexpired entries of the fact type.
+    pub fn entries<F: Fact>(&self, now_secs: f64) -> impl Iterator<Item = &BlackboardEntry> {
+        self.entries
+            .get(&TypeId::of::<F>())
+            .into_iter()
+            .flat_map(|entries| entries.iter())
+            .filter(move |entry| entry.expires_secs > now_secs)
+    }
+
+    #[inline]
+    pub fn read<F: Fact>(&self, now_secs: f64) -> impl Iterator<Item = &F> {
+        self.entries::<F>(now_secs).filter_map(|entry| entry.fact())
+    }
+
+    #[inline]
+    pub fn first<F: Fact>(&self, now_secs: f64) -> Option<&F> {
+        self.read::<F>(now_secs).next()
+    }
+
+    #[inline]
+    pub fn contains<F: Fact>(&self, now_secs: f64) -> bool {
+        self.entries::<F>(now_secs).next().is_some()
+    }
+
+    /// Removes all facts of the type.
+    pub fn erase<F: Fact>(&mut self) {
+        self.entries.remove(&TypeId::of::<F>());
+    }
+
+    pub fn forget_expired(&mut self, now_secs: f64) {
+        for entries in self.entries.values_mut() {
+            entries.retain(|entry| entry.expires_secs > now_secs);
+        }
+        self.entries.retain(|_, entries| !entries.is_empty());
+    }
+}
+
+/// Helper for systems that only read blackboards.
+#[derive(SystemParam)]
+pub struct BlackboardReader<'w, 's> {
+    boards: Query<'w, 's, &'static Blackboard>,
+    time: Res<'w, Time>,
+}
+
+impl BlackboardReader<'_, '_> {
+    #[inline]
+    pub fn get(&self, entt: Entity) -> Option<&Blackboard> {
+        self.boards.get(entt).ok()
+    }
+
+    #[inline]
+    pub fn contains<F: Fact>(&self, entt: Entity) -> bool {
+        self.get(entt)
+            .map(|board| board.contains::<F>(self.time.seconds_since_startup()))
+            .unwrap_or(false)
+    }
+}
+
+/// Helper for systems that write to blackboards.
+#[derive(SystemParam)]
+pub struct BlackboardWriter<'w, 's> {
+    boards: Query<'w, 's, &'static mut Blackboard>,
+    time: Res<'w, Time>,
+}
+
+impl BlackboardWriter<'_, '_> {
+    /// Returns false if the entity has no [`Blackboard`].
+    pub fn write<F: Fact>(
+        &mut self,
+        entt: Entity,
+        fact: F,
+        source: StimulusSource,
+        ttl_secs: f64,
+    ) -> bool {
+        let now_secs = self.time.seconds_since_startup();
+        match self.boards.get_mut(entt) {
+            Ok(mut board) => {
+                board.write(fact, source, now_secs, ttl_secs);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, entt: Entity) -> Option<&Blackboard> {
+        self.boards.get(entt).ok()
+    }
+}
+
+/// The boid took damage.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnderFire {
+    /// The craft responsible if known.
+    pub attacker: Option<Entity>,
+}
+impl Fact for UnderFire {}
+
+impl UnderFire {
+    pub const TTL_SECS: f64 = 5.;
+}
+
+/// Some member of the flock's [`UnderFire`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemberUnderFire {
+    pub member: Entity,
+    pub attacker: Option<Entity>,
+}
+impl Fact for MemberUnderFire {}
+
+/// The boid's been ordered to attack.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttackOrder {
+    pub quarry: Entity,
+}
+impl Fact for AttackOrder {}
+
+pub(super) fn blackboard_janitor(mut boards: Query<&mut Blackboard>, time: Res<Time>) {
+    let now_secs = time.seconds_since_startup();
+    for mut board in boards.iter_mut() {
+        board.forget_expired(now_secs);
+    }
+}
+
+pub(super) fn damage_stimuli(
+    mut pd_events: EventReader<ProjectileDamageEvent>,
+    rapier: Res<bevy_rapier3d::prelude::RapierContext>,
+    wpns: Query<&CraftWeapon>,
+    mut writer: BlackboardWriter,
+) {
+    for event in pd_events.iter() {
+        let craft = match rapier.collider_parent(event.attire_entt) {
+            Some(craft) => craft,
+            None => continue,
+        };
+        writer.write(
+            craft,
+            UnderFire {
+                attacker: wpns
+                    .get(event.ixn_event.projectile.source_wpn)
+                    .ok()
+                    .map(|wpn| wpn.boid_entt()),
+            },
+            StimulusSource::Damage,
+            UnderFire::TTL_SECS,
+        );
+    }
+}
+
+/// Copies facts from the members' blackboards onto their flock's.
+pub(super) fn flock_blackboard_relay(
+    flocks: Query<(Entity, &FlockMembers)>,
+    mut boards: Query<&mut Blackboard>,
+    time: Res<Time>,
+    mut relayed: Local<Vec<(MemberUnderFire, f64)>>,
+) {
+    let now_secs = time.seconds_since_startup();
+    for (flock_entt, members) in flocks.iter() {
+        for member in members.iter() {
+            if let Ok(board) = boards.get(*member) {
+                relayed.extend(board.entries::<UnderFire>(now_secs).filter_map(|entry| {
+                    entry.fact::<UnderFire>().map(|fact| {
+                        (
+                            MemberUnderFire {
+                                member: *member,
+                                attacker: fact.attacker,
+                            },
+                            entry.expires_secs,
+                        )
+                    })
+                }));
+            }
+        }
+        if let Ok(mut board) = boards.get_mut(flock_entt) {
+            for (fact, expires_secs) in relayed.drain(..) {
+                board.write(
+                    fact,
+                    StimulusSource::Relay,
+                    now_secs,
+                    expires_secs - now_secs,
+                );
+            }
+        } else {
+            relayed.clear();
+        }
+    }
+}
+
+#[test]
+fn blackboard_test() {
+    let mut board = Blackboard::default();
+    let (a, b) = (Entity::from_raw(0), Entity::from_raw(1));
+    board.write(
+        UnderFire { attacker: Some(a) },
+        StimulusSource::Damage,
+        0.,
+        5.,
+    );
+    board.write(
+        UnderFire { attacker: Some(b) },
+        StimulusSource::Damage,
+        1.,
+        1.,
+    );
+    assert_eq!(board.read::<UnderFire>(1.5).count(), 2);
+    assert!(!board.contains::<AttackOrder>(1.5));
+
+    // equal facts overwrite each other
+    board.write(
+        UnderFire { attacker: Some(a) },
+        StimulusSource::Damage,
+        1.5,
+        5.,
+    );
+    assert_eq!(board.read::<UnderFire>(1.5).count(), 2);
+    assert_eq!(
+        board
+            .entries::<UnderFire>(1.5)
+            .find(|entry| entry.fact::<UnderFire>() == Some(&UnderFire { attacker: Some(a) }))
+            .map(|entry| entry.expires_secs),
+        Some(6.5)
+    );
+
+    // expired facts can't be read before they're forgotten
+    assert_eq!(
+        board.read::<UnderFire>(3.).collect::<Vec<_>>(),
+        vec![&UnderFire { attacker: Some(a) }]
+    );
+    assert!(board.contains::<UnderFire>(6.));
+    assert!(!board.contains::<UnderFire>(6.5));
+    assert!(board.first::<UnderFire>(6.5).is_none());
+
+    board.forget_expired(3.);
+    assert_eq!(
+        board.entries.get(&TypeId::of::<UnderFire>()).unwrap().len(),
+        1
+    );
+    board.forget_expired(6.5);
+    assert!(board.entries.is_empty());
+
+    board.write(
+        AttackOrder { quarry: b },
+        StimulusSource::Order,
+        0.,
+        Blackboard::FOREVER,
+    );
+    assert_eq!(
+        board.first::<AttackOrder>(1e9),
+        Some(&AttackOrder { quarry: b })
+    );
+}
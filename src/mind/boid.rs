@@ -4,23 +4,38 @@ use bevy::prelude::*;
 use bevy_inspector_egui::Inspectable;
 use educe::Educe;
 
-use crate::{craft::*, math::*, mind::sensors::*};
+use crate::{
+    craft::*,
+    math::*,
+    mind::{blackboard, sensors::*},
+};
 
 use steering::*;
 use strategy::*;
 
+pub mod defence;
 pub mod steering;
 pub mod strategy;
+pub mod targeting;
 
 #[derive(Debug, Clone, Inspectable, Component)]
 pub struct BoidMindConfig {
     pub angular_input_multiplier: TReal,
+    /// How far off the lead point a weapon can be pointing and still be fired.
+    pub aim_tolerance_radians: TReal,
+    /// How soon an incoming projectile has to be to trigger countermeasures.
+    pub countermeasure_warning_secs: TReal,
+    /// How close an incoming projectile has to pass to be considered a threat.
+    pub countermeasure_miss_radius: TReal,
 }
 
 impl Default for BoidMindConfig {
     fn default() -> Self {
         Self {
             angular_input_multiplier: 10.,
+            aim_tolerance_radians: 1.5 * (real::consts::PI / 180.),
+            countermeasure_warning_secs: 1.5,
+            countermeasure_miss_radius: 25.,
         }
     }
 }
@@ -34,6 +49,13 @@ pub struct BoidMindBundle {
     pub cur_routine: CurrentSteeringRoutine,
     pub directive: BoidMindDirective,
 
+    // perception
+    pub radar: Radar,
+    pub contacts: Contacts,
+    pub blackboard: blackboard::Blackboard,
+    pub targeting: targeting::TargetingConfig,
+    pub target: targeting::CurrentTarget,
+
     // indices
     pub routine_index: SteeringRoutinesIndex,
     pub wpn_index: CraftWeaponsIndex,
@@ -62,6 +84,18 @@ pub enum BoidMindDirective {
     AttackPresue {
         param: strategy::attack_persue::AttackPersue,
     },
+    /// Docks at or undocks from a [`strategy::dock::DockingPort`].
+    Dock {
+        param: strategy::dock::Dock,
+    },
+    /// Composes the routines as described by the asset.
+    Custom {
+        composition: Handle<strategy::custom::description::CompositionAsset>,
+    },
+    /// Runs the tree described by the asset.
+    BehaviorTree {
+        tree: Handle<strategy::behavior_tree::description::BehaviorTreeAsset>,
+    },
 }
 
 pub fn boid_mind(
@@ -73,156 +107,82 @@ pub fn boid_mind(
             &mut CurrentBoidStrategy,
             &engine::EngineConfig,
             &CraftDimensions,
+            Option<&mut blackboard::Blackboard>,
         ),
         Changed<BoidMindDirective>,
     >,
+    compositions: Res<Assets<strategy::custom::description::CompositionAsset>>,
+    trees: Res<Assets<strategy::behavior_tree::description::BehaviorTreeAsset>>,
+    time: Res<Time>,
 ) {
-    for (boid_entt, directive, mut cur_stg, engine_config, dim) in boids.iter_mut() {
+    use strategy::custom::description::*;
+    for (boid_entt, directive, mut cur_stg, engine_config, dim, board) in boids.iter_mut() {
         if let Some(cur_stg) = cur_stg.strategy.take() {
             commands.entity(cur_stg).despawn_recursive();
         }
-        use BoidMindDirective::*;
-        cur_stg.strategy = match directive {
-            Nil => None,
-            KeepGoingForward => {
-                let raycast_toi_modifier = dim.max_element();
-                let cast_shape_radius = raycast_toi_modifier * 0.5;
-                let avoid_collision: Box<strategy::custom::RoutineSpawner> =
-                    Box::new(move |commands, strategy_entt, _| {
-                        commands.entity(strategy_entt).add_children(|p| {
-                            p.spawn()
-                                .insert_bundle(steering::avoid_collision::Bundle::new(
-                                    steering::avoid_collision::AvoidCollision::new(
-                                        cast_shape_radius,
-                                        raycast_toi_modifier,
-                                    ),
-                                    boid_entt,
-                                    default(),
-                                ))
-                                .id()
-                        })
-                    });
-                let closure: Box<strategy::custom::RoutineSpawner> =
-                    Box::new(move |commands, strategy_entt, strategy| {
-                        commands.entity(strategy_entt).add_children(|p| {
-                            p.spawn()
-                                .insert_bundle(steering::closure::Bundle::new(
-                                    steering::closure::Closure {
-                                        closure: Box::new(|xform, _, _| {
-                                            (
-                                                LinearRoutineOutput::Dir(xform.forward()),
-                                                // LinearRoutineOutput::Dir(TVec3::Z),
-                                                steering::look_to(-TVec3::Z).into(),
-                                            )
-                                        }),
-                                    },
-                                    strategy.boid_entt(),
-                                ))
-                                .id()
-                        })
-                    });
-                Some(commands.entity(boid_entt).add_children(|p| {
-                    p.spawn()
-                        .insert_bundle(strategy::custom::Bundle::new(
-                            strategy::custom::Custom::new(
-                                strategy::custom::Composition::PriorityOverride {
-                                    routines: smallvec::smallvec![avoid_collision, closure],
-                                },
-                            ),
-                            boid_entt,
-                        ))
-                        .id()
-                }))
+        // let the rest of the mind know about the orders
+        if let Some(mut board) = board {
+            board.erase::<blackboard::AttackOrder>();
+            if let BoidMindDirective::AttackPresue {
+                param:
+                    strategy::attack_persue::AttackPersue {
+                        quarry_rb: Some(quarry),
+                        ..
+                    },
+            } = directive
+            {
+                board.write(
+                    blackboard::AttackOrder { quarry: *quarry },
+                    blackboard::StimulusSource::Order,
+                    time.seconds_since_startup(),
+                    blackboard::Blackboard::FOREVER,
+                );
             }
-            /* BoidMindDirective::Custom { composition } => Some(
-                commands.entity(boid_entt).add_children(|p|
-                    p
-                    .spawn()
+        }
+        let ctx = DescriptionContext {
+            boid_entt,
+            dimensions: dim.0,
+            accel_limit: engine_config.actual_accel_limit(),
+            linvel_limit: engine_config.linvel_limit,
+        };
+        let spawn_custom = |commands: &mut Commands, composition: Composition| {
+            commands.entity(boid_entt).add_children(|p| {
+                p.spawn()
                     .insert_bundle(strategy::custom::Bundle::new(
-                        strategy::custom::Custom::new(composition.take().unwrap_or_log()),
+                        strategy::custom::Custom::new(composition),
                         boid_entt,
                     ))
-                    .id(),
-            )
-            ), */
-            SlaveToPlayerControl => {
-                let player: Box<strategy::custom::RoutineSpawner> =
-                    Box::new(move |commands, strategy_entt, _| {
-                        commands.entity(strategy_entt).add_children(|p| {
-                            p.spawn()
-                                .insert_bundle(steering::player::Bundle::new(
-                                    steering::player::Player,
-                                    boid_entt,
-                                ))
-                                .id()
-                        })
-                    });
-                Some(commands.entity(boid_entt).add_children(|p| {
-                    p.spawn()
-                        .insert_bundle(strategy::custom::Bundle::new(
-                            strategy::custom::Custom::new(strategy::custom::Composition::Single {
-                                routine_spawner: player,
-                            }),
-                            boid_entt,
-                        ))
-                        .id()
-                }))
-            }
-            HoldPosition { pos } => {
-                let pos = *pos;
-                let accel_limit = engine_config.actual_accel_limit();
-                let raycast_toi_modifier = dim.max_element();
-                let cast_shape_radius = raycast_toi_modifier * 0.5;
-                let avoid_collision: Box<strategy::custom::RoutineSpawner> =
-                    Box::new(move |commands, strategy_entt, _| {
-                        commands.entity(strategy_entt).add_children(|p| {
-                            p.spawn()
-                                .insert_bundle(steering::avoid_collision::Bundle::new(
-                                    steering::avoid_collision::AvoidCollision::new(
-                                        cast_shape_radius,
-                                        raycast_toi_modifier,
-                                    ),
-                                    boid_entt,
-                                    default(),
-                                ))
-                                .id()
-                        })
-                    });
-                let arrive: Box<strategy::custom::RoutineSpawner> =
-                    Box::new(move |commands, strategy_entt, _| {
-                        commands.entity(strategy_entt).add_children(|p| {
-                            p.spawn()
-                                .insert_bundle(steering::arrive::Bundle::new(
-                                    steering::arrive::Arrive {
-                                        target: arrive::Target::Vector {
-                                            at_pos: pos,
-                                            pos_linvel: default(),
-                                            // with_linvel: default(),
-                                            with_speed: 0.,
-                                        },
-                                        arrival_tolerance: 5.,
-                                        deceleration_radius: None,
-                                        avail_accel: accel_limit,
-                                    },
-                                    boid_entt,
-                                ))
-                                .id()
-                        })
-                    });
-
-                Some(commands.entity(boid_entt).add_children(|p| {
-                    p.spawn()
-                        .insert_bundle(strategy::custom::Bundle::new(
-                            strategy::custom::Custom::new(
-                                strategy::custom::Composition::PriorityOverride {
-                                    routines: smallvec::smallvec![avoid_collision, arrive],
-                                },
-                            ),
-                            boid_entt,
-                        ))
-                        .id()
-                }))
-            }
+                    .id()
+            })
+        };
+        use strategy::custom::Composition;
+        use BoidMindDirective::*;
+        cur_stg.strategy = match directive {
+            Nil => None,
+            KeepGoingForward => Some(spawn_custom(
+                &mut commands,
+                CompositionDesc::PriorityOverride(vec![
+                    RoutineDesc::AvoidCollision,
+                    RoutineDesc::GoForward,
+                ])
+                .composition(ctx),
+            )),
+            SlaveToPlayerControl => Some(spawn_custom(
+                &mut commands,
+                CompositionDesc::Single(RoutineDesc::Player).composition(ctx),
+            )),
+            HoldPosition { pos } => Some(spawn_custom(
+                &mut commands,
+                CompositionDesc::PriorityOverride(vec![
+                    RoutineDesc::AvoidCollision,
+                    RoutineDesc::Arrive {
+                        at_pos: pos.to_array(),
+                        with_linvel: default(),
+                        arrival_tolerance: 5.,
+                    },
+                ])
+                .composition(ctx),
+            )),
             JoinFomation { formation } => {
                 let formation = *formation;
                 Some(commands.entity(boid_entt).add_children(|p| {
@@ -236,24 +196,8 @@ pub fn boid_mind(
                 }))
             }
             FlyWithFlockCAS { param } => {
+                // the flock's not something that can be described
                 let param = param.clone();
-                let raycast_toi_modifier = dim.max_element();
-                let cast_shape_radius = raycast_toi_modifier * 0.5;
-                let avoid_collision: Box<strategy::custom::RoutineSpawner> =
-                    Box::new(move |commands, strategy_entt, _| {
-                        commands.entity(strategy_entt).add_children(|p| {
-                            p.spawn()
-                                .insert_bundle(steering::avoid_collision::Bundle::new(
-                                    steering::avoid_collision::AvoidCollision::new(
-                                        cast_shape_radius,
-                                        raycast_toi_modifier,
-                                    ),
-                                    boid_entt,
-                                    default(),
-                                ))
-                                .id()
-                        })
-                    });
                 let fly_with_flock: Box<strategy::custom::RoutineSpawner> =
                     Box::new(move |commands, strategy_entt, _| {
                         commands.entity(strategy_entt).add_children(|p| {
@@ -264,19 +208,15 @@ pub fn boid_mind(
                                 .id()
                         })
                     });
-
-                Some(commands.entity(boid_entt).add_children(|p| {
-                    p.spawn()
-                        .insert_bundle(strategy::custom::Bundle::new(
-                            strategy::custom::Custom::new(
-                                strategy::custom::Composition::PriorityOverride {
-                                    routines: smallvec::smallvec![avoid_collision, fly_with_flock],
-                                },
-                            ),
-                            boid_entt,
-                        ))
-                        .id()
-                }))
+                Some(spawn_custom(
+                    &mut commands,
+                    Composition::PriorityOverride {
+                        routines: smallvec::smallvec![
+                            RoutineDesc::AvoidCollision.spawner(ctx),
+                            fly_with_flock
+                        ],
+                    },
+                ))
             }
             RunCircuit { param } => Some(commands.entity(boid_entt).add_children(|p| {
                 p.spawn()
@@ -296,6 +236,38 @@ pub fn boid_mind(
                     ))
                     .id()
             })),
+            Dock { param } => Some(commands.entity(boid_entt).add_children(|p| {
+                p.spawn()
+                    .insert_bundle(strategy::dock::Bundle::new(
+                        param.clone(),
+                        boid_entt,
+                        default(),
+                    ))
+                    .id()
+            })),
+            Custom { composition } => match compositions.get(composition) {
+                Some(asset) => Some(spawn_custom(&mut commands, asset.0.composition(ctx))),
+                None => {
+                    // the reloader will get back to it once it's loaded
+                    tracing::debug!(?composition, "composition not loaded yet");
+                    None
+                }
+            },
+            BehaviorTree { tree } => match trees.get(tree) {
+                Some(asset) => Some(commands.entity(boid_entt).add_children(|p| {
+                    p.spawn()
+                        .insert_bundle(strategy::behavior_tree::Bundle::new(
+                            strategy::behavior_tree::BehaviorTree::new(&asset.0),
+                            boid_entt,
+                            default(),
+                        ))
+                        .id()
+                })),
+                None => {
+                    tracing::debug!(?tree, "behavior tree not loaded yet");
+                    None
+                }
+            },
         }
     }
 }
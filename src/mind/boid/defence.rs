@@ -0,0 +1,274 @@
+use deps::*;
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_rapier3d::prelude::*;
+
+use crate::craft::{arms::*, countermeasures::*, Faction};
+use crate::math::*;
+use crate::mind::*;
+
+/// The time till and the distance at the closest approach of a body at `rel_pos` moving
+/// at `rel_vel` relative to the origin.
+/// `None` if it's not closing in.
+#[inline]
+pub fn closest_approach(rel_pos: TVec3, rel_vel: TVec3) -> Option<(TReal, TReal)> {
+    let closing = -rel_pos.dot(rel_vel);
+    let rel_speed_squared = rel_vel.length_squared();
+    if closing <= 0. || rel_speed_squared < TReal::EPSILON {
+        return None;
+    }
+    let time = closing / rel_speed_squared;
+    Some((time, (rel_pos + (rel_vel * time)).length()))
+}
+
+/// Projectiles from friendly crafts, see [`boid::targeting::is_friendly`], aren't hostile.
+/// Ones from weapons that've since been despawned are considered hostile.
+#[inline]
+fn is_hostile(
+    proj: &Projectile,
+    faction: Faction,
+    flock: Option<&Entity>,
+    wpns: &Query<&CraftWeapon>,
+    factions: &Query<&Faction>,
+    member_to_flock: &HashMap<Entity, Entity>,
+) -> bool {
+    let source_entt = match wpns.get(proj.source_wpn) {
+        Ok(wpn) => wpn.boid_entt(),
+        Err(_) => return true,
+    };
+    !boid::targeting::is_friendly(
+        faction,
+        flock,
+        factions.get(source_entt).copied().unwrap_or_default(),
+        member_to_flock.get(&source_entt),
+    )
+}
+
+/// A projectile contact's going to pass close by soon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncomingProjectile {
+    pub projectile: Entity,
+}
+impl blackboard::Fact for IncomingProjectile {}
+
+/// Puts up [`IncomingProjectile`]s when any projectile contact's going to pass too close too soon.
+pub fn incoming_projectile_sensor(
+    crafts: Query<(
+        Entity,
+        &GlobalTransform,
+        &Velocity,
+        &sensors::Contacts,
+        &boid::BoidMindConfig,
+        Option<&Faction>,
+    )>,
+    projectiles: Query<&Projectile>,
+    wpns: Query<&CraftWeapon>,
+    factions: Query<&Faction>,
+    flocks: Query<(Entity, &flock::FlockMembers)>,
+    mut member_to_flock: Local<HashMap<Entity, Entity>>,
+    mut writer: blackboard::BlackboardWriter,
+    time: Res<Time>,
+) {
+    boid::targeting::map_members_to_flocks(&flocks, &mut member_to_flock);
+    for (craft_entt, xform, vel, contacts, config, faction) in crafts.iter() {
+        let pos = xform.translation();
+        let faction = faction.copied().unwrap_or_default();
+        let flock = member_to_flock.get(&craft_entt);
+        for (proj_entt, contact) in contacts.of_class(sensors::ContactClass::Projectile) {
+            let hostile = projectiles
+                .get(proj_entt)
+                .map(|proj| is_hostile(proj, faction, flock, &wpns, &factions, &member_to_flock))
+                .unwrap_or(false);
+            if !hostile {
+                continue;
+            }
+            if let Some((time_to_pass, miss_dst)) = closest_approach(
+                contact.estimated_pos(&time) - pos,
+                contact.last_vel - vel.linvel,
+            ) {
+                if time_to_pass < config.countermeasure_warning_secs
+                    && miss_dst < config.countermeasure_miss_radius
+                {
+                    writer.write(
+                        craft_entt,
+                        IncomingProjectile {
+                            projectile: proj_entt,
+                        },
+                        blackboard::StimulusSource::Sensor,
+                        time_to_pass as f64,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Deploys countermeasures while there are [`IncomingProjectile`]s.
+pub fn deploy_countermeasures(
+    crafts: Query<(Entity, &blackboard::Blackboard, &boid::BoidMindDirective)>,
+    mut deploy_events: EventWriter<DeployCountermeasuresEvent>,
+    time: Res<Time>,
+) {
+    let now_secs = time.seconds_since_startup();
+    for (craft_entt, board, directive) in crafts.iter() {
+        if matches!(directive, boid::BoidMindDirective::SlaveToPlayerControl) {
+            continue;
+        }
+        if board.contains::<IncomingProjectile>(now_secs) {
+            deploy_events.send(DeployCountermeasuresEvent {
+                boid_entt: craft_entt,
+            });
+        }
+    }
+}
+
+/// Turns a turret by at most `max_angle` towards facing `dir`, in world space.
+pub fn swivel(
+    xform: &GlobalTransform,
+    local_xform: &mut Transform,
+    dir: TVec3,
+    up: TVec3,
+    max_angle: TReal,
+) {
+    // the turret needn't be a direct child of the craft
+    let parent_rot = xform.compute_transform().rotation * local_xform.rotation.inverse();
+    let desired_rot = parent_rot.inverse() * Transform::identity().looking_at(dir, up).rotation;
+    let angle = local_xform.rotation.angle_between(desired_rot);
+    local_xform.rotation = if angle > max_angle {
+        local_xform.rotation.slerp(desired_rot, max_angle / angle)
+    } else {
+        desired_rot
+    };
+}
+
+/// Swivels [`PointDefence`] turrets towards the most imminent hostile projectile contact
+/// heading for their craft and fires when there's a firing solution and a clear line of fire.
+pub fn point_defence(
+    mut turrets: Query<(
+        Entity,
+        &mut PointDefence,
+        &CraftWeapon,
+        &WeaponActivationState,
+        &GlobalTransform,
+        &mut Transform,
+    )>,
+    crafts: Query<(
+        &GlobalTransform,
+        &Velocity,
+        &sensors::Contacts,
+        &sensors::CraftWeaponsIndex,
+        &boid::BoidMindConfig,
+        &crate::Colliders,
+        Option<&Faction>,
+    )>,
+    projectiles: Query<&Projectile>,
+    wpns: Query<&CraftWeapon>,
+    factions: Query<&Faction>,
+    flocks: Query<(Entity, &flock::FlockMembers)>,
+    mut member_to_flock: Local<HashMap<Entity, Entity>>,
+    mut los: sensors::los::LineOfSight,
+    mut activate_wpn_events: EventWriter<ActivateWeaponEvent>,
+    time: Res<Time>,
+) {
+    boid::targeting::map_members_to_flocks(&flocks, &mut member_to_flock);
+    for (wpn_entt, mut pd, wpn, activation_state, xform, mut local_xform) in turrets.iter_mut() {
+        let craft_entt = wpn.boid_entt();
+        let (craft_xform, craft_vel, contacts, wpn_index, config, craft_colliders, faction) =
+            match crafts.get(craft_entt) {
+                Ok(craft) => craft,
+                Err(_) => continue,
+            };
+        // might not be indexed yet
+        let desc = match wpn_index.entt_to_desc.get(&wpn_entt) {
+            Some(desc) => desc,
+            None => continue,
+        };
+        let wpn_pos = xform.translation();
+        let craft_pos = craft_xform.translation();
+        let range = pd.range.min(desc.range);
+        let faction = faction.copied().unwrap_or_default();
+        let flock = member_to_flock.get(&craft_entt);
+
+        pd.target = contacts
+            .of_class(sensors::ContactClass::Projectile)
+            .map(|(entt, contact)| (entt, contact.estimated_pos(&time), contact.last_vel))
+            .filter(|(_, pos, _)| pos.distance_squared(wpn_pos) < range * range)
+            .filter(|(entt, _, _)| {
+                projectiles
+                    .get(*entt)
+                    .map(|proj| {
+                        is_hostile(proj, faction, flock, &wpns, &factions, &member_to_flock)
+                    })
+                    .unwrap_or(false)
+            })
+            .filter_map(|(entt, pos, vel)| {
+                closest_approach(pos - craft_pos, vel - craft_vel.linvel)
+                    .filter(|(_, miss_dst)| *miss_dst < config.countermeasure_miss_radius)
+                    .map(|(time, _)| (entt, time))
+            })
+            .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(entt, _)| entt);
+
+        let (target_entt, target_pos, target_vel) = match pd
+            .target
+            .and_then(|entt| contacts.get(entt).map(|c| (entt, c)))
+        {
+            Some((entt, contact)) => (entt, contact.estimated_pos(&time), contact.last_vel),
+            None => continue,
+        };
+
+        // swivel towards the lead
+        let lead_pos =
+            match desc
+                .ballistics
+                .solve_lead(wpn_pos, craft_vel.linvel, target_pos, target_vel)
+            {
+                Some(solution) => solution.aim_pos,
+                None => continue,
+            };
+        swivel(
+            xform,
+            &mut local_xform,
+            lead_pos - wpn_pos,
+            craft_xform.up(),
+            pd.traverse_rate_radians * time.delta_seconds(),
+        );
+
+        if activation_state.can_activate(&time)
+            && boid::strategy::firing_solution(
+                wpn_pos,
+                xform.forward(),
+                craft_vel.linvel,
+                desc,
+                target_pos,
+                target_vel,
+                config.aim_tolerance_radians,
+            )
+            .is_some()
+            && los.line_of_fire(wpn_entt, wpn_pos, Some(target_entt), lead_pos, &|entt| {
+                craft_colliders.set.contains(&entt)
+            })
+        {
+            activate_wpn_events.send(ActivateWeaponEvent {
+                weapon_id: wpn_entt,
+            });
+        }
+    }
+}
+
+#[test]
+fn closest_approach_test() {
+    // head on
+    let (time, miss_dst) = closest_approach(TVec3::Z * 100., TVec3::Z * -50.).unwrap();
+    assert!((time - 2.).abs() < 0.001);
+    assert!(miss_dst < 0.001);
+
+    // passing by 10 off to the side
+    let (time, miss_dst) = closest_approach(TVec3::new(10., 0., 100.), TVec3::Z * -50.).unwrap();
+    assert!((time - 2.).abs() < 0.001);
+    assert!((miss_dst - 10.).abs() < 0.001);
+
+    // moving away or not moving at all
+    assert!(closest_approach(TVec3::Z * 100., TVec3::Z * 50.).is_none());
+    assert!(closest_approach(TVec3::Z * 100., TVec3::ZERO).is_none());
+}
@@ -11,11 +11,18 @@ use crate::mind::*;
 
 pub mod arrive;
 pub mod avoid_collision;
+pub mod avoid_crafts;
 pub mod closure;
 pub mod compose;
+pub mod context;
+pub mod evade;
 pub mod face;
 pub mod fly_with_flock;
+pub mod follow_path;
+pub mod follow_spline;
 pub mod intercept;
+pub mod jink;
+pub mod orbit;
 pub mod player;
 pub mod seek;
 pub mod steering_behaviours;
@@ -135,16 +142,80 @@ impl LinearRoutineOutput {
     }
 }
 
-/// Output of angular steering routines which is currently angular velocity desired next frame in local space.
-#[derive(Debug, Clone, Copy, Default, Inspectable, Component)]
-pub struct AngularRoutineOutput(pub TVec3);
+/// All the values here are in the craft's local basis unless noted.
+pub struct ToAngVelParams {
+    /// The craft's orientation in world space.
+    pub rotation: TQuat,
+    pub angvel: TVec3,
+    /// See [`boid::BoidMindConfig::angular_input_multiplier`].
+    pub angular_input_multiplier: TReal,
+    pub angular_accel_limit: TVec3,
+}
+
+impl ToAngVelParams {
+    pub fn new(
+        xform: &Transform,
+        state: &engine::AngularEngineState,
+        mind_config: &boid::BoidMindConfig,
+        angular_accel_limit: TVec3,
+    ) -> Self {
+        Self {
+            rotation: xform.rotation,
+            angvel: state.velocity,
+            angular_input_multiplier: mind_config.angular_input_multiplier,
+            angular_accel_limit,
+        }
+    }
+}
+
+/// Output of angular steering routines, in the craft's local basis unless noted.
+#[derive(Debug, Clone, Copy, Inspectable, Component, educe::Educe)]
+#[educe(Default)]
+pub enum AngularRoutineOutput {
+    /// Point the nose (`-Z`) this way. Leaves the roll be.
+    LookDir(TVec3),
+    /// Turn to this orientation in world space, roll included.
+    Orientation(TQuat),
+    /// In rad/s.
+    #[educe(Default)]
+    AngVel(TVec3),
+    /// In fraction of the angular acceleration limit.
+    FracAngAccel(TVec3),
+}
+
+impl AngularRoutineOutput {
+    /// The angular velocity desired next frame.
+    #[inline(always)]
+    pub fn to_angvel(self, param: &ToAngVelParams) -> TVec3 {
+        use AngularRoutineOutput::*;
+        match self {
+            // We'll apply the multiplier to the direction errors as oppposed to the velocity
+            // error which would have been the case if we'd used the multiplier after the sub
+            // operation.
+            LookDir(dir) => look_to(dir) * param.angular_input_multiplier,
+            Orientation(rot) => {
+                orientation_error(param.rotation, rot) * param.angular_input_multiplier
+            }
+            AngVel(vel) => vel,
+            FracAngAccel(v) => param.angvel + (v * param.angular_accel_limit),
+        }
+    }
+}
 
-impl From<TVec3> for AngularRoutineOutput {
-    fn from(v: TVec3) -> Self {
-        Self(v)
+/// The error between the `current` and `target` orientations in the current local basis.
+/// Scaled like [`look_to`] for small errors but keeps pushing until it's aligned.
+#[inline]
+pub fn orientation_error(current: TQuat, target: TQuat) -> TVec3 {
+    let mut diff = current.inverse() * target;
+    // take the short way around
+    if diff.w < 0. {
+        diff = -diff;
     }
+    let (axis, angle) = diff.to_axis_angle();
+    axis * angle.min(1.)
 }
 
+#[allow(clippy::type_complexity)]
 pub fn steering_output_to_engine(
     mut crafts: Query<(
         &Transform,
@@ -153,6 +224,8 @@ pub fn steering_output_to_engine(
         &mut engine::LinearEngineState,
         &mut engine::AngularEngineState,
         &engine::EngineConfig,
+        &engine::DerivedEngineConfig,
+        &ReadMassProperties,
         &CraftControllerConsts,
         &Velocity,
     )>,
@@ -167,11 +240,13 @@ pub fn steering_output_to_engine(
         mut lin_state,
         mut ang_state,
         engine_config,
+        derived_config,
+        mass_props,
         consts,
         vel,
     ) in crafts.iter_mut()
     {
-        let (lin_out, ang_out) = if let Some(cur_routine) = cur_routine.routine.as_ref() {
+        let (lin_out, angvel) = if let Some(cur_routine) = cur_routine.routine.as_ref() {
             // TODO: use a different componet to get the final outputs
             let (lin_out, ang_out) = routines.get(*cur_routine)
             .expect_or_log("CurrentSteeringRoutine's routine not located in world. \
@@ -183,17 +258,18 @@ pub fn steering_output_to_engine(
                     engine_config,
                     consts,
                 )),
-                ang_out.0,
+                ang_out.to_angvel(&ToAngVelParams::new(
+                    xform,
+                    &ang_state,
+                    mind_config,
+                    engine::angular_accel_limit(engine_config, derived_config, mass_props),
+                )),
             )
         } else {
             (TVec3::ZERO, TVec3::ZERO)
         };
         lin_state.input = xform.rotation.inverse() * lin_out;
-        // if ang_out is coming from a `look_at` call as is usual,
-        // it'll be the error betweein the set direction and current direction.
-        // We'll apply the multiplier to that error as oppposed to the velocity error which would have been
-        // the case if we'd used the multiplier after the sub operation.
-        ang_state.input = (ang_out * mind_config.angular_input_multiplier) - ang_state.velocity;
+        ang_state.input = angvel - ang_state.velocity;
     }
 }
 
@@ -354,39 +430,67 @@ where
     }
 }
 
-/* fn routine_garbage_collector(
+/// Routines unreferenced for longer than this are despawned.
+pub const ROUTINE_GRACE_PERIOD_SECS: f64 = 3.;
+
+/// Exempts the routine from the [`routine_garbage_collector`]. For strategies that juggle
+/// routines, keeping some around to switch back to without composing them.
+#[derive(Debug, Clone, Copy, Component, Default)]
+pub struct PinnedSteeringRoutine;
+
+/// Marks routines that are owned by the craft instead of a strategy. They're spawned
+/// as children of the craft and new strategies reuse them through
+/// [`sensors::SteeringRoutinesIndex::reusable`]. Only use it for routines whose parameters
+/// don't depend on the strategy.
+#[derive(Debug, Clone, Copy, Component, Default)]
+pub struct SharedSteeringRoutine;
+
+/// Routines are owned by the strategy they're spawned under, or by the craft if they're
+/// [`SharedSteeringRoutine`]s, and are despawned along with it. Whatever's left
+/// unreferenced for [`ROUTINE_GRACE_PERIOD_SECS`] is despawned here.
+/// Routines are referenced if they're being composed, they're a craft's current routine
+/// or a strategy's output, or they're [`PinnedSteeringRoutine`]s.
+#[allow(clippy::too_many_arguments)]
+pub fn routine_garbage_collector(
     mut commands: Commands,
-    mut crafts: Query<
-        (Entity, &RoutineComposer, &mut CraftRoutinesIndex),
-        Changed<RoutineComposer>,
-    >,
-    routines: Query<&SteeringRoutine>,
-    mut cache: Local<bevy::utils::HashSet<Entity>>,
+    routines: Query<(Entity, &SteeringRoutine), Without<PinnedSteeringRoutine>>,
+    composers: Query<&compose::Compose>,
+    crafts: Query<&CurrentSteeringRoutine>,
+    strategies: Query<&boid::strategy::BoidStrategyOutput>,
+    time: Res<Time>,
+    registry: Res<registry::MindRegistry>,
+    mut referenced: Local<bevy::utils::HashSet<Entity>>,
+    mut unreferenced_since: Local<bevy::utils::HashMap<Entity, f64>>,
 ) {
-    for (boid_entt, composer, mut index) in crafts.iter_mut() {
-        // make a set of all the composed routines
-        cache.extend(composer.all_routines());
-        // for all index
-        for routine in index.entt_to_kind.keys() {
-            // if being composed
-            if cache.contains(routine) {
-                // remove from set
-                cache.remove(routine);
-            } else {
-                // destroy routine
-                commands.entity(*routine).despawn_recursive();
-            }
+    let now = time.seconds_since_startup();
+    referenced.extend(
+        composers
+            .iter()
+            .flat_map(|param| param.composer.all_routines()),
+    );
+    referenced.extend(crafts.iter().filter_map(|cur| cur.routine));
+    referenced.extend(strategies.iter().filter_map(|out| out.steering_routine));
+    for (entt, routine) in routines.iter() {
+        if referenced.contains(&entt) {
+            unreferenced_since.remove(&entt);
+            continue;
         }
-        // for remaining composed routines not in indices
-        for entt in cache.drain() {
-            let routine = routines
-                .get(entt)
-                .expect_or_log("composed steering routine not found");
-            index.add_routine(entt, routine.kind);
+        let since = *unreferenced_since.entry(entt).or_insert(now);
+        if now - since > ROUTINE_GRACE_PERIOD_SECS {
+            tracing::debug!(
+                routine = ?entt,
+                boid = ?routine.boid_entt(),
+                kind = registry.routine_name(routine.kind()).unwrap_or("unregistered"),
+                "despawning unreferenced steering routine"
+            );
+            commands.entity(entt).despawn_recursive();
+            unreferenced_since.remove(&entt);
         }
     }
+    // forget the ones that were despawned or pinned since
+    unreferenced_since.retain(|entt, _| routines.contains(*entt));
+    referenced.clear();
 }
- */
 
 /*
 #[inline]
@@ -438,3 +542,141 @@ pub fn look_to(local_dir: TVec3) -> TVec3 {
         )
     */
 }
+
+/// A point fixed to some entity, as tracked by routines chasing moving things.
+/// All in world basis.
+#[derive(Debug, Clone, Copy)]
+pub struct TrackedPoint {
+    pub pos: TVec3,
+    pub linvel: TVec3,
+    pub rotation: TQuat,
+}
+
+/// The entities steering routines follow. See [`track_object`].
+#[derive(bevy::ecs::system::SystemParam)]
+pub struct TrackedObjects<'w, 's> {
+    objects: Query<'w, 's, (&'static GlobalTransform, Option<&'static Velocity>)>,
+    /// Routines whose target's already been reported missing.
+    reported: Local<'s, bevy::utils::HashSet<Entity>>,
+}
+
+/// Locates the point `offset` away from `entt` in its local basis along with how fast the
+/// point's moving, the entity's spin included.
+/// Returns None if the entity's gone, logging an error the first time for each routine.
+pub fn track_object(
+    objects: &mut TrackedObjects,
+    entt: Entity,
+    offset: TVec3,
+    routine_entt: Entity,
+) -> Option<TrackedPoint> {
+    match objects.objects.get(entt) {
+        Ok((g_xform, vel)) => {
+            let xform = g_xform.compute_transform();
+            let offset = xform.rotation * offset;
+            let linvel = vel
+                .map(|vel| vel.linvel + vel.angvel.cross(offset))
+                .unwrap_or_default();
+            Some(TrackedPoint {
+                pos: xform.translation + offset,
+                linvel,
+                rotation: xform.rotation,
+            })
+        }
+        Err(err) => {
+            if objects.reported.insert(routine_entt) {
+                tracing::error!(
+                    ?err,
+                    target = ?entt,
+                    routine = ?routine_entt,
+                    "steering target not found, was it despawned?"
+                );
+            }
+            None
+        }
+    }
+}
+
+#[test]
+fn orientation_error_test() {
+    let current = TQuat::from_rotation_y(0.3);
+    // small errors match look_to
+    let target = current * TQuat::from_rotation_y(0.01);
+    let err = orientation_error(current, target);
+    let look = look_to(TQuat::from_rotation_y(0.01) * -TVec3::Z);
+    assert!((err - look).length() < 1e-4);
+    // roll's accounted for
+    let target = current * TQuat::from_rotation_z(0.5);
+    let err = orientation_error(current, target);
+    assert!((err - TVec3::new(0., 0., 0.5)).length() < 1e-4);
+    // takes the short way around
+    let target = current * TQuat::from_rotation_x(-3.5);
+    assert!(orientation_error(current, target).x > 0.);
+}
+
+#[test]
+fn routine_garbage_collector_test() {
+    use bevy::utils::{Duration, Instant};
+
+    let mut world = World::new();
+    let startup = Instant::now();
+    let mut time = Time::default();
+    time.update_with_instant(startup);
+    world.insert_resource(time);
+    world.insert_resource(registry::MindRegistry::default());
+    let mut stage = SystemStage::single(routine_garbage_collector);
+    let mut run_at = |world: &mut World, secs: f64| {
+        world
+            .resource_mut::<Time>()
+            .update_with_instant(startup + Duration::from_secs_f64(secs));
+        stage.run(world);
+    };
+
+    let boid_entt = world.spawn().id();
+    let mut spawn_routine = || {
+        world
+            .spawn()
+            .insert(SteeringRoutine::new(
+                boid_entt,
+                RoutineKind::of::<PinnedSteeringRoutine>(),
+            ))
+            .id()
+    };
+    let (orphan, pinned, current, output) = (
+        spawn_routine(),
+        spawn_routine(),
+        spawn_routine(),
+        spawn_routine(),
+    );
+    world.entity_mut(pinned).insert(PinnedSteeringRoutine);
+    world.entity_mut(boid_entt).insert(CurrentSteeringRoutine {
+        routine: Some(current),
+    });
+    world.spawn().insert(boid::strategy::BoidStrategyOutput {
+        steering_routine: Some(output),
+        ..default()
+    });
+
+    run_at(&mut world, 0.);
+    run_at(&mut world, ROUTINE_GRACE_PERIOD_SECS - 0.5);
+    assert!(
+        world.get_entity(orphan).is_some(),
+        "despawned before its grace period"
+    );
+    run_at(&mut world, ROUTINE_GRACE_PERIOD_SECS + 0.5);
+    assert!(world.get_entity(orphan).is_none());
+    for entt in [pinned, current, output] {
+        assert!(world.get_entity(entt).is_some());
+    }
+
+    // the grace period starts once it's no longer referenced
+    world
+        .entity_mut(boid_entt)
+        .insert(CurrentSteeringRoutine { routine: None });
+    run_at(&mut world, ROUTINE_GRACE_PERIOD_SECS + 1.);
+    run_at(&mut world, (ROUTINE_GRACE_PERIOD_SECS * 2.) + 0.5);
+    assert!(world.get_entity(current).is_some());
+    run_at(&mut world, (ROUTINE_GRACE_PERIOD_SECS * 2.) + 1.5);
+    assert!(world.get_entity(current).is_none());
+    assert!(world.get_entity(pinned).is_some());
+    assert!(world.get_entity(output).is_some());
+}
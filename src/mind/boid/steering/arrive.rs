@@ -1,125 +1,101 @@
 use deps::*;
 
 use bevy::prelude::*;
-// use bevy_prototype_debug_lines::*;
 use bevy_rapier3d::prelude::*;
 
-use super::{ActiveSteeringRoutine, LinOnlyRoutineBundle, LinearRoutineOutput, SteeringRoutine};
+use super::{
+    ActiveSteeringRoutine, AngularRoutineOutput, LinAngRoutineBundle, LinearRoutineOutput,
+    SteeringRoutine, TrackedObjects,
+};
 use crate::math::*;
 
-/// All vectors are in in world basis
+/// All vectors are in in world basis unless noted otherwise
 #[derive(Debug, Clone, Component)]
 pub enum Target {
-    /// must have a [`GlobalTransform`] component.
-    // Object { entt: Entity, offset: TVec3 },
+    /// Must have a [`GlobalTransform`]. Matches its velocity, [`Velocity`] permitting, and
+    /// outputs nothing if it's gone.
+    Object {
+        entt: Entity,
+        /// In the object's local basis.
+        offset: TVec3,
+        /// The velocity to be moving at on arrival relative to the object's, in its local basis.
+        with_linvel: TVec3,
+    },
     Vector {
         at_pos: TVec3,
-        // with_linvel: TVec3,
-        with_speed: TReal,
+        /// The velocity to be moving at on arrival.
+        with_linvel: TVec3,
+        /// How fast `at_pos` itself is moving.
         pos_linvel: TVec3,
     },
 }
 
+/// Gets to the target position with the target velocity in the least time the per axis
+/// acceleration limits allow.
 #[derive(Debug, Clone, Component)]
 pub struct Arrive {
     pub target: Target,
+    /// Within this distance of the target, the output eases off.
     pub arrival_tolerance: TReal,
-    /// If not given, will be calculated based on accel and linvel_limit
-    pub deceleration_radius: Option<TReal>,
+    /// In the craft's local basis.
     pub avail_accel: TVec3,
+    /// Direction to face, in world basis. Faces the direction of acceleration if None.
+    pub with_facing: Option<TVec3>,
 }
 
-/*
-use crate::utils::*;
-#[derive(Debug, Clone, Component)]
-pub struct ArriveState {
-    pid: PIDControllerVec3,
-}
-
-impl Default for ArriveState {
-    fn default() -> Self {
-        Self {
-            pid: PIDControllerVec3::new(
-                TVec3::ONE * 1.0,
-                TVec3::ONE * 0.,
-                TVec3::ONE * 0.,
-                TVec3::ONE * 0.,
-                TVec3::ONE * 0.,
-            ),
-        }
-    }
-} */
-
-pub type Bundle = LinOnlyRoutineBundle<Arrive>;
+pub type Bundle = LinAngRoutineBundle<Arrive>;
 
 pub fn update(
     mut routines: Query<
         (
+            Entity,
             &SteeringRoutine,
             &Arrive,
-            // &mut ArriveState,
             &mut LinearRoutineOutput,
+            &mut AngularRoutineOutput,
         ),
         With<ActiveSteeringRoutine>,
     >,
-    boids: Query<(&Transform, &Velocity, &crate::craft::engine::EngineConfig)>, // boids
-                                                                                // objects: Query<&GlobalTransform>,
-                                                                                // mut lines: ResMut<DebugLines>,
+    boids: Query<(&Transform, &Velocity)>, // boids
+    mut objects: TrackedObjects,
 ) {
-    for (routine, param, mut output) in routines.iter_mut() {
-        let (xform, vel, engine_conf) = boids.get(routine.boid_entt()).unwrap_or_log();
+    for (routine_entt, routine, param, mut lin_out, mut ang_out) in routines.iter_mut() {
+        let (xform, vel) = boids.get(routine.boid_entt()).unwrap_or_log();
+        let inv_rot = xform.rotation.inverse();
 
-        *output = match param.target {
-            /* Target::Object { entt, offset } => match objects.get(entt) {
-                Ok(obj_xform) => obj_xform.translation + offset,
-                Err(err) => {
-                    tracing::error!("error getting SeekTarget Object g_xform: {err:?}");
-                    continue;
-                }
-            }, */
+        let (at_pos, with_linvel, pos_linvel) = match param.target {
             Target::Vector {
                 at_pos,
-                // with_linvel,
+                with_linvel,
                 pos_linvel,
-                with_speed,
-            } => {
-                super::steering_behaviours::seek_position(xform.translation, at_pos)
-
-                /* let vel = vel.linvel;
-                let target_offset = (at_pos + pos_linvel) - xform.translation;
-                let target_spd = pos_linvel.length() + with_speed;
-
-                let spd_to_target = vel.project_onto_scalar(target_offset);
-
-                super::steering_behaviours::arrive_at_position(
-                    xform.translation,
-                    at_pos,
-                    vel,
-                    default(),
-                    with_speed,
-                    param.arrival_tolerance,
-                    {
-                        let max_accel = xform.rotation * param.avail_accel;
-                        // let max_vel = xform.rotation * engine_conf.linvel_limit;
-                        let accel = max_accel.project_onto_scalar(target_offset).abs();
-                        super::steering_behaviours::dst_to_change(spd_to_target, target_spd, accel)
-                        // .max_element()
-                    },
-                ) */
-                /*  super::steering_behaviours::be_ray(
-                    at_pos,
-                    // with_linvel,
-                    TVec3::Z * -90.,
-                    pos_linvel,
-                    xform.translation,
-                    vel.linvel.into(),
-                    param.avail_accel,
-                    param.linvel_limit,
-                    xform.rotation,
-                    &mut |v1, v2| lines.line(v1, v2, 0.),
-                )
-                .into() */
-            }
+            } => (at_pos, with_linvel, pos_linvel),
+            Target::Object {
+                entt,
+                offset,
+                with_linvel,
+            } => match super::track_object(&mut objects, entt, offset, routine_entt) {
+                Some(point) => (
+                    point.pos,
+                    point.linvel + (point.rotation * with_linvel),
+                    point.linvel,
+                ),
+                None => {
+                    *lin_out = default();
+                    *ang_out = default();
+                    continue;
+                }
+            },
         };
+        // solve it in the craft's basis where the acceleration limits are
+        let accel = xform.rotation
+            * super::steering_behaviours::arrive_with_velocity(
+                inv_rot * (at_pos - xform.translation),
+                inv_rot * (vel.linvel - pos_linvel),
+                inv_rot * (with_linvel - pos_linvel),
+                param.avail_accel.abs(),
+                param.arrival_tolerance,
+            );
+        *lin_out = LinearRoutineOutput::Accel(accel);
+        *ang_out = AngularRoutineOutput::LookDir(inv_rot * param.with_facing.unwrap_or(accel));
     }
 }
@@ -0,0 +1,266 @@
+use deps::*;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use super::{
+    context::{ContextMap, CONTEXT_DIRECTIONS},
+    ActiveSteeringRoutine, LinOnlyRoutineBundle, LinearRoutineOutput, SteeringRoutine,
+};
+use crate::{
+    craft::{engine::*, CraftDimensions},
+    math::*,
+    mind::sensors::{spatial::SpatialIndex, ContactClass, SteeringRoutinesIndex},
+};
+
+/// Keeps the craft from ramming other crafts using Optimal Reciprocal Collision Avoidance.
+/// Only outputs when the craft's current intent would lead to a collision within
+/// [`Self::time_horizon_secs`] so it's meant to go first in a
+/// [`super::compose::SteeringRoutineComposer::AvoidCollisionHelper`].
+/// Writes danger for the velocities it rules out if given a [`ContextMap`].
+#[derive(Debug, Clone, Component)]
+pub struct AvoidCrafts {
+    pub time_horizon_secs: TReal,
+    /// Crafts further than this are ignored.
+    pub neighbour_radius: TReal,
+    pub max_neighbours: usize,
+    /// How far ahead the craft's acceleration limits are projected when deciding
+    /// which velocities are reachable.
+    pub reaction_secs: TReal,
+}
+
+impl Default for AvoidCrafts {
+    fn default() -> Self {
+        Self {
+            time_horizon_secs: 3.,
+            neighbour_radius: 300.,
+            max_neighbours: 8,
+            reaction_secs: 0.5,
+        }
+    }
+}
+
+pub type Bundle = LinOnlyRoutineBundle<AvoidCrafts>;
+
+/// Half-space of permitted velocities i.e. `(v - point) · normal >= 0`.
+#[derive(Debug, Clone, Copy)]
+pub struct VelocityPlane {
+    pub point: TVec3,
+    pub normal: TVec3,
+}
+
+/// The ORCA plane induced by a neighbour `rel_pos` away closing at `rel_vel`
+/// (the craft's velocity minus the neighbour's).
+/// `responsibility` is the share of the avoidance the craft takes on, half if the
+/// neighbour's avoiding it as well.
+pub fn orca_plane(
+    vel: TVec3,
+    rel_pos: TVec3,
+    rel_vel: TVec3,
+    combined_radius: TReal,
+    time_horizon_secs: TReal,
+    frame_secs: TReal,
+    responsibility: TReal,
+) -> VelocityPlane {
+    let dist_squared = rel_pos.length_squared();
+    let radius_squared = combined_radius * combined_radius;
+    let (u, normal) = if dist_squared > radius_squared {
+        // vector from the cut-off center to the relative velocity
+        let w = rel_vel - (rel_pos / time_horizon_secs);
+        let w_len_squared = w.length_squared();
+        let dot = w.dot(rel_pos);
+        if dot < 0. && dot * dot > radius_squared * w_len_squared {
+            // closest to the cut-off sphere
+            let w_len = w_len_squared.sqrt();
+            let unit_w = w / w_len;
+            (
+                unit_w * ((combined_radius / time_horizon_secs) - w_len),
+                unit_w,
+            )
+        } else {
+            // closest to the side of the cone
+            let a = dist_squared;
+            let b = rel_pos.dot(rel_vel);
+            let c = rel_vel.length_squared()
+                - (rel_pos.cross(rel_vel).length_squared() / (dist_squared - radius_squared));
+            let t = (b + ((b * b) - (a * c)).max(0.).sqrt()) / a;
+            let w = rel_vel - (rel_pos * t);
+            let w_len = w.length();
+            let unit_w = if w_len > TReal::EPSILON {
+                w / w_len
+            } else {
+                -rel_pos.normalize_or_zero()
+            };
+            (unit_w * ((combined_radius * t) - w_len), unit_w)
+        }
+    } else {
+        // already colliding, get out within the frame
+        let w = rel_vel - (rel_pos / frame_secs);
+        let w_len = w.length();
+        let unit_w = if w_len > TReal::EPSILON {
+            w / w_len
+        } else {
+            -rel_pos.normalize_or_zero()
+        };
+        (unit_w * ((combined_radius / frame_secs) - w_len), unit_w)
+    };
+    VelocityPlane {
+        point: vel + (u * responsibility),
+        normal,
+    }
+}
+
+/// The velocity closest to `preferred` that's in all the `planes`.
+/// Solved by repeated projection instead of linear programming so it's only approximate
+/// when the planes conflict. `constrain` is applied after each pass, use it for speed and
+/// acceleration limits.
+pub fn solve_velocity(
+    preferred: TVec3,
+    planes: &[VelocityPlane],
+    constrain: impl Fn(TVec3) -> TVec3,
+    max_iterations: usize,
+) -> TVec3 {
+    let mut vel = constrain(preferred);
+    for _ in 0..max_iterations {
+        let mut violated = false;
+        for plane in planes {
+            let depth = (plane.point - vel).dot(plane.normal);
+            if depth > 0. {
+                vel += plane.normal * depth;
+                violated = true;
+            }
+        }
+        vel = constrain(vel);
+        if !violated {
+            break;
+        }
+    }
+    vel
+}
+
+pub fn update(
+    mut routines: Query<
+        (
+            &AvoidCrafts,
+            &SteeringRoutine,
+            &mut LinearRoutineOutput,
+            Option<&mut ContextMap>,
+        ),
+        With<ActiveSteeringRoutine>,
+    >,
+    crafts: Query<(
+        &Transform,
+        &Velocity,
+        &LinearEngineState,
+        &EngineConfig,
+        &CraftDimensions,
+        Option<&SteeringRoutinesIndex>,
+    )>,
+    active: Query<(), (With<AvoidCrafts>, With<ActiveSteeringRoutine>)>,
+    index: Res<SpatialIndex>,
+    time: Res<Time>,
+) {
+    let frame_secs = time.delta_seconds().max(1. / 60.);
+    for (param, routine, mut output, mut context) in routines.iter_mut() {
+        *output = default();
+        if let Some(context) = context.as_mut() {
+            context.clear();
+        }
+        let (xform, vel, lin_state, config, dim, _) = crafts
+            .get(routine.boid_entt())
+            .expect_or_log("craft entt not found for routine");
+        let radius = dim.max_element() * 0.5;
+
+        let planes: SVec<[VelocityPlane; 8]> = index
+            .k_nearest(
+                xform.translation,
+                param.max_neighbours,
+                param.neighbour_radius,
+                |item| item.class == ContactClass::Craft && item.entt != routine.boid_entt(),
+            )
+            .iter()
+            .filter_map(|item| {
+                let (_, _, _, _, other_dim, other_routines) = crafts.get(item.entt).ok()?;
+                // split the work if they're avoiding us too
+                // the index has their idle routines as well
+                let reciprocates = other_routines
+                    .and_then(|idx| idx.kind::<AvoidCrafts>())
+                    .map(|routines| routines.iter().any(|entt| active.contains(*entt)))
+                    .unwrap_or(false);
+                Some(orca_plane(
+                    vel.linvel,
+                    item.pos - xform.translation,
+                    vel.linvel - item.vel,
+                    radius + (other_dim.max_element() * 0.5),
+                    param.time_horizon_secs,
+                    frame_secs,
+                    if reciprocates { 0.5 } else { 1. },
+                ))
+            })
+            .collect();
+        if planes.is_empty() {
+            continue;
+        }
+
+        // where last frame's flame was taking us
+        let preferred = vel.linvel + (xform.rotation * lin_state.flame * param.reaction_secs);
+        let max_speed = config.linvel_limit.max_element();
+        let max_delta = config.actual_accel_limit() * param.reaction_secs;
+        let inv_rot = xform.rotation.inverse();
+        let cur_vel = vel.linvel;
+        let solved = solve_velocity(
+            preferred,
+            &planes,
+            |v| {
+                // only what's reachable given the per axis acceleration limits
+                let delta = (inv_rot * (v - cur_vel)).clamp(-max_delta, max_delta);
+                (cur_vel + (xform.rotation * delta)).clamp_length_max(max_speed)
+            },
+            16,
+        );
+        if let Some(context) = context.as_mut() {
+            // danger in the directions whose reachable velocities break the planes
+            let reach = max_delta.length().max(TReal::EPSILON);
+            for (ii, dir) in CONTEXT_DIRECTIONS.iter().enumerate() {
+                let vel = cur_vel
+                    + (xform.rotation
+                        * super::steering_behaviours::max_accel_along(
+                            inv_rot * *dir,
+                            config.actual_accel_limit(),
+                        )
+                        * param.reaction_secs);
+                let depth = planes
+                    .iter()
+                    .map(|plane| (plane.point - vel).dot(plane.normal))
+                    .fold(0., TReal::max);
+                context.danger[ii] = (depth / reach).min(1.);
+            }
+        }
+        // only override the rest if the intent needed correcting
+        const CORRECTION_THRESHOLD: TReal = 0.5;
+        if solved.distance_squared(preferred) > CORRECTION_THRESHOLD * CORRECTION_THRESHOLD {
+            *output = LinearRoutineOutput::Vel(solved);
+        }
+    }
+}
+
+#[test]
+fn orca_head_on_test() {
+    // two crafts 100m apart closing at 40m/s
+    let vel = TVec3::new(0., 0., -20.);
+    let plane = orca_plane(
+        vel,
+        TVec3::new(0., 0., -100.),
+        vel - TVec3::new(0., 0., 20.),
+        20.,
+        5.,
+        1. / 60.,
+        0.5,
+    );
+    // the current velocity's not allowed
+    assert!((plane.point - vel).dot(plane.normal) > 0.);
+    let solved = solve_velocity(vel, &[plane], |v| v, 16);
+    assert!((plane.point - solved).dot(plane.normal) <= 1e-3);
+    // slows down or swerves away
+    assert!(solved.z > vel.z || solved.truncate().length() > 1.);
+}
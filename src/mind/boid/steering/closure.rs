@@ -9,8 +9,10 @@ use super::{
     SteeringRoutine,
 };
 
-#[derive(Component)]
+#[derive(Component, educe::Educe)]
+#[educe(Debug)]
 pub struct Closure {
+    #[educe(Debug(ignore))]
     pub closure: Box<
         dyn FnMut(
                 &GlobalTransform,
@@ -8,8 +8,9 @@ use crate::math::*;
 use crate::mind::*;
 
 use super::{
-    ActiveSteeringRoutine, AngularRoutineOutput, CraftControllerConsts, LinAngRoutineBundle,
-    LinearRoutineOutput, SteeringRoutine, ToAccelParams,
+    context::ContextMap, steering_behaviours, ActiveSteeringRoutine, AngularRoutineOutput,
+    CraftControllerConsts, LinAngRoutineBundle, LinearRoutineOutput, SteeringRoutine,
+    ToAccelParams, ToAngVelParams,
 };
 
 #[derive(Debug, Clone, Component)]
@@ -31,22 +32,14 @@ pub fn butler(
         let (index,) = crafts.get(routine.boid_entt()).unwrap_or_log();
         // make a set of all the composed routines
         cache.extend(param.composer.all_routines());
-        // for all index
+        // for all the craft's routines
         for routine in index.entt_to_kind.keys() {
-            // if being composed
-            if cache.contains(routine) {
-                // these routines are already in index so no modification
-                // necessary
-                // remove from the update set
-                cache.remove(routine);
-            }
-            // if it's stil around
-            else if all.contains(*routine) {
-                // deactivate it at least
+            // if not being composed and it's stil around
+            if !cache.contains(routine) && all.contains(*routine) {
+                // deactivate it
                 commands.entity(*routine).remove::<ActiveSteeringRoutine>();
             }
         }
-        // for remaining composed routines not in indices
         for entt in cache.drain() {
             commands.entity(entt).insert(ActiveSteeringRoutine);
         }
@@ -68,31 +61,43 @@ pub fn update(
         (Option<&LinearRoutineOutput>, Option<&AngularRoutineOutput>),
         (With<SteeringRoutine>, Without<Compose>),
     >,
+    context_maps: Query<&ContextMap>,
+    mut context: Local<ContextMap>,
     boids: Query<(
         &Transform,
         &Velocity,
         &engine::EngineConfig,
         &CraftControllerConsts,
+        &engine::AngularEngineState,
+        &engine::DerivedEngineConfig,
+        &ReadMassProperties,
+        &boid::BoidMindConfig,
     )>,
 ) {
     for (param, routine, mut lin_out, mut ang_out) in composer_routines.iter_mut() {
-        let (xform, vel, engine_config, consts) = boids.get(routine.boid_entt()).unwrap_or_log();
+        let (xform, vel, engine_config, consts, ang_state, derived_config, mass_props, mind_config) =
+            boids.get(routine.boid_entt()).unwrap_or_log();
         let to_accel = ToAccelParams::new(vel.linvel, xform, engine_config, consts);
+        let to_angvel = ToAngVelParams::new(
+            xform,
+            ang_state,
+            mind_config,
+            engine::angular_accel_limit(engine_config, derived_config, mass_props),
+        );
         /* *lin_out = super::steering_behaviours::seek_position(
             xform.translation,
             [0., 0., 1000.].into()
         );
-        *ang_out = super::look_to([0., 0., -1.].into()).into();
+        *ang_out = AngularRoutineOutput::LookDir([0., 0., -1.].into());
         continue; */
         use SteeringRoutineComposer::*;
         // FIXME: i hate this code
         let active_res = match &param.composer {
             Empty => default(),
             Single { entt: routine_entt } => {
-                match other_routines
-                    .get(*routine_entt)
-                    .map(|(lin, ang)| BoidSteeringSystemOutput::get_active_res(lin, ang, &to_accel))
-                {
+                match other_routines.get(*routine_entt).map(|(lin, ang)| {
+                    BoidSteeringSystemOutput::get_active_res(lin, ang, &to_accel, &to_angvel)
+                }) {
                     Ok(Some(res)) => res,
                     Ok(None) => {
                         tracing::error!(
@@ -116,7 +121,7 @@ pub fn update(
                 let mut sum = default();
                 for (weight, routine_entt) in summed {
                     match other_routines.get(*routine_entt).map(|(lin, ang)| {
-                        BoidSteeringSystemOutput::get_active_res(lin, ang, &to_accel)
+                        BoidSteeringSystemOutput::get_active_res(lin, ang, &to_accel, &to_angvel)
                     }) {
                         Ok(Some(res)) => {
                             sum = sum + (*weight * res);
@@ -147,7 +152,7 @@ pub fn update(
                 let mut pick = default();
                 'priority_loop: for routine_entt in priority {
                     match other_routines.get(*routine_entt).map(|(lin, ang)| {
-                        BoidSteeringSystemOutput::get_active_res(lin, ang, &to_accel)
+                        BoidSteeringSystemOutput::get_active_res(lin, ang, &to_accel, &to_angvel)
                     }) {
                         Ok(Some(res)) => {
                             // FIXME: this bugs out when we get with some zero values due to
@@ -184,34 +189,41 @@ pub fn update(
                 avoid_collision,
                 routines: summed,
             } => {
-                let avoid_coll_out = match other_routines
-                    .get(*avoid_collision)
-                    .map(|(lin, ang)| BoidSteeringSystemOutput::get_active_res(lin, ang, &to_accel))
-                {
-                    Ok(Some(res)) => res,
-                    Ok(None) => {
-                        tracing::error!(
-                            ?avoid_collision,
-                            "Routine doesn't have linear or angular results"
-                        );
-                        default()
-                    }
-                    Err(err) => {
-                        tracing::error!(
-                            ?err,
-                            ?avoid_collision,
-                            concat!("routine not found for ", stringify!(AvoidCollisionHelper))
-                        );
-                        default()
+                let mut avoid_coll_out = default();
+                for avoid_collision in avoid_collision {
+                    avoid_coll_out = match other_routines.get(*avoid_collision).map(|(lin, ang)| {
+                        BoidSteeringSystemOutput::get_active_res(lin, ang, &to_accel, &to_angvel)
+                    }) {
+                        Ok(Some(res)) => res,
+                        Ok(None) => {
+                            tracing::error!(
+                                ?avoid_collision,
+                                "Routine doesn't have linear or angular results"
+                            );
+                            default()
+                        }
+                        Err(err) => {
+                            tracing::error!(
+                                ?err,
+                                ?avoid_collision,
+                                concat!("routine not found for ", stringify!(AvoidCollisionHelper))
+                            );
+                            default()
+                        }
+                    };
+                    if !avoid_coll_out.is_zero() {
+                        break;
                     }
-                };
+                }
                 if !avoid_coll_out.is_zero() {
                     avoid_coll_out
                 } else {
                     let mut sum = default();
                     for (weight, routine_entt) in summed {
                         match other_routines.get(*routine_entt).map(|(lin, ang)| {
-                            BoidSteeringSystemOutput::get_active_res(lin, ang, &to_accel)
+                            BoidSteeringSystemOutput::get_active_res(
+                                lin, ang, &to_accel, &to_angvel,
+                            )
                         }) {
                             Ok(Some(res)) => {
                                 sum = sum + (*weight * res);
@@ -241,21 +253,207 @@ pub fn update(
                     sum
                 }
             }
+            ContextSteering {
+                interests,
+                dangers,
+                danger_threshold,
+            } => {
+                let accel_limit = engine_config.actual_accel_limit();
+                let inv_rot = xform.rotation.inverse();
+                // how much of what's available in that direction it's asking for
+                let frac_of_limit = |accel: TVec3| {
+                    let avail =
+                        steering_behaviours::max_accel_along(inv_rot * accel, accel_limit).length();
+                    if avail > TReal::EPSILON {
+                        (accel.length() / avail).min(1.)
+                    } else {
+                        0.
+                    }
+                };
+                context.clear();
+                let mut ang_sum: Option<TVec3> = None;
+                let mut failed = false;
+                for (weight, routine_entt) in interests {
+                    let map = context_maps.get(*routine_entt).ok();
+                    if let Some(map) = map {
+                        context.merge(map, weight.lin);
+                    }
+                    match other_routines.get(*routine_entt).map(|(lin, ang)| {
+                        BoidSteeringSystemOutput::get_active_res(lin, ang, &to_accel, &to_angvel)
+                    }) {
+                        Ok(Some(res)) => {
+                            let (lin, ang) = match res {
+                                BoidSteeringSystemOutput::Both { lin, ang } => {
+                                    (Some(lin), Some(ang))
+                                }
+                                BoidSteeringSystemOutput::LinOnly { lin } => (Some(lin), None),
+                                BoidSteeringSystemOutput::AngOnly { ang } => (None, Some(ang)),
+                            };
+                            // those without maps get interest along their output
+                            if let (Some(lin), None) = (lin, map) {
+                                context.add_interest(lin, frac_of_limit(lin) * weight.lin);
+                            }
+                            if let Some(ang) = ang {
+                                *ang_sum.get_or_insert(TVec3::ZERO) += ang * weight.ang;
+                            }
+                        }
+                        Ok(None) => {
+                            tracing::error!(
+                                ?routine_entt,
+                                "Routine doesn't have linear or angular results"
+                            );
+                            failed = true;
+                            break;
+                        }
+                        Err(err) => {
+                            tracing::error!(
+                                ?err,
+                                ?routine_entt,
+                                concat!("routine not found for ", stringify!(ContextSteering))
+                            );
+                            failed = true;
+                            break;
+                        }
+                    }
+                }
+                for routine_entt in dangers {
+                    if let Ok(map) = context_maps.get(*routine_entt) {
+                        context.merge(map, 0.);
+                        continue;
+                    }
+                    // those without maps get danger against the way they're steering
+                    match other_routines
+                        .get(*routine_entt)
+                        .map(|(lin, _)| lin.map(|lin| lin.to_accel(&to_accel)))
+                    {
+                        Ok(Some(lin)) => context.add_danger(-lin, frac_of_limit(lin)),
+                        Ok(None) => {
+                            tracing::error!(?routine_entt, "Routine doesn't have linear results");
+                            failed = true;
+                            break;
+                        }
+                        Err(err) => {
+                            tracing::error!(
+                                ?err,
+                                ?routine_entt,
+                                concat!("routine not found for ", stringify!(ContextSteering))
+                            );
+                            failed = true;
+                            break;
+                        }
+                    }
+                }
+                if failed {
+                    default()
+                } else {
+                    let lin = context
+                        .resolve(*danger_threshold)
+                        .map(|(dir, interest)| {
+                            xform.rotation
+                                * steering_behaviours::max_accel_along(inv_rot * dir, accel_limit)
+                                * interest.min(1.)
+                        })
+                        .unwrap_or_default();
+                    match ang_sum {
+                        Some(ang) => BoidSteeringSystemOutput::Both { lin, ang },
+                        None => BoidSteeringSystemOutput::LinOnly { lin },
+                    }
+                }
+            }
+            PrioritizedAllocation {
+                routines: prioritized,
+            } => {
+                let inv_rot = xform.rotation.inverse();
+                let mut budget = to_accel.actual_accel_limit.abs();
+                let mut lin = TVec3::ZERO;
+                let mut ang = None;
+                let mut failed = false;
+                for routine_entt in prioritized {
+                    match other_routines.get(*routine_entt).map(|(lin, ang)| {
+                        BoidSteeringSystemOutput::get_active_res(lin, ang, &to_accel, &to_angvel)
+                    }) {
+                        Ok(Some(res)) => {
+                            let (res_lin, res_ang) = match res {
+                                BoidSteeringSystemOutput::Both { lin, ang } => {
+                                    (Some(lin), Some(ang))
+                                }
+                                BoidSteeringSystemOutput::LinOnly { lin } => (Some(lin), None),
+                                BoidSteeringSystemOutput::AngOnly { ang } => (None, Some(ang)),
+                            };
+                            if ang.is_none() {
+                                ang = res_ang;
+                            }
+                            if let Some(res_lin) = res_lin {
+                                // allocated in the craft's basis where the limits are
+                                lin +=
+                                    xform.rotation * allocate_accel(&mut budget, inv_rot * res_lin);
+                            }
+                        }
+                        Ok(None) => {
+                            tracing::error!(
+                                ?routine_entt,
+                                "Routine doesn't have linear or angular results"
+                            );
+                            failed = true;
+                            break;
+                        }
+                        Err(err) => {
+                            tracing::error!(
+                                ?err,
+                                ?routine_entt,
+                                concat!(
+                                    "routine not found for ",
+                                    stringify!(PrioritizedAllocation)
+                                )
+                            );
+                            failed = true;
+                            break;
+                        }
+                    }
+                }
+                if failed {
+                    default()
+                } else {
+                    match ang {
+                        Some(ang) => BoidSteeringSystemOutput::Both { lin, ang },
+                        None => BoidSteeringSystemOutput::LinOnly { lin },
+                    }
+                }
+            }
         };
         let (lin, ang) = match active_res {
             BoidSteeringSystemOutput::Both { lin, ang } => (lin, ang),
             BoidSteeringSystemOutput::LinOnly { lin } => {
                 // Look at the direction you want to accelerate
                 // TODO: parameterize this
-                (lin, super::look_to(xform.rotation.inverse() * lin))
+                (
+                    lin,
+                    AngularRoutineOutput::LookDir(xform.rotation.inverse() * lin)
+                        .to_angvel(&to_angvel),
+                )
             }
             BoidSteeringSystemOutput::AngOnly { ang } => (TVec3::ZERO, ang),
         };
         *lin_out = LinearRoutineOutput::Accel(lin);
-        *ang_out = ang.into();
+        *ang_out = AngularRoutineOutput::AngVel(ang);
     }
 }
 
+/// Grants as much of `request` as fits in the per axis `budget` without changing its
+/// direction and takes that out of the budget. Both in the same basis.
+pub fn allocate_accel(budget: &mut TVec3, request: TVec3) -> TVec3 {
+    let mut scale: TReal = 1.;
+    for ii in 0..3 {
+        let wanted = request[ii].abs();
+        if wanted > TReal::EPSILON {
+            scale = scale.min(budget[ii].max(0.) / wanted);
+        }
+    }
+    let granted = request * scale;
+    *budget = (*budget - granted.abs()).max(TVec3::ZERO);
+    granted
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct SteeringRoutineWeight {
     pub lin: TReal,
@@ -315,12 +513,31 @@ pub enum SteeringRoutineComposer {
     PriorityOverride {
         routines: SVec<[Entity; 4]>,
     },
-    /// A variant of WeightSummed except with a single priority checked routine that goes first
+    /// A variant of WeightSummed except with priority checked avoidance routines that go first
     /// In order to avoid making a second composition layer for the common avoid collision case
     AvoidCollisionHelper {
-        avoid_collision: Entity,
+        /// The first one with a non zero output overrides the rest.
+        avoid_collision: SVec<[Entity; 2]>,
         routines: SVec<[(SteeringRoutineWeight, Entity); 2]>,
     },
+    /// Reynolds' prioritized acceleration allocation. Each routine, in order, gets as much of
+    /// what it's asking for as is left of the engine's acceleration limits.
+    /// The angular output's taken from the first routine that has one.
+    PrioritizedAllocation {
+        routines: SVec<[Entity; 4]>,
+    },
+    /// Picks the most interesting direction that's not too dangerous from the
+    /// [`ContextMap`]s of the routines. Avoids the cancelling out weighted sums suffer from.
+    ContextSteering {
+        /// Routines without a [`ContextMap`] add interest along their linear output.
+        /// Angular outputs are weight summed.
+        interests: SVec<[(SteeringRoutineWeight, Entity); 4]>,
+        /// Only the danger of these is used. Routines without a [`ContextMap`] add danger
+        /// against their linear output.
+        dangers: SVec<[Entity; 2]>,
+        /// Directions more dangerous than the safest one by this are masked out.
+        danger_threshold: TReal,
+    },
 }
 
 impl Default for SteeringRoutineComposer {
@@ -339,14 +556,22 @@ impl SteeringRoutineComposer {
                 routines.iter().map(|(_, entt)| *entt).collect()
             }
             SteeringRoutineComposer::PriorityOverride { routines } => routines.clone(),
+            SteeringRoutineComposer::PrioritizedAllocation { routines } => routines.clone(),
             SteeringRoutineComposer::AvoidCollisionHelper {
                 avoid_collision,
                 routines,
             } => {
-                let mut out = smallvec::smallvec![*avoid_collision];
+                let mut out: SVec<[Entity; 4]> = avoid_collision.iter().copied().collect();
                 out.extend(routines.iter().map(|(_, entt)| *entt));
                 out
             }
+            SteeringRoutineComposer::ContextSteering {
+                interests, dangers, ..
+            } => {
+                let mut out: SVec<[Entity; 4]> = interests.iter().map(|(_, entt)| *entt).collect();
+                out.extend(dangers.iter().copied());
+                out
+            }
         }
     }
 }
@@ -362,6 +587,7 @@ enum BoidSteeringSystemOutput {
     Both {
         /// world space accel
         lin: TVec3,
+        /// local space angvel
         ang: TVec3,
     },
     LinOnly {
@@ -369,6 +595,7 @@ enum BoidSteeringSystemOutput {
         lin: TVec3,
     },
     AngOnly {
+        /// local space angvel
         ang: TVec3,
     },
 }
@@ -457,17 +684,36 @@ impl BoidSteeringSystemOutput {
         lin_res: Option<&LinearRoutineOutput>,
         ang_res: Option<&AngularRoutineOutput>,
         to_accel: &ToAccelParams,
+        to_angvel: &ToAngVelParams,
     ) -> Option<Self> {
         match (lin_res, ang_res) {
             (Some(lin_res), Some(ang_res)) => Some(Self::Both {
                 lin: lin_res.to_accel(to_accel),
-                ang: ang_res.0,
+                ang: ang_res.to_angvel(to_angvel),
             }),
             (Some(lin_res), None) => Some(Self::LinOnly {
                 lin: lin_res.to_accel(to_accel),
             }),
-            (None, Some(ang_res)) => Some(Self::AngOnly { ang: ang_res.0 }),
+            (None, Some(ang_res)) => Some(Self::AngOnly {
+                ang: ang_res.to_angvel(to_angvel),
+            }),
             (None, None) => None,
         }
     }
 }
+
+#[test]
+fn allocate_accel_test() {
+    let mut budget = TVec3::new(10., 10., 10.);
+    // fits whole
+    let granted = allocate_accel(&mut budget, TVec3::new(4., 0., -2.));
+    assert!((granted - TVec3::new(4., 0., -2.)).length() < 1e-3);
+    assert!((budget - TVec3::new(6., 10., 8.)).length() < 1e-3);
+    // scaled down to what's left on x, direction kept
+    let granted = allocate_accel(&mut budget, TVec3::new(12., 6., 0.));
+    assert!((granted - TVec3::new(6., 3., 0.)).length() < 1e-3);
+    assert!(budget.x < 1e-3);
+    // nothing left on x
+    let granted = allocate_accel(&mut budget, TVec3::new(1., 1., 0.));
+    assert!(granted.length() < 1e-3);
+}
@@ -0,0 +1,120 @@
+use deps::*;
+
+use bevy::prelude::*;
+use once_cell::sync::Lazy;
+
+use crate::math::*;
+
+pub const CONTEXT_DIRECTION_COUNT: usize = 64;
+
+/// The fixed set of directions context maps are sampled over, in world basis.
+pub static CONTEXT_DIRECTIONS: Lazy<Vec<TVec3>> =
+    Lazy::new(|| crate::utils::points_on_sphere(CONTEXT_DIRECTION_COUNT));
+
+/// Interest and danger for each of the [`CONTEXT_DIRECTIONS`].
+/// Interest is in fractions of the craft's acceleration limit and danger is from zero to one.
+/// Routines that support context steering keep one of these next to their regular output.
+/// Compose them with [`super::compose::SteeringRoutineComposer::ContextSteering`].
+#[derive(Debug, Clone, Component)]
+pub struct ContextMap {
+    pub interest: Vec<TReal>,
+    pub danger: Vec<TReal>,
+}
+
+impl Default for ContextMap {
+    fn default() -> Self {
+        Self {
+            interest: vec![0.; CONTEXT_DIRECTION_COUNT],
+            danger: vec![0.; CONTEXT_DIRECTION_COUNT],
+        }
+    }
+}
+
+impl ContextMap {
+    pub fn clear(&mut self) {
+        self.interest.iter_mut().for_each(|val| *val = 0.);
+        self.danger.iter_mut().for_each(|val| *val = 0.);
+    }
+
+    /// Interest in the directions around `dir`, falling off with the angle to it.
+    pub fn add_interest(&mut self, dir: TVec3, weight: TReal) {
+        let dir = dir.normalize_or_zero();
+        for (ii, sample) in CONTEXT_DIRECTIONS.iter().enumerate() {
+            let dot = sample.dot(dir);
+            if dot > 0. {
+                self.interest[ii] = self.interest[ii].max(dot * weight);
+            }
+        }
+    }
+
+    /// Danger in the directions around `dir`, falling off with the angle to it.
+    pub fn add_danger(&mut self, dir: TVec3, weight: TReal) {
+        let dir = dir.normalize_or_zero();
+        for (ii, sample) in CONTEXT_DIRECTIONS.iter().enumerate() {
+            let dot = sample.dot(dir);
+            if dot > 0. {
+                self.danger[ii] = self.danger[ii].max((dot * weight).min(1.));
+            }
+        }
+    }
+
+    /// Interest is summed while danger takes the max.
+    pub fn merge(&mut self, other: &Self, interest_weight: TReal) {
+        for ii in 0..CONTEXT_DIRECTION_COUNT {
+            self.interest[ii] += other.interest[ii] * interest_weight;
+            self.danger[ii] = self.danger[ii].max(other.danger[ii]);
+        }
+    }
+
+    /// The most interesting direction that's not masked by danger and how interesting it is.
+    /// Directions that are more dangerous than the least dangerous one by `danger_threshold`
+    /// are masked so that there's always somewhere to go.
+    /// The pick's nudged towards its neighbours' interest so that it's not stuck to the samples.
+    pub fn resolve(&self, danger_threshold: TReal) -> Option<(TVec3, TReal)> {
+        let least_danger = self
+            .danger
+            .iter()
+            .copied()
+            .fold(TReal::INFINITY, TReal::min);
+        let unmasked = |ii: usize| self.danger[ii] <= least_danger + danger_threshold;
+        let best = (0..CONTEXT_DIRECTION_COUNT)
+            .filter(|ii| unmasked(*ii))
+            .max_by(|a, b| {
+                self.interest[*a]
+                    .partial_cmp(&self.interest[*b])
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })?;
+        let interest = self.interest[best];
+        if interest < TReal::EPSILON {
+            return None;
+        }
+        // the sample spacing, roughly
+        let neighbour_dot = 1. - (4. / CONTEXT_DIRECTION_COUNT as TReal);
+        let best_dir = CONTEXT_DIRECTIONS[best];
+        let dir = (0..CONTEXT_DIRECTION_COUNT)
+            .filter(|ii| unmasked(*ii) && CONTEXT_DIRECTIONS[*ii].dot(best_dir) > neighbour_dot)
+            .map(|ii| CONTEXT_DIRECTIONS[ii] * self.interest[ii])
+            .sum::<TVec3>()
+            .normalize_or_zero();
+        Some((dir, interest))
+    }
+}
+
+#[test]
+fn context_map_test() {
+    let mut map = ContextMap::default();
+    let ahead = TVec3::new(0., 0., -1.);
+    let up = TVec3::new(0., 1., 0.);
+    // wants to go ahead or up, ahead's blocked
+    map.add_interest(ahead, 1.);
+    map.add_interest(up, 0.5);
+    map.add_danger(ahead, 1.);
+    let (dir, interest) = map.resolve(0.1).unwrap();
+    assert!(interest > 0.);
+    assert!(dir.dot(ahead) < 0.5);
+    assert!(dir.dot(up) > 0.5);
+    // without the danger, it goes ahead
+    map.danger.iter_mut().for_each(|val| *val = 0.);
+    let (dir, _) = map.resolve(0.1).unwrap();
+    assert!(dir.dot(ahead) > 0.9);
+}
@@ -0,0 +1,54 @@
+use deps::*;
+
+use bevy::prelude::*;
+
+use super::{ActiveSteeringRoutine, LinOnlyRoutineBundle, LinearRoutineOutput, SteeringRoutine};
+use crate::{math::*, mind::sensors::Contacts};
+
+#[derive(Debug, Clone, Component)]
+pub enum Target {
+    /// Runs from where the contact's going to be. Outputs nothing if it's been lost.
+    Pursuer { rb: Entity },
+    /// Runs from the position, assumed to be in world basis.
+    Position { pos: TVec3 },
+}
+
+/// Gets away from the target. The counterpart of [`super::intercept::Intercept`].
+#[derive(Debug, Clone, Component)]
+pub struct Evade {
+    pub target: Target,
+    /// Will use the craft engine's config if None.
+    pub speed: Option<TReal>,
+    pub linvel_limit: TVec3,
+}
+
+pub type Bundle = LinOnlyRoutineBundle<Evade>;
+
+pub fn update(
+    mut routines: Query<
+        (&Evade, &SteeringRoutine, &mut LinearRoutineOutput),
+        With<ActiveSteeringRoutine>,
+    >,
+    boids: Query<(&Transform, &Contacts)>,
+    time: Res<Time>,
+) {
+    for (param, routine, mut output) in routines.iter_mut() {
+        let (xform, contacts) = boids
+            .get(routine.boid_entt())
+            .expect_or_log("craft entt not found for routine");
+        *output = match param.target {
+            Target::Pursuer { rb } => match contacts.get(rb) {
+                Some(pursuer) => super::steering_behaviours::evade_pursuer(
+                    xform.translation,
+                    param.speed.unwrap_or(param.linvel_limit.z),
+                    pursuer.estimated_pos(&time),
+                    pursuer.last_vel,
+                ),
+                None => default(),
+            },
+            Target::Position { pos } => {
+                super::steering_behaviours::flee_position(xform.translation, pos)
+            }
+        };
+    }
+}
@@ -1,13 +1,27 @@
 use deps::*;
 
-use super::{ActiveSteeringRoutine, AngOnlyRoutineBundle, AngularRoutineOutput, SteeringRoutine};
+use super::{
+    ActiveSteeringRoutine, AngOnlyRoutineBundle, AngularRoutineOutput, SteeringRoutine,
+    TrackedObjects,
+};
 use crate::math::*;
 use bevy::prelude::*;
 
 #[derive(Debug, Clone, Component)]
 pub enum Target {
-    /// must have a global xform
-    Object { entt: Entity },
+    /// Must have a [`GlobalTransform`]. Outputs nothing if it's gone.
+    Object {
+        entt: Entity,
+        /// In the object's local basis.
+        offset: TVec3,
+    },
+    /// Must have a [`GlobalTransform`]. Matches its orientation, roll included, and outputs
+    /// nothing if it's gone.
+    Align {
+        entt: Entity,
+        /// Orientation relative to the object's.
+        rot: TQuat,
+    },
     /// assumed to be in world basis
     Direction { dir: TVec3 },
 }
@@ -21,21 +35,33 @@ pub type Bundle = AngOnlyRoutineBundle<Face>;
 
 pub fn update(
     mut routines: Query<
-        (&Face, &SteeringRoutine, &mut AngularRoutineOutput),
+        (Entity, &Face, &SteeringRoutine, &mut AngularRoutineOutput),
         With<ActiveSteeringRoutine>,
     >,
-    objects: Query<&GlobalTransform>,
+    mut objects: TrackedObjects,
     boids: Query<(&Transform,)>,
 ) {
-    for (param, routine, mut output) in routines.iter_mut() {
+    for (routine_entt, param, routine, mut output) in routines.iter_mut() {
         let (xform,) = boids.get(routine.boid_entt()).unwrap_or_log();
-        let dir = match param.target {
-            Target::Object { entt } => {
-                let target_pos = objects.get(entt).unwrap_or_log().translation();
-                (target_pos - xform.translation).normalize()
+        *output = match param.target {
+            Target::Object { entt, offset } => {
+                match super::track_object(&mut objects, entt, offset, routine_entt) {
+                    Some(point) => AngularRoutineOutput::LookDir(
+                        xform.rotation.inverse()
+                            * (point.pos - xform.translation).normalize_or_zero(),
+                    ),
+                    None => default(),
+                }
+            }
+            Target::Align { entt, rot } => {
+                match super::track_object(&mut objects, entt, TVec3::ZERO, routine_entt) {
+                    Some(point) => AngularRoutineOutput::Orientation(point.rotation * rot),
+                    None => default(),
+                }
+            }
+            Target::Direction { dir } => {
+                AngularRoutineOutput::LookDir(xform.rotation.inverse() * dir)
             }
-            Target::Direction { dir } => dir,
         };
-        *output = super::look_to(xform.rotation.inverse() * dir).into();
     }
 }
@@ -4,10 +4,12 @@ use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
 
 use crate::craft::*;
+use crate::math::*;
 use crate::mind::flock::strategy::cas::*;
+use crate::mind::sensors::{spatial::SpatialIndex, ContactClass};
 
 use super::{
-    look_to, steering_behaviours, ActiveSteeringRoutine, AngularRoutineOutput,
+    steering_behaviours, ActiveSteeringRoutine, AngularRoutineOutput,
     CraftControllerConsts, LinAngRoutineBundle, LinearRoutineOutput, SteeringRoutine,
     ToAccelParams
 };
@@ -17,6 +19,11 @@ pub struct FlyWithFlock {
     pub flock_strategy_entt: Entity,
 }
 
+impl FlyWithFlock {
+    /// Crafts closer than this are steered away from, flockmates or not.
+    pub const SEPARATION_RADIUS: TReal = 100.;
+}
+
 pub type Bundle = LinAngRoutineBundle<FlyWithFlock>;
 
 pub fn update(
@@ -36,12 +43,18 @@ pub fn update(
         &engine::EngineConfig,
         &CraftControllerConsts,
     )>, // crafts
+    index: Res<SpatialIndex>,
 ) {
     for (param, routine, mut lin_out, mut ang_out) in routines.iter_mut() {
         let (xform, vel, config, consts) = crafts.get(routine.boid_entt).unwrap_or_log();
         let to_accel = ToAccelParams::new(vel.linvel, xform, config, consts);
 
         let cas = strategies.get(param.flock_strategy_entt).unwrap_or_log();
+        let neighbours: SVec<[TVec3; 8]> = index
+            .within_radius(xform.translation, FlyWithFlock::SEPARATION_RADIUS)
+            .filter(|item| item.class == ContactClass::Craft)
+            .map(|item| item.pos)
+            .collect();
         let (cohesion, allignment, separation) = (
             10. * steering_behaviours::cohesion(
                 xform.translation,
@@ -52,11 +65,11 @@ pub fn update(
             steering_behaviours::allignment(vel.linvel, cas.member_count, cas.vel_sum)
                 .to_accel(&to_accel),
             // NOTE: 10x multiplier
-            steering_behaviours::separation(xform.translation, &cas.craft_positions[..])
+            steering_behaviours::separation(xform.translation, &neighbours[..])
                 .to_accel(&to_accel),
         );
         *lin_out = LinearRoutineOutput::Accel(cohesion + allignment + separation);
         // *lin_out = (dir - TVec3::from(vel.linvel)).normalize_or_zero().into();
-        *ang_out = look_to(xform.rotation.inverse() * allignment).into();
+        *ang_out = AngularRoutineOutput::LookDir(xform.rotation.inverse() * allignment);
     }
 }
@@ -0,0 +1,149 @@
+use deps::*;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use super::{
+    ActiveSteeringRoutine, LinOnlyRoutineBundleExtra, LinearRoutineOutput, SteeringRoutine,
+};
+use crate::{math::*, mind::navigation};
+
+#[derive(Debug, Clone, Component)]
+pub enum Target {
+    /// Follow the given path.
+    Path { path: navigation::NavPath },
+    /// Plan a path there through [`navigation::Navigation`] and follow that.
+    Destination { pos: TVec3 },
+}
+
+/// Goes through the points of a path one after the other, coming to a stop at the last one.
+#[derive(Debug, Clone, Component)]
+pub struct FollowPath {
+    pub target: Target,
+    /// The clearance to plan for, usually the craft's bounding radius.
+    pub craft_radius: TReal,
+    /// How fast to go through the points on the way.
+    pub cruise_speed: TReal,
+    /// How close to get to a point before moving on to the next.
+    pub waypoint_radius: TReal,
+    /// In the craft's local basis.
+    pub avail_accel: TVec3,
+    /// How often to replan for [`Target::Destination`]s.
+    pub replan_secs: f64,
+}
+
+#[derive(Debug, Clone, Component, Default)]
+pub struct FollowPathState {
+    /// The last path planned. Kept while a new one's being searched for.
+    pub path: navigation::NavPath,
+    /// Index of the point currently headed for.
+    pub next: usize,
+    /// Reset whenever the [`FollowPath`] is changed.
+    pub planned_secs: Option<f64>,
+}
+
+impl FollowPathState {
+    fn follow(&mut self, path: navigation::NavPath, now_secs: f64) {
+        self.path = path;
+        self.next = 0;
+        self.planned_secs = Some(now_secs);
+    }
+
+    /// Brake where we are if there's no path to keep on following.
+    fn hold_if_pathless(&mut self, pos: TVec3) {
+        if self.path.points.is_empty() {
+            self.path = navigation::NavPath { points: vec![pos] };
+            self.next = 0;
+        }
+    }
+}
+
+pub type Bundle = LinOnlyRoutineBundleExtra<FollowPath, FollowPathState>;
+
+pub fn update(
+    mut routines: Query<
+        (
+            Entity,
+            &FollowPath,
+            ChangeTrackers<FollowPath>,
+            &mut FollowPathState,
+            &SteeringRoutine,
+            &mut LinearRoutineOutput,
+        ),
+        With<ActiveSteeringRoutine>,
+    >,
+    boids: Query<(&Transform, &Velocity)>,
+    mut nav: navigation::Navigation,
+    time: Res<Time>,
+) {
+    let now_secs = time.seconds_since_startup();
+    for (entt, param, param_tracker, mut state, routine, mut output) in routines.iter_mut() {
+        let (xform, vel) = boids
+            .get(routine.boid_entt())
+            .expect_or_log("craft entt not found for routine");
+        if param_tracker.is_changed() {
+            state.planned_secs = None;
+        }
+        match &param.target {
+            Target::Path { path } => {
+                if state.planned_secs.is_none() {
+                    state.follow(path.clone(), now_secs);
+                }
+            }
+            Target::Destination { pos } => {
+                let stale = state
+                    .planned_secs
+                    .map(|planned_secs| now_secs - planned_secs > param.replan_secs)
+                    .unwrap_or(true);
+                if stale {
+                    match nav.request_path(entt, xform.translation, *pos, param.craft_radius) {
+                        navigation::PathRequest::Ready(path) => state.follow(path, now_secs),
+                        // keep on the old path meanwhile
+                        navigation::PathRequest::Pending => {
+                            state.hold_if_pathless(xform.translation)
+                        }
+                        navigation::PathRequest::Failed => {
+                            tracing::debug!(?pos, "no path found, holding course");
+                            state.planned_secs = Some(now_secs);
+                            state.hold_if_pathless(xform.translation);
+                        }
+                    }
+                }
+            }
+        }
+
+        // move on from the points we've reached
+        let waypoint_radius_squared = param.waypoint_radius * param.waypoint_radius;
+        while state.next + 1 < state.path.points.len()
+            && state.path.points[state.next].distance_squared(xform.translation)
+                < waypoint_radius_squared
+        {
+            state.next += 1;
+        }
+        let point = match state.path.points.get(state.next) {
+            Some(point) => *point,
+            None => {
+                *output = default();
+                continue;
+            }
+        };
+        // pass through heading for the one after or stop if it's the last
+        let with_linvel = state
+            .path
+            .points
+            .get(state.next + 1)
+            .map(|after| (*after - point).normalize_or_zero() * param.cruise_speed)
+            .unwrap_or_default();
+        let inv_rot = xform.rotation.inverse();
+        *output = LinearRoutineOutput::Accel(
+            xform.rotation
+                * super::steering_behaviours::arrive_with_velocity(
+                    inv_rot * (point - xform.translation),
+                    inv_rot * vel.linvel,
+                    inv_rot * with_linvel,
+                    param.avail_accel.abs(),
+                    param.waypoint_radius,
+                ),
+        );
+    }
+}
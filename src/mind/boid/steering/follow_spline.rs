@@ -0,0 +1,118 @@
+use deps::*;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use super::{
+    ActiveSteeringRoutine, AngularRoutineOutput, LinAngRoutineBundleExtra, LinearRoutineOutput,
+    SteeringRoutine,
+};
+use crate::{math::*, mind::navigation::SplinePath};
+
+/// Flies along a [`SplinePath`] without stopping at the points, steering for a spot
+/// ahead of where the craft's predicted to be on it. Comes to a stop at the end of paths
+/// that aren't looped.
+#[derive(Debug, Clone, Component)]
+pub struct FollowSpline {
+    pub path: SplinePath,
+    /// Speed to be going at each of the path's points, interpolated in between.
+    /// A single value's used for the whole path.
+    pub speeds: SVec<[TReal; 4]>,
+    /// Slows down for the turns to keep the sideways acceleration under this.
+    pub max_lateral_accel: Option<TReal>,
+    /// How far ahead to predict the craft's position when finding where it is on the path.
+    pub prediction_secs: TReal,
+    /// How far ahead on the path to steer for.
+    pub lookahead_secs: TReal,
+    /// The lookahead won't go under this distance.
+    pub min_lookahead: TReal,
+    /// In the craft's local basis.
+    pub avail_accel: TVec3,
+    pub arrival_tolerance: TReal,
+}
+
+impl FollowSpline {
+    /// The speed profile at param `t`, the turns included.
+    pub fn speed_at(&self, t: TReal) -> TReal {
+        let speed = self.path.lerp_per_point(&self.speeds[..], t);
+        match self.max_lateral_accel {
+            Some(lateral_accel) => {
+                let curvature = self.path.curvature(t);
+                if curvature > TReal::EPSILON {
+                    speed.min((lateral_accel / curvature).sqrt())
+                } else {
+                    speed
+                }
+            }
+            None => speed,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Component, Default)]
+pub struct FollowSplineState {
+    /// How far along the path the craft was last frame.
+    pub distance: Option<TReal>,
+}
+
+pub type Bundle = LinAngRoutineBundleExtra<FollowSpline, FollowSplineState>;
+
+pub fn update(
+    mut routines: Query<
+        (
+            &FollowSpline,
+            &mut FollowSplineState,
+            &SteeringRoutine,
+            &mut LinearRoutineOutput,
+            &mut AngularRoutineOutput,
+        ),
+        With<ActiveSteeringRoutine>,
+    >,
+    boids: Query<(&Transform, &Velocity)>,
+) {
+    for (param, mut state, routine, mut lin_out, mut ang_out) in routines.iter_mut() {
+        let (xform, vel) = boids
+            .get(routine.boid_entt())
+            .expect_or_log("craft entt not found for routine");
+        let path = &param.path;
+        if path.segment_count() == 0 {
+            *lin_out = default();
+            *ang_out = default();
+            continue;
+        }
+        let speed = vel.linvel.length();
+        let predicted = xform.translation + (vel.linvel * param.prediction_secs);
+        // don't look too far from where we were on the path
+        let window = (speed * (param.prediction_secs + 1.)) + param.min_lookahead;
+        let dist = path.nearest_distance(predicted, state.distance.map(|dist| (dist, window)));
+        state.distance = Some(dist);
+
+        let lookahead =
+            (param.speed_at(path.param_at(dist)) * param.lookahead_secs).max(param.min_lookahead);
+        let inv_rot = xform.rotation.inverse();
+        if !path.looped() && dist + lookahead >= path.length() {
+            // come to a stop at the end
+            let end = path.position(path.segment_count() as TReal);
+            let accel = xform.rotation
+                * super::steering_behaviours::arrive_with_velocity(
+                    inv_rot * (end - xform.translation),
+                    inv_rot * vel.linvel,
+                    TVec3::ZERO,
+                    param.avail_accel.abs(),
+                    param.arrival_tolerance,
+                );
+            *lin_out = LinearRoutineOutput::Accel(accel);
+            *ang_out = AngularRoutineOutput::LookDir(
+                inv_rot * path.direction(path.segment_count() as TReal),
+            );
+            continue;
+        }
+        let t = path.param_at(dist + lookahead);
+        // pure pursuit: head for the spot ahead at the speed it calls for
+        let carrot = path.position(t);
+        *lin_out = LinearRoutineOutput::Vel(
+            (carrot - xform.translation).normalize_or_zero() * param.speed_at(t),
+        );
+        *ang_out = AngularRoutineOutput::LookDir(inv_rot * path.direction(t));
+    }
+}
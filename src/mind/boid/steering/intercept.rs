@@ -1,14 +1,14 @@
 use deps::*;
 
 use bevy::prelude::*;
-use bevy_rapier3d::prelude::*;
 
 use super::{ActiveSteeringRoutine, LinOnlyRoutineBundle, LinearRoutineOutput, SteeringRoutine};
-use crate::math::*;
+use crate::{math::*, mind::sensors::Contacts};
 
 #[derive(Debug, Clone, Component)]
 pub struct Intercept {
-    pub quarry_rb: Entity,
+    /// Outputs nothing if None.
+    pub quarry_rb: Option<Entity>,
     /// Will use the craft engine's config if None.
     pub speed: Option<TReal>,
     pub linvel_limit: TVec3,
@@ -21,21 +21,27 @@ pub fn update(
         (&Intercept, &SteeringRoutine, &mut LinearRoutineOutput),
         With<ActiveSteeringRoutine>,
     >,
-    boids: Query<(&Transform, &Velocity)>,
+    boids: Query<(&Transform, &Contacts)>,
+    time: Res<Time>,
 ) {
     for (param, routine, mut output) in routines.iter_mut() {
-        let (xform, _) = boids
+        let (xform, contacts) = boids
             .get(routine.boid_entt)
             .expect_or_log("craft entt not found for routine");
-        let (quarry_xform, quarry_vel) = boids
-            .get(param.quarry_rb)
-            .expect_or_log("quarry rigid body not found for on Intercept routine");
+        // nothing to go on if the quarry's been lost
+        let quarry = match param.quarry_rb.and_then(|entt| contacts.get(entt)) {
+            Some(contact) => contact,
+            None => {
+                *output = default();
+                continue;
+            }
+        };
         let travel_speed = param.speed.unwrap_or(param.linvel_limit.z);
         *output = super::steering_behaviours::intercept_target(
             xform.translation,
             travel_speed,
-            quarry_xform.translation,
-            quarry_vel.linvel,
+            quarry.estimated_pos(&time),
+            quarry.last_vel,
         );
         // *output = (dir - TVec3::from(vel.linvel)).normalize_or_zero().into();
     }
@@ -0,0 +1,86 @@
+use deps::*;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use rand::Rng;
+
+use super::{
+    ActiveSteeringRoutine, LinOnlyRoutineBundleExtra, LinearRoutineOutput, SteeringRoutine,
+};
+use crate::{math::*, mind::sensors::Contacts};
+
+/// Randomly changing lateral acceleration to spoil the firing solutions of whoever's
+/// shooting. Meant to be weight summed with whatever's doing the actual moving.
+#[derive(Debug, Clone, Component)]
+pub struct Jink {
+    /// The contact to jink across the line of fire of. Jinks across the craft's own
+    /// velocity if None or lost.
+    pub threat_rb: Option<Entity>,
+    /// A new direction is picked every so often, randomly within this range.
+    pub min_period_secs: f64,
+    pub max_period_secs: f64,
+    /// Zero to one. Fraction of the acceleration available in the jink direction to use.
+    pub intensity: TReal,
+    /// In the craft's local basis.
+    pub avail_accel: TVec3,
+}
+
+#[derive(Debug, Clone, Component, Default)]
+pub struct JinkState {
+    /// Around the threat bearing, in radians.
+    pub angle: TReal,
+    pub next_change_secs: f64,
+}
+
+pub type Bundle = LinOnlyRoutineBundleExtra<Jink, JinkState>;
+
+pub fn update(
+    mut routines: Query<
+        (
+            &Jink,
+            &mut JinkState,
+            &SteeringRoutine,
+            &mut LinearRoutineOutput,
+        ),
+        With<ActiveSteeringRoutine>,
+    >,
+    boids: Query<(&Transform, &Velocity, &Contacts)>,
+    time: Res<Time>,
+) {
+    let now_secs = time.seconds_since_startup();
+    let mut rng = rand::thread_rng();
+    for (param, mut state, routine, mut output) in routines.iter_mut() {
+        let (xform, vel, contacts) = boids
+            .get(routine.boid_entt())
+            .expect_or_log("craft entt not found for routine");
+        if now_secs >= state.next_change_secs {
+            state.angle = rng.gen_range(0.0..std::f64::consts::TAU) as TReal;
+            let period_secs = if param.max_period_secs > param.min_period_secs {
+                rng.gen_range(param.min_period_secs..param.max_period_secs)
+            } else {
+                param.min_period_secs
+            };
+            state.next_change_secs = now_secs + period_secs;
+        }
+        // the axis we want to be moving across
+        let bearing = param
+            .threat_rb
+            .and_then(|entt| contacts.get(entt))
+            .map(|threat| threat.estimated_pos(&time) - xform.translation)
+            .unwrap_or(vel.linvel)
+            .normalize_or_zero();
+        let bearing = if bearing.length_squared() > TReal::EPSILON {
+            bearing
+        } else {
+            xform.forward()
+        };
+        let (side, up) = bearing.any_orthonormal_pair();
+        let dir = (side * state.angle.cos()) + (up * state.angle.sin());
+
+        let inv_rot = xform.rotation.inverse();
+        let accel =
+            super::steering_behaviours::max_accel_along(inv_rot * dir, param.avail_accel.abs())
+                * param.intensity.clamp(0., 1.);
+        *output = LinearRoutineOutput::Accel(xform.rotation * accel);
+    }
+}
@@ -0,0 +1,154 @@
+use deps::*;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use super::{
+    ActiveSteeringRoutine, AngularRoutineOutput, LinAngRoutineBundle, LinearRoutineOutput,
+    SteeringRoutine, TrackedObjects,
+};
+use crate::math::*;
+
+/// Circles the target at a fixed radius while keeping an axis pointed at it.
+/// Good for strafing runs and broadsides.
+#[derive(Debug, Clone, Component)]
+pub struct Orbit {
+    /// Must have a [`GlobalTransform`]. Outputs nothing if it's gone.
+    pub target: Entity,
+    pub radius: TReal,
+    /// Normal of the plane to orbit in, in world basis. Orbits counter-clockwise
+    /// around it given a positive [`Self::angular_speed`].
+    pub plane_normal: TVec3,
+    /// In radians per second.
+    pub angular_speed: TReal,
+    /// The axis to keep pointed at the target in the craft's local basis. `-Z` is the nose,
+    /// `±X` gives broadsides.
+    pub facing_axis: TVec3,
+    /// In the craft's local basis.
+    pub avail_accel: TVec3,
+    /// Off the circle by less than this, the corrections ease off.
+    pub tolerance: TReal,
+}
+
+pub type Bundle = LinAngRoutineBundle<Orbit>;
+
+pub fn update(
+    mut routines: Query<
+        (
+            Entity,
+            &Orbit,
+            &SteeringRoutine,
+            &mut LinearRoutineOutput,
+            &mut AngularRoutineOutput,
+        ),
+        With<ActiveSteeringRoutine>,
+    >,
+    boids: Query<(&Transform, &Velocity)>,
+    mut objects: TrackedObjects,
+) {
+    for (routine_entt, param, routine, mut lin_out, mut ang_out) in routines.iter_mut() {
+        let (xform, vel) = boids
+            .get(routine.boid_entt())
+            .expect_or_log("craft entt not found for routine");
+        let center =
+            match super::track_object(&mut objects, param.target, TVec3::ZERO, routine_entt) {
+                Some(point) => point,
+                None => {
+                    *lin_out = default();
+                    *ang_out = default();
+                    continue;
+                }
+            };
+        *lin_out = LinearRoutineOutput::Accel(
+            xform.rotation
+                * orbit_accel(
+                    param,
+                    xform.rotation,
+                    xform.translation - center.pos,
+                    vel.linvel - center.linvel,
+                ),
+        );
+
+        let to_target = (center.pos - xform.translation).normalize_or_zero();
+        let facing_axis = xform.rotation * param.facing_axis.normalize_or_zero();
+        *ang_out = if to_target == TVec3::ZERO || facing_axis == TVec3::ZERO {
+            AngularRoutineOutput::AngVel(TVec3::ZERO)
+        } else {
+            // swing the facing axis onto the target
+            AngularRoutineOutput::Orientation(
+                TQuat::from_rotation_arc(facing_axis, to_target) * xform.rotation,
+            )
+        };
+    }
+}
+
+/// The acceleration to get onto the orbit and keep going round, in the craft's basis and
+/// within its [`Orbit::avail_accel`].
+/// `offset` and `rel_vel` are relative to the center in world basis.
+pub fn orbit_accel(param: &Orbit, rotation: TQuat, offset: TVec3, rel_vel: TVec3) -> TVec3 {
+    let normal = param.plane_normal.normalize_or_zero();
+    // our bearing on the orbit plane
+    let radial = {
+        let radial = offset - (normal * offset.dot(normal));
+        if radial.length_squared() > TReal::EPSILON {
+            radial.normalize()
+        } else {
+            normal.any_orthonormal_vector()
+        }
+    };
+    let tangent = normal.cross(radial);
+    let speed = param.angular_speed * param.radius;
+
+    // solved in the craft's basis where the acceleration limits are
+    let inv_rot = rotation.inverse();
+    let avail_accel = param.avail_accel.abs();
+    let correction = super::steering_behaviours::arrive_with_velocity(
+        inv_rot * ((radial * param.radius) - offset),
+        inv_rot * rel_vel,
+        inv_rot * (tangent * speed),
+        avail_accel,
+        param.tolerance,
+    );
+    // what it takes to keep going round
+    let centripetal = -radial * ((speed * speed) / param.radius.max(TReal::EPSILON));
+    (correction + (inv_rot * centripetal)).clamp(-avail_accel, avail_accel)
+}
+
+#[test]
+fn orbit_accel_test() {
+    let mut param = Orbit {
+        target: Entity::from_raw(0),
+        radius: 100.,
+        plane_normal: TVec3::Y,
+        angular_speed: 0.1,
+        facing_axis: -TVec3::Z,
+        avail_accel: TVec3::splat(10.),
+        tolerance: 1.,
+    };
+    // on the circle at speed, all that's needed is the pull towards the center
+    let offset = TVec3::X * 100.;
+    let on_orbit = TVec3::Y.cross(TVec3::X) * 10.;
+    let accel = orbit_accel(&param, TQuat::IDENTITY, offset, on_orbit);
+    assert!(accel.distance(-TVec3::X) < 1e-2, "{accel:?}");
+    // in the craft's basis
+    let rot = TQuat::from_rotation_y(real::consts::FRAC_PI_2);
+    let accel = orbit_accel(&param, rot, offset, on_orbit);
+    assert!((rot * accel).distance(-TVec3::X) < 1e-2, "{accel:?}");
+
+    // way off and too weak to make it, nothing over the limits
+    param.avail_accel = TVec3::new(0.5, 0.25, 0.5);
+    for (offset, vel) in [
+        (offset, on_orbit),
+        (TVec3::new(300., 50., -20.), TVec3::new(0., 0., 40.)),
+        (TVec3::new(5., 0., 5.), TVec3::ZERO),
+    ] {
+        let accel = orbit_accel(&param, rot, offset, vel);
+        assert!(
+            accel
+                .abs()
+                .cmple(param.avail_accel + TVec3::splat(1e-4))
+                .all(),
+            "{accel:?}"
+        );
+    }
+}
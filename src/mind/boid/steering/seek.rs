@@ -5,14 +5,18 @@ use bevy::prelude::*;
 
 use super::{
     steering_behaviours, ActiveSteeringRoutine, LinOnlyRoutineBundle, LinearRoutineOutput,
-    SteeringRoutine,
+    SteeringRoutine, TrackedObjects,
 };
 use crate::math::*;
 
 #[derive(Debug, Clone, Component)]
 pub enum Target {
-    /// must have a global xform
-    Object { entt: Entity },
+    /// Must have a [`GlobalTransform`]. Outputs nothing if it's gone.
+    Object {
+        entt: Entity,
+        /// In the object's local basis.
+        offset: TVec3,
+    },
     /// assumed to be in world basis
     Position { pos: TVec3 },
 }
@@ -26,16 +30,24 @@ pub type Bundle = LinOnlyRoutineBundle<Seek>;
 
 pub fn update(
     mut routines: Query<
-        (&Seek, &SteeringRoutine, &mut LinearRoutineOutput),
+        (Entity, &Seek, &SteeringRoutine, &mut LinearRoutineOutput),
         With<ActiveSteeringRoutine>,
     >,
     boids: Query<(&Transform,)>,
-    objects: Query<&GlobalTransform>,
+    mut objects: TrackedObjects,
 ) {
-    for (param, routine, mut output) in routines.iter_mut() {
+    for (routine_entt, param, routine, mut output) in routines.iter_mut() {
         let (xform,) = boids.get(routine.boid_entt()).unwrap_or_log();
         let pos = match param.target {
-            Target::Object { entt } => objects.get(entt).unwrap_or_log().translation(),
+            Target::Object { entt, offset } => {
+                match super::track_object(&mut objects, entt, offset, routine_entt) {
+                    Some(point) => point.pos,
+                    None => {
+                        *output = default();
+                        continue;
+                    }
+                }
+            }
             Target::Position { pos } => pos,
         };
         *output = steering_behaviours::seek_position(xform.translation, pos);
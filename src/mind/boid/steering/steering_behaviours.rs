@@ -275,6 +275,45 @@ pub fn intercept_target(
     )
 }
 
+/// Outputs the direction to move towards, directly away from the threat.
+#[inline(always)]
+pub fn flee_position(current_pos: TVec3, threat_pos: TVec3) -> LinearRoutineOutput {
+    LinearRoutineOutput::Dir((current_pos - threat_pos).normalize_or_zero())
+}
+
+/// Outputs the direction to move towards, away from where the pursuer's going to be.
+/// The counterpart of [`intercept_target`].
+#[inline]
+pub fn evade_pursuer(
+    current_pos: TVec3,
+    travel_speed: TReal,
+    pursuer_pos: TVec3,
+    pursuer_vel: TVec3,
+) -> LinearRoutineOutput {
+    flee_position(
+        current_pos,
+        find_intercept_pos(current_pos, travel_speed, pursuer_pos, pursuer_vel),
+    )
+}
+
+/// The largest acceleration along `dir` that fits in the per axis `accel_limit`.
+/// Both in the same basis.
+#[inline]
+pub fn max_accel_along(dir: TVec3, accel_limit: TVec3) -> TVec3 {
+    let dir = dir.normalize_or_zero();
+    let mut scale = TReal::INFINITY;
+    for ii in 0..3 {
+        if dir[ii].abs() > TReal::EPSILON {
+            scale = scale.min(accel_limit[ii].abs() / dir[ii].abs());
+        }
+    }
+    if scale.is_finite() {
+        dir * scale
+    } else {
+        TVec3::ZERO
+    }
+}
+
 /// Outputs the direction to move towards.
 /// Assumes the current craft's in the flock.
 #[inline]
@@ -417,6 +456,158 @@ pub fn dst_to_change(cur: TReal, target: TReal, accel: TReal) -> TReal {
     dist.abs()
 }
 
+/// The position on the switching curve of the time-optimal control for a body that's to
+/// reach the origin moving at `goal_vel` for when it's moving at `vel`.
+/// Bodies on the curve get there by accelerating at full tilt towards `goal_vel`.
+#[inline]
+fn switching_curve(vel: TReal, goal_vel: TReal, accel: TReal) -> TReal {
+    if vel <= goal_vel {
+        ((vel * vel) - (goal_vel * goal_vel)) / (2. * accel)
+    } else {
+        ((goal_vel * goal_vel) - (vel * vel)) / (2. * accel)
+    }
+}
+
+/// Least time it takes for a body on a single axis at `pos` moving at `vel` to get to the
+/// origin moving at `goal_vel` if it can accelerate at `accel` either way, i.e. under
+/// bang-bang control.
+pub fn bang_bang_time(pos: TReal, vel: TReal, goal_vel: TReal, accel: TReal) -> TReal {
+    if accel < TReal::EPSILON {
+        return if pos.abs() < TReal::EPSILON && (vel - goal_vel).abs() < TReal::EPSILON {
+            0.
+        } else {
+            TReal::INFINITY
+        };
+    }
+    let switch = pos - switching_curve(vel, goal_vel, accel);
+    if switch > 0. {
+        // full reverse till we hit the curve then full forward
+        let switch_vel =
+            -((((2. * accel * pos) + (vel * vel) + (goal_vel * goal_vel)) * 0.5).max(0.)).sqrt();
+        ((vel - switch_vel) + (goal_vel - switch_vel)) / accel
+    } else if switch < 0. {
+        // full forward till we hit the curve then full reverse
+        let switch_vel =
+            ((((-2. * accel * pos) + (vel * vel) + (goal_vel * goal_vel)) * 0.5).max(0.)).sqrt();
+        ((switch_vel - vel) + (switch_vel - goal_vel)) / accel
+    } else {
+        (goal_vel - vel).abs() / accel
+    }
+}
+
+/// The acceleration for the time-optimal control of [`bang_bang_time`].
+/// Within `boundary` of the switching curve, the output's eased to avoid chattering
+/// between the limits from frame to frame.
+pub fn bang_bang_accel(
+    pos: TReal,
+    vel: TReal,
+    goal_vel: TReal,
+    accel: TReal,
+    boundary: TReal,
+) -> TReal {
+    if accel < TReal::EPSILON {
+        return 0.;
+    }
+    let boundary = boundary.max(TReal::EPSILON);
+    let switch = pos - switching_curve(vel, goal_vel, accel);
+    // sized for a critically damped approach once we're near the goal
+    let vel_boundary = (0.5 * (accel * boundary).sqrt()).max(TReal::EPSILON);
+    let along_curve = -accel * ((vel - goal_vel) / vel_boundary).clamp(-1., 1.);
+    (along_curve - (accel * switch / boundary)).clamp(-accel, accel)
+}
+
+/// Per axis acceleration to get to a goal `offset` away moving at `goal_vel` in the least time.
+/// Axes that'd get there early are held back so that all of them arrive together which keeps
+/// the path straight-ish.
+/// All the vectors, including the per axis `accel_limit`, are expected to be in the same basis,
+/// usually the craft's local space.
+pub fn arrive_with_velocity(
+    offset: TVec3,
+    vel: TVec3,
+    goal_vel: TVec3,
+    accel_limit: TVec3,
+    boundary: TReal,
+) -> TVec3 {
+    let pos = -offset;
+    let times = [0, 1, 2].map(|ii| bang_bang_time(pos[ii], vel[ii], goal_vel[ii], accel_limit[ii]));
+    let sync_time = times
+        .iter()
+        .copied()
+        .filter(|time| time.is_finite())
+        .fold(0., TReal::max);
+    let mut accel = TVec3::ZERO;
+    for ii in 0..3 {
+        let mut limit = accel_limit[ii];
+        if times[ii] < sync_time {
+            // find the acceleration that'd take just as long
+            let (mut lo, mut hi) = (0., limit);
+            for _ in 0..12 {
+                let mid = 0.5 * (lo + hi);
+                if bang_bang_time(pos[ii], vel[ii], goal_vel[ii], mid) > sync_time {
+                    lo = mid;
+                } else {
+                    hi = mid;
+                }
+            }
+            limit = hi;
+        }
+        accel[ii] = bang_bang_accel(pos[ii], vel[ii], goal_vel[ii], limit, boundary);
+    }
+    accel
+}
+
+#[test]
+fn arrive_with_velocity_test() {
+    // from rest to rest, accelerate for half the way and brake for the other
+    let time = bang_bang_time(-10., 0., 0., 1.);
+    assert!((time - (2. * 10.0_f32.sqrt() as TReal)).abs() < 1e-3);
+    // already there
+    assert!(bang_bang_time(0., 5., 5., 1.) < 1e-3);
+
+    // simulate
+    let dt = 1. / 60.;
+    let accel_limit = TVec3::new(6., 6., 9.);
+    let goal_vel = TVec3::new(0., 0., -10.);
+    let (mut pos, mut vel) = (TVec3::new(-300., 150., 400.), TVec3::new(20., 0., 0.));
+    let mut goal = TVec3::ZERO;
+    let goal_linvel = TVec3::new(5., 0., 0.);
+    let mut closest = TReal::INFINITY;
+    for _ in 0..(60 * 60) {
+        let accel = arrive_with_velocity(goal - pos, vel - goal_linvel, goal_vel, accel_limit, 1.);
+        assert!(accel.abs().cmple(accel_limit + TVec3::splat(1e-3)).all());
+        vel += accel * dt;
+        pos += vel * dt;
+        goal += goal_linvel * dt;
+        let dst = pos.distance(goal);
+        if dst < closest {
+            closest = dst;
+            if dst < 1. {
+                // made it with about the right velocity, the easing costs a little
+                assert!((vel - goal_linvel).distance(goal_vel) < 2.);
+                return;
+            }
+        }
+    }
+    panic!("never arrived, closest: {closest}");
+}
+
+#[test]
+fn evade_test() {
+    // pursuer coming in from the side ahead of us
+    let out = evade_pursuer(
+        TVec3::ZERO,
+        100.,
+        TVec3::new(100., 0., -100.),
+        TVec3::new(-50., 0., 0.),
+    )
+    .get_dir();
+    // away from it and away from where it's headed
+    assert!(out.z > 0.);
+    assert!(out.x < 0.);
+    let accel = max_accel_along(TVec3::new(1., 1., 0.), TVec3::new(10., 5., 5.));
+    assert!((accel - TVec3::new(5., 5., 0.)).length() < 1e-3);
+}
+
 #[test]
 fn zmblo() {
     let to_target: Vec3 = [10., 10., 0.].into();
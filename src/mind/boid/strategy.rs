@@ -2,12 +2,16 @@ use deps::*;
 
 use bevy::prelude::*;
 use bevy_inspector_egui::Inspectable;
+use bevy_rapier3d::prelude::*;
 
 use crate::craft::*;
+use crate::math::*;
 use crate::mind::*;
 
 pub mod attack_persue;
+pub mod behavior_tree;
 pub mod custom;
+pub mod dock;
 pub mod form;
 pub mod run_circuit;
 
@@ -113,7 +117,12 @@ where
 #[derive(Debug, Clone, Default, Inspectable, Component)]
 pub struct BoidStrategyOutput {
     pub steering_routine: Option<Entity>,
-    pub fire_weapons: bool,
+    /// The craft's [`arms::WeaponGroups`] to fire.
+    pub fire_orders: arms::FireOrders,
+    /// Weapons will only be fired if they have a firing solution on this target.
+    /// Must have [`GlobalTransform`] and [`Velocity`] components.
+    /// If `None`, the ordered weapons are fired regardless of where they're pointed.
+    pub fire_target: Option<Entity>,
 }
 
 pub type BoidStrategyKind = std::any::TypeId;
@@ -144,20 +153,40 @@ pub struct CurrentBoidStrategy {
 }
 
 /// This system assigns the [`SteeringRoutineComposer`] emitted by the strategy to the craft
-/// and fires weapon.
+/// and fires the ordered weapon groups at the target.
 /// TODO: use change tracking to avoid work
 pub fn craft_boid_strategy_output_mgr(
     mut crafts: Query<(
+        Entity,
+        &GlobalTransform,
         &mut boid::steering::CurrentSteeringRoutine,
         &CurrentBoidStrategy,
         &sensors::CraftWeaponsIndex,
+        &arms::WeaponGroups,
+        &boid::BoidMindConfig,
+        &crate::Colliders,
+        &Velocity,
+        &sensors::Contacts,
     )>,
     strategies: Query<&BoidStrategyOutput>,
     mut activate_wpn_events: EventWriter<arms::ActivateWeaponEvent>,
-    weapons: Query<&arms::WeaponActivationState>,
+    weapons: Query<(&arms::WeaponActivationState, &GlobalTransform)>,
+    mut los: sensors::los::LineOfSight,
     time: Res<Time>,
 ) {
-    for (mut cur_routine, mind, wpn_index) in crafts.iter_mut() {
+    for (
+        craft_entt,
+        craft_xform,
+        mut cur_routine,
+        mind,
+        wpn_index,
+        wpn_groups,
+        config,
+        craft_colliders,
+        craft_vel,
+        contacts,
+    ) in crafts.iter_mut()
+    {
         let strategy = match mind.strategy {
             Some(s) => s,
             None => continue,
@@ -167,19 +196,93 @@ pub fn craft_boid_strategy_output_mgr(
             .expect_or_log("active BoidStrategy not found");
         cur_routine.routine = output.steering_routine;
 
-        if output.fire_weapons {
-            for wpn in wpn_index.entt_to_desc.keys() {
-                if weapons
-                    .get(*wpn)
-                    .expect_or_log("Indexed weapon has no WeaponActivationState")
-                    .can_activate(&time)
-                {
-                    activate_wpn_events.send(arms::ActivateWeaponEvent { weapon_id: *wpn });
+        if output.fire_orders.is_empty() {
+            continue;
+        }
+        let target = match output.fire_target.map(|entt| (entt, contacts.get(entt))) {
+            Some((entt, Some(contact))) => {
+                let pos = contact.estimated_pos(&time);
+                // don't waste rounds on the rocks it's hiding behind
+                if !los.between(craft_entt, craft_xform.translation(), entt, pos) {
+                    continue;
+                }
+                Some((entt, pos, contact.last_vel))
+            }
+            Some((entt, None)) => {
+                tracing::debug!("fire_target {entt:?} not in contacts, holding fire");
+                continue;
+            }
+            None => None,
+        };
+        for wpn in wpn_index.ordered(wpn_groups, output.fire_orders) {
+            let (activation_state, wpn_xform) = weapons
+                .get(wpn)
+                .expect_or_log("Indexed weapon has no WeaponActivationState");
+            if !activation_state.can_activate(&time) {
+                continue;
+            }
+            let desc = wpn_index
+                .entt_to_desc
+                .get(&wpn)
+                .expect_or_log("WeaponDesc not found for indexed weapon");
+            let wpn_pos = wpn_xform.translation();
+            let (target_entt, aim_pos) = match target {
+                Some((target_entt, target_pos, target_vel)) => {
+                    match firing_solution(
+                        wpn_pos,
+                        wpn_xform.forward(),
+                        craft_vel.linvel,
+                        desc,
+                        target_pos,
+                        target_vel,
+                        config.aim_tolerance_radians,
+                    ) {
+                        Some(pos) => (Some(target_entt), pos),
+                        None => continue,
+                    }
                 }
+                // untargeted fire goes wherever the weapon's pointing
+                None => (None, wpn_pos + (wpn_xform.forward() * desc.range)),
+            };
+            // check the line of fire for anything that's not the target, flock-mates included
+            let line_of_fire_clear =
+                los.line_of_fire(wpn, wpn_pos, target_entt, aim_pos, &|entt| {
+                    // our own colliders
+                    craft_colliders.set.contains(&entt)
+                });
+            if !line_of_fire_clear {
+                continue;
             }
+            activate_wpn_events.send(arms::ActivateWeaponEvent { weapon_id: wpn });
         }
     }
 }
+
+/// Returns the aim point if a weapon at `wpn_pos` facing `wpn_fwd` can hit the target.
+/// I.e. if the target's within reach of the weapon's [`sensors::Ballistics`] and the aim point's
+/// within the `aim_tolerance_radians` cone about `wpn_fwd`. Doesn't check the line of fire.
+pub fn firing_solution(
+    wpn_pos: TVec3,
+    wpn_fwd: TVec3,
+    shooter_vel: TVec3,
+    desc: &sensors::WeaponDesc,
+    target_pos: TVec3,
+    target_vel: TVec3,
+    aim_tolerance_radians: TReal,
+) -> Option<TVec3> {
+    let solution = desc
+        .ballistics
+        .solve_lead(wpn_pos, shooter_vel, target_pos, target_vel)?;
+    let offset = solution.aim_pos - wpn_pos;
+    if offset.length_squared() < TReal::EPSILON {
+        return None;
+    }
+    if wpn_fwd.angle_between(offset) > aim_tolerance_radians {
+        return None;
+    }
+    Some(solution.aim_pos)
+}
+
 /*
 pub fn craft_boid_strategy_output_mgr(
     mut crafts: Query<(
@@ -208,3 +311,87 @@ pub fn craft_boid_strategy_output_mgr(
     }
 }
 */
+
+#[test]
+fn firing_solution_test() {
+    let desc = sensors::WeaponDesc {
+        kind: arms::WeaponKind::of::<arms::ProjectileWeapon>(),
+        ballistics: sensors::Ballistics {
+            speed: 100.,
+            lifespan_secs: 2.,
+            ..default()
+        },
+        range: 200.,
+        class: "cannon",
+        damage_type: default(),
+        automatic: false,
+    };
+    let tolerance = 5. * (real::consts::PI / 180.);
+    let target_pos = -TVec3::Z * 100.;
+
+    // dead ahead
+    let aim_pos = firing_solution(
+        TVec3::ZERO,
+        -TVec3::Z,
+        TVec3::ZERO,
+        &desc,
+        target_pos,
+        TVec3::ZERO,
+        tolerance,
+    )
+    .unwrap();
+    assert!(aim_pos.distance(target_pos) < 0.001);
+
+    // crossing fast enough that the lead falls outside the cone
+    assert!(firing_solution(
+        TVec3::ZERO,
+        -TVec3::Z,
+        TVec3::ZERO,
+        &desc,
+        target_pos,
+        TVec3::X * 50.,
+        tolerance,
+    )
+    .is_none());
+    // but not if we're pointed at the lead
+    let lead_dir = desc
+        .ballistics
+        .solve_lead(TVec3::ZERO, TVec3::ZERO, target_pos, TVec3::X * 50.)
+        .unwrap()
+        .aim_pos
+        .normalize();
+    assert!(firing_solution(
+        TVec3::ZERO,
+        lead_dir,
+        TVec3::ZERO,
+        &desc,
+        target_pos,
+        TVec3::X * 50.,
+        tolerance,
+    )
+    .is_some());
+
+    // pointed away
+    assert!(firing_solution(
+        TVec3::ZERO,
+        TVec3::Z,
+        TVec3::ZERO,
+        &desc,
+        target_pos,
+        TVec3::ZERO,
+        tolerance,
+    )
+    .is_none());
+
+    // out of reach
+    assert!(firing_solution(
+        TVec3::ZERO,
+        -TVec3::Z,
+        TVec3::ZERO,
+        &desc,
+        -TVec3::Z * 300.,
+        TVec3::ZERO,
+        tolerance,
+    )
+    .is_none());
+}
@@ -1,17 +1,23 @@
 use deps::*;
 
 use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
 
 use super::{ActiveBoidStrategy, BoidStrategy, BoidStrategyBundleExtra, BoidStrategyOutput};
 use crate::{
     craft::*,
     math::*,
-    mind::{boid::steering::*, sensors::*},
+    mind::{
+        boid::{steering::*, targeting},
+        sensors::*,
+    },
 };
 
 #[derive(Debug, Clone, Component)]
 pub struct AttackPersue {
-    pub quarry_rb: Entity,
+    /// An explicit quarry overrides the boid's [`targeting::CurrentTarget`].
+    /// Leave as None to follow the current target instead.
+    pub quarry_rb: Option<Entity>,
     pub attacking_range: TReal,
 }
 
@@ -19,7 +25,8 @@ pub struct AttackPersue {
 pub struct AttackPersueState {
     pub composer_routine: Option<Entity>,
     pub intercept_routine: Option<Entity>,
-    pub intercept_wpn_speed: Option<Entity>,
+    /// Seeks the weapons' lead point.
+    pub aim_routine: Option<Entity>,
     pub avoid_collision: Option<Entity>,
 }
 
@@ -37,33 +44,24 @@ pub fn butler(
         ),
         Added<AttackPersue>,
     >,
-    mut crafts: ParamSet<(
-        Query<(
-            &engine::EngineConfig,
-            &CraftDimensions,
-            &SteeringRoutinesIndex,
-            &CraftWeaponsIndex,
-        )>,
-        Query<(&CraftWeaponsIndex, &BoidStrategyIndex), Changed<CraftWeaponsIndex>>,
+    crafts: Query<(
+        &engine::EngineConfig,
+        &CraftDimensions,
+        &SteeringRoutinesIndex,
     )>,
-    mut routines: Query<&mut intercept::Intercept>,
+    shared_avoid_collision: SharedRoutines<avoid_collision::AvoidCollision>,
 ) {
-    let p0 = crafts.p0();
     for (strategy_entt, param, strategy, mut state, mut out) in added_strategies.iter_mut() {
-        let (engine_config, dim, routines_idx, wpns, ..) = p0
+        let (engine_config, dim, routines_idx, ..) = crafts
             .get(strategy.boid_entt())
             .expect_or_log("craft not found for BoidStrategy");
 
         let raycast_toi_modifier = dim.max_element();
         let cast_shape_radius = raycast_toi_modifier * 0.5;
-        let (avoid_collision, intercept_routine, intercept_wpn_speed) =
-            commands.entity(strategy_entt).add_children(|par| {
-                (
-                    // routines_idx
-                    //     .kind::<avoid_collision::AvoidCollision>()
-                    //     .map(|v| v[0])
-                    //     .unwrap_or_else(|| {
-                    //     }),
+        let avoid_collision = routines_idx
+            .reusable(&shared_avoid_collision)
+            .unwrap_or_else(|| {
+                commands.entity(strategy.boid_entt()).add_children(|par| {
                     par.spawn()
                         .insert_bundle(avoid_collision::Bundle::new(
                             avoid_collision::AvoidCollision::new(
@@ -73,33 +71,36 @@ pub fn butler(
                             strategy.boid_entt(),
                             default(),
                         ))
-                        .id(),
-                    par.spawn()
-                        .insert_bundle(intercept::Bundle::new(
-                            intercept::Intercept {
-                                quarry_rb: param.quarry_rb,
-                                linvel_limit: engine_config.linvel_limit,
-                                speed: None,
-                            },
-                            strategy.boid_entt(),
-                        ))
-                        .id(),
-                    par.spawn()
-                        .insert_bundle(intercept::Bundle::new(
-                            intercept::Intercept {
-                                quarry_rb: param.quarry_rb,
-                                linvel_limit: engine_config.linvel_limit,
-                                speed: if wpns.avg_projectile_speed > 0. {
-                                    Some(wpns.avg_projectile_speed)
-                                } else {
-                                    None
-                                },
-                            },
-                            strategy.boid_entt(),
-                        ))
-                        .id(),
-                )
+                        .insert(SharedSteeringRoutine)
+                        .id()
+                })
             });
+        let (intercept_routine, aim_routine) = commands.entity(strategy_entt).add_children(|par| {
+            (
+                par.spawn()
+                    .insert_bundle(intercept::Bundle::new(
+                        intercept::Intercept {
+                            quarry_rb: param.quarry_rb,
+                            linvel_limit: engine_config.linvel_limit,
+                            speed: None,
+                        },
+                        strategy.boid_entt(),
+                    ))
+                    // we swap between these two so keep them around
+                    .insert(PinnedSteeringRoutine)
+                    .id(),
+                par.spawn()
+                    .insert_bundle(seek::Bundle::new(
+                        seek::Seek {
+                            // set to the lead point before it's used
+                            target: seek::Target::Position { pos: TVec3::ZERO },
+                        },
+                        strategy.boid_entt(),
+                    ))
+                    .insert(PinnedSteeringRoutine)
+                    .id(),
+            )
+        });
         let compose = commands.entity(strategy_entt).add_children(|p| {
             p.spawn()
                 .insert_bundle(compose::Bundle::new(
@@ -114,29 +115,17 @@ pub fn butler(
         });
 
         state.intercept_routine = Some(intercept_routine);
-        state.intercept_wpn_speed = Some(intercept_wpn_speed);
+        state.aim_routine = Some(aim_routine);
         state.avoid_collision = Some(avoid_collision);
         state.composer_routine = Some(compose);
 
         *out = BoidStrategyOutput {
             steering_routine: Some(compose),
-            fire_weapons: false,
+            fire_orders: default(),
+            fire_target: None,
         };
         commands.entity(strategy_entt).insert(ActiveBoidStrategy);
     }
-    for (weapons, strategy_index) in crafts.p1().iter() {
-        if let Some(entts) = strategy_index.kind::<AttackPersue>() {
-            for strategy in entts {
-                if let Ok((_, _, _, state, ..)) = added_strategies.get(*strategy) {
-                    if let Some(entt) = state.intercept_wpn_speed {
-                        if let Ok(mut routine) = routines.get_mut(entt) {
-                            routine.speed = Some(weapons.avg_projectile_speed)
-                        }
-                    }
-                }
-            }
-        }
-    }
 }
 
 #[allow(clippy::if_same_then_else)]
@@ -150,25 +139,56 @@ pub fn update(
         ),
         With<ActiveBoidStrategy>,
     >,
-    crafts: Query<&Transform>, // crafts
+    crafts: Query<(&Transform, &Velocity, &Contacts, &targeting::CurrentTarget)>, // crafts
+    armaments: Query<(&CraftWeaponsIndex, &arms::WeaponGroups)>,
     mut composers: Query<(&mut compose::Compose,)>,
+    mut aim_routines: Query<&mut seek::Seek>,
+    mut intercept_routines: Query<&mut intercept::Intercept>,
+    time: Res<Time>,
 ) {
     for (param, strategy, state, mut out) in strategies.iter_mut() {
-        let xform = crafts
+        let (xform, vel, contacts, target) = crafts
             .get(strategy.boid_entt())
             .expect_or_log("craft xform not found for CraftStrategy boid_entt");
-        let quarry_xform = crafts
-            .get(param.quarry_rb)
-            .expect_or_log("quarry_xform not found for on AttackPersue strategy");
-
-        let target_distance_squared =
-            (quarry_xform.translation - xform.translation).length_squared();
-        let target_direction = (quarry_xform.translation - xform.translation).normalize();
+        let (wpn_index, wpn_groups) = armaments
+            .get(strategy.boid_entt())
+            .expect_or_log("craft weapons not found for CraftStrategy boid_entt");
 
         let (mut composer,) = composers
             .get_mut(state.composer_routine.unwrap_or_log())
             .unwrap_or_log();
 
+        // orders trump whatever we'd pick ourselves
+        let quarry_rb = param.quarry_rb.or(target.entt);
+        {
+            let mut intercept = intercept_routines
+                .get_mut(state.intercept_routine.unwrap_or_log())
+                .unwrap_or_log();
+            if intercept.quarry_rb != quarry_rb {
+                intercept.quarry_rb = quarry_rb;
+            }
+        }
+
+        let (quarry_rb, quarry_pos, quarry_vel) =
+            match quarry_rb.and_then(|entt| contacts.get(entt).map(|contact| (entt, contact))) {
+                Some((entt, contact)) => (entt, contact.estimated_pos(&time), contact.last_vel),
+                None => {
+                    // lost track of the quarry
+                    out.fire_orders = arms::FireOrders::NONE;
+                    out.fire_target = None;
+                    match &mut composer.composer {
+                        compose::SteeringRoutineComposer::PriorityOverride { routines } => {
+                            routines[1] = state.intercept_routine.unwrap_or_log();
+                        }
+                        _ => unreachable!(),
+                    }
+                    continue;
+                }
+            };
+
+        let target_distance_squared = (quarry_pos - xform.translation).length_squared();
+        let target_direction = (quarry_pos - xform.translation).normalize();
+
         // if beyond range
         let (fire_wpns, second_routine) =
             if target_distance_squared > (param.attacking_range * param.attacking_range) {
@@ -180,10 +200,8 @@ pub fn update(
                 let fwdness = xform.forward().dot(target_direction);
                 // ahead
                 if fwdness > DIRECTION_DETERMINATION_COS_THRESHOLD {
-                    (
-                        1. - fwdness < crate::math::real::EPSILON * 10_000.,
-                        state.intercept_wpn_speed.unwrap_or_log(),
-                    )
+                    // the aim's checked per weapon by `craft_boid_strategy_output_mgr`
+                    (true, state.aim_routine.unwrap_or_log())
                 }
                 // aside
                 else if fwdness < -DIRECTION_DETERMINATION_COS_THRESHOLD {
@@ -194,7 +212,36 @@ pub fn update(
                     (false, state.intercept_routine.unwrap_or_log())
                 }
             };
-        out.fire_weapons = fire_wpns;
+        if fire_wpns {
+            // aim the first group that can reach the quarry
+            let aim_pos = (0..wpn_groups.groups.len())
+                .find_map(|group| {
+                    wpn_index.group_lead(
+                        wpn_groups,
+                        group,
+                        xform.translation,
+                        vel.linvel,
+                        quarry_pos,
+                        quarry_vel,
+                    )
+                })
+                .unwrap_or(quarry_pos);
+            aim_routines
+                .get_mut(state.aim_routine.unwrap_or_log())
+                .unwrap_or_log()
+                .target = seek::Target::Position { pos: aim_pos };
+        }
+        out.fire_orders = arms::FireOrders::NONE;
+        out.fire_target = Some(quarry_rb);
+        if fire_wpns {
+            // only fire the groups that can reach the quarry
+            for group in 0..wpn_groups.groups.len() {
+                if let Some(range) = wpn_index.group_range(wpn_groups, group) {
+                    out.fire_orders
+                        .set(group, target_distance_squared < range * range);
+                }
+            }
+        }
         match &mut composer.composer {
             compose::SteeringRoutineComposer::PriorityOverride { routines } => {
                 routines[1] = second_routine;
@@ -0,0 +1,649 @@
+use deps::*;
+
+use bevy::prelude::*;
+
+use super::{ActiveBoidStrategy, BoidStrategy, BoidStrategyBundleExtra, BoidStrategyOutput};
+use crate::craft::{arms::FireOrders, *};
+use crate::mind::{
+    blackboard::Blackboard,
+    boid::{
+        steering::*,
+        strategy::custom::description::{CompositionDesc, DescriptionContext},
+        targeting,
+    },
+};
+
+pub mod description;
+
+use description::{Condition, NodeDesc};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Running,
+    Success,
+    Failure,
+}
+
+/// A compiled [`NodeDesc`]. Children are indices into the [`Tree`].
+#[derive(Debug, Clone)]
+pub enum Node {
+    Sequence(SVec<[usize; 4]>),
+    Selector(SVec<[usize; 4]>),
+    Parallel {
+        children: SVec<[usize; 4]>,
+        success_threshold: usize,
+    },
+    Invert(usize),
+    Succeed(usize),
+    Repeat {
+        child: usize,
+        times: Option<u32>,
+    },
+    Timeout {
+        child: usize,
+        secs: f64,
+    },
+    Guard {
+        condition: Condition,
+        child: usize,
+    },
+    Steer {
+        composition: CompositionDesc,
+        secs: Option<f64>,
+    },
+    Wait {
+        secs: f64,
+    },
+    Check(Condition),
+    WaitFor(Condition),
+    Fire {
+        orders: FireOrders,
+        at_target: bool,
+        secs: Option<f64>,
+    },
+}
+
+impl Node {
+    fn children(&self) -> &[usize] {
+        match self {
+            Node::Sequence(children)
+            | Node::Selector(children)
+            | Node::Parallel { children, .. } => children,
+            Node::Invert(child)
+            | Node::Succeed(child)
+            | Node::Repeat { child, .. }
+            | Node::Timeout { child, .. }
+            | Node::Guard { child, .. } => std::slice::from_ref(child),
+            _ => &[],
+        }
+    }
+}
+
+/// The nodes of a behavior tree laid out flat so that their run state can be kept alongside.
+#[derive(Debug, Clone)]
+pub struct Tree {
+    nodes: Vec<Node>,
+    root: usize,
+}
+
+impl Tree {
+    pub fn new(desc: &NodeDesc) -> Self {
+        let mut nodes = vec![];
+        let root = Self::compile(&mut nodes, desc);
+        Self { nodes, root }
+    }
+
+    /// Children go in before their parents.
+    fn compile(nodes: &mut Vec<Node>, desc: &NodeDesc) -> usize {
+        let node = match desc {
+            NodeDesc::Sequence(children) => Node::Sequence(
+                children
+                    .iter()
+                    .map(|child| Self::compile(nodes, child))
+                    .collect(),
+            ),
+            NodeDesc::Selector(children) => Node::Selector(
+                children
+                    .iter()
+                    .map(|child| Self::compile(nodes, child))
+                    .collect(),
+            ),
+            NodeDesc::Parallel {
+                children,
+                success_threshold,
+            } => Node::Parallel {
+                children: children
+                    .iter()
+                    .map(|child| Self::compile(nodes, child))
+                    .collect(),
+                success_threshold: success_threshold.unwrap_or(children.len()),
+            },
+            NodeDesc::Invert(child) => Node::Invert(Self::compile(nodes, child)),
+            NodeDesc::Succeed(child) => Node::Succeed(Self::compile(nodes, child)),
+            NodeDesc::Repeat { child, times } => Node::Repeat {
+                child: Self::compile(nodes, child),
+                times: *times,
+            },
+            NodeDesc::Timeout { child, secs } => Node::Timeout {
+                child: Self::compile(nodes, child),
+                secs: *secs,
+            },
+            NodeDesc::Guard { condition, child } => Node::Guard {
+                condition: condition.clone(),
+                child: Self::compile(nodes, child),
+            },
+            NodeDesc::Steer { composition, secs } => Node::Steer {
+                composition: composition.clone(),
+                secs: *secs,
+            },
+            NodeDesc::Wait { secs } => Node::Wait { secs: *secs },
+            NodeDesc::Check(condition) => Node::Check(condition.clone()),
+            NodeDesc::WaitFor(condition) => Node::WaitFor(condition.clone()),
+            NodeDesc::Fire {
+                groups,
+                at_target,
+                secs,
+            } => Node::Fire {
+                orders: groups.iter().fold(FireOrders::NONE, |mut orders, group| {
+                    orders.set(*group, true);
+                    orders
+                }),
+                at_target: *at_target,
+                secs: *secs,
+            },
+        };
+        nodes.push(node);
+        nodes.len() - 1
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct NodeState {
+    pub running: bool,
+    /// How far along a sequence or selector is or how many times a repeat has gone.
+    pub cursor: usize,
+    pub entered_secs: f64,
+    /// How the node finished since its parent was entered. Parallels use it to avoid
+    /// rerunning their finished children.
+    pub result: Option<Status>,
+    /// The compose routine of a running steer leaf.
+    pub routine: Option<Entity>,
+}
+
+/// What the nodes get to work with when ticked.
+pub struct TickContext<'a, 'w, 's> {
+    pub commands: &'a mut Commands<'w, 's>,
+    pub strategy_entt: Entity,
+    pub strategy: &'a BoidStrategy,
+    pub description: DescriptionContext,
+    pub blackboard: Option<&'a Blackboard>,
+    pub target: Option<Entity>,
+    pub now_secs: f64,
+    pub output: &'a mut BoidStrategyOutput,
+}
+
+impl TickContext<'_, '_, '_> {
+    #[inline]
+    fn holds(&self, condition: &Condition) -> bool {
+        condition.holds(self.blackboard, self.target, self.now_secs)
+    }
+}
+
+/// Runs a behavior tree in a [`BoidStrategy`]. The tree's restarted whenever it finishes.
+#[derive(Debug, Clone, Component)]
+pub struct BehaviorTree {
+    pub tree: std::sync::Arc<Tree>,
+}
+
+impl BehaviorTree {
+    pub fn new(desc: &NodeDesc) -> Self {
+        Self {
+            tree: std::sync::Arc::new(Tree::new(desc)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Component, Default)]
+pub struct BehaviorTreeState {
+    pub nodes: Vec<NodeState>,
+}
+
+impl BehaviorTreeState {
+    pub fn new(tree: &Tree) -> Self {
+        Self {
+            nodes: vec![default(); tree.len()],
+        }
+    }
+
+    pub fn tick(&mut self, tree: &Tree, ctx: &mut TickContext) -> Status {
+        self.tick_node(tree, tree.root, ctx)
+    }
+
+    /// Cancels all the running nodes.
+    pub fn cancel(&mut self, tree: &Tree, ctx: &mut TickContext) {
+        if self.nodes[tree.root].running {
+            self.exit(tree, tree.root, ctx);
+        }
+    }
+
+    fn tick_node(&mut self, tree: &Tree, idx: usize, ctx: &mut TickContext) -> Status {
+        let node = &tree.nodes[idx];
+        if !self.nodes[idx].running {
+            self.nodes[idx] = NodeState {
+                running: true,
+                entered_secs: ctx.now_secs,
+                ..default()
+            };
+            for child in node.children() {
+                self.nodes[*child].result = None;
+            }
+        }
+        let elapsed = ctx.now_secs - self.nodes[idx].entered_secs;
+        let status = match node {
+            Node::Sequence(children) => loop {
+                match children.get(self.nodes[idx].cursor) {
+                    None => break Status::Success,
+                    Some(child) => match self.tick_node(tree, *child, ctx) {
+                        Status::Success => self.nodes[idx].cursor += 1,
+                        status => break status,
+                    },
+                }
+            },
+            Node::Selector(children) => loop {
+                match children.get(self.nodes[idx].cursor) {
+                    None => break Status::Failure,
+                    Some(child) => match self.tick_node(tree, *child, ctx) {
+                        Status::Failure => self.nodes[idx].cursor += 1,
+                        status => break status,
+                    },
+                }
+            },
+            Node::Parallel {
+                children,
+                success_threshold,
+            } => {
+                let (mut successes, mut failures) = (0, 0);
+                for child in children {
+                    let status = match self.nodes[*child].result {
+                        Some(status) => status,
+                        None => self.tick_node(tree, *child, ctx),
+                    };
+                    match status {
+                        Status::Success => successes += 1,
+                        Status::Failure => failures += 1,
+                        Status::Running => {}
+                    }
+                }
+                if successes >= *success_threshold {
+                    Status::Success
+                } else if children.len() - failures < *success_threshold {
+                    Status::Failure
+                } else {
+                    Status::Running
+                }
+            }
+            Node::Invert(child) => match self.tick_node(tree, *child, ctx) {
+                Status::Success => Status::Failure,
+                Status::Failure => Status::Success,
+                Status::Running => Status::Running,
+            },
+            Node::Succeed(child) => match self.tick_node(tree, *child, ctx) {
+                Status::Running => Status::Running,
+                _ => Status::Success,
+            },
+            Node::Repeat { child, times } => match self.tick_node(tree, *child, ctx) {
+                Status::Success => {
+                    self.nodes[idx].cursor += 1;
+                    // the child's rerun next tick so that instant successes don't spin
+                    match times {
+                        Some(times) if self.nodes[idx].cursor >= *times as usize => Status::Success,
+                        _ => Status::Running,
+                    }
+                }
+                status => status,
+            },
+            Node::Timeout { child, secs } => {
+                if elapsed > *secs {
+                    Status::Failure
+                } else {
+                    self.tick_node(tree, *child, ctx)
+                }
+            }
+            Node::Guard { condition, child } => {
+                if ctx.holds(condition) {
+                    self.tick_node(tree, *child, ctx)
+                } else {
+                    Status::Failure
+                }
+            }
+            Node::Steer { composition, secs } => {
+                let routine = match self.nodes[idx].routine {
+                    Some(routine) => routine,
+                    None => {
+                        let routine = self.spawn_steering(composition, ctx);
+                        self.nodes[idx].routine = Some(routine);
+                        routine
+                    }
+                };
+                ctx.output.steering_routine = Some(routine);
+                match secs {
+                    Some(secs) if elapsed >= *secs => Status::Success,
+                    _ => Status::Running,
+                }
+            }
+            Node::Wait { secs } => {
+                if elapsed >= *secs {
+                    Status::Success
+                } else {
+                    Status::Running
+                }
+            }
+            Node::Check(condition) => {
+                if ctx.holds(condition) {
+                    Status::Success
+                } else {
+                    Status::Failure
+                }
+            }
+            Node::WaitFor(condition) => {
+                if ctx.holds(condition) {
+                    Status::Success
+                } else {
+                    Status::Running
+                }
+            }
+            Node::Fire {
+                orders,
+                at_target,
+                secs,
+            } => {
+                ctx.output.fire_orders = *orders;
+                ctx.output.fire_target = if *at_target { ctx.target } else { None };
+                match secs {
+                    Some(secs) if elapsed >= *secs => Status::Success,
+                    _ => Status::Running,
+                }
+            }
+        };
+        if status != Status::Running {
+            self.nodes[idx].result = Some(status);
+            self.exit(tree, idx, ctx);
+        }
+        status
+    }
+
+    /// Stops the node and whatever it's got running, cleaning up after the leaves.
+    fn exit(&mut self, tree: &Tree, idx: usize, ctx: &mut TickContext) {
+        let node = &tree.nodes[idx];
+        for child in node.children() {
+            if self.nodes[*child].running {
+                self.exit(tree, *child, ctx);
+            }
+        }
+        let state = &mut self.nodes[idx];
+        state.running = false;
+        if let Some(routine) = state.routine.take() {
+            ctx.commands.entity(routine).despawn_recursive();
+            if ctx.output.steering_routine == Some(routine) {
+                ctx.output.steering_routine = None;
+            }
+        }
+        if let Node::Fire { .. } = node {
+            ctx.output.fire_orders = FireOrders::NONE;
+            ctx.output.fire_target = None;
+        }
+    }
+
+    fn spawn_steering(&self, composition: &CompositionDesc, ctx: &mut TickContext) -> Entity {
+        let compose = ctx
+            .commands
+            .entity(ctx.strategy_entt)
+            .add_children(|p| p.spawn().id());
+        let composer =
+            composition
+                .composition(ctx.description)
+                .spawn(ctx.commands, compose, ctx.strategy);
+        ctx.commands
+            .entity(compose)
+            .insert_bundle(compose::Bundle::new(
+                compose::Compose { composer },
+                ctx.strategy.boid_entt(),
+            ))
+            // parallel branches might not be steering at the moment
+            .insert(PinnedSteeringRoutine);
+        compose
+    }
+}
+
+pub type Bundle = BoidStrategyBundleExtra<BehaviorTree, BehaviorTreeState>;
+
+pub fn butler(
+    mut commands: Commands,
+    mut added_strategies: Query<
+        (
+            Entity,
+            &BehaviorTree,
+            &mut BehaviorTreeState,
+            &mut BoidStrategyOutput,
+        ),
+        Added<BehaviorTree>,
+    >,
+) {
+    for (strategy_entt, param, mut state, mut out) in added_strategies.iter_mut() {
+        *state = BehaviorTreeState::new(&param.tree);
+        *out = default();
+        commands.entity(strategy_entt).insert(ActiveBoidStrategy);
+    }
+}
+
+pub fn update(
+    mut commands: Commands,
+    mut strategies: Query<
+        (
+            Entity,
+            &BehaviorTree,
+            &BoidStrategy,
+            &mut BehaviorTreeState,
+            &mut BoidStrategyOutput,
+        ),
+        With<ActiveBoidStrategy>,
+    >,
+    crafts: Query<(
+        &engine::EngineConfig,
+        &CraftDimensions,
+        &targeting::CurrentTarget,
+        Option<&Blackboard>,
+    )>,
+    time: Res<Time>,
+) {
+    let now_secs = time.seconds_since_startup();
+    for (strategy_entt, param, strategy, mut state, mut out) in strategies.iter_mut() {
+        let (engine_config, dim, target, blackboard) = crafts
+            .get(strategy.boid_entt())
+            .expect_or_log("craft not found for BoidStrategy");
+        let mut ctx = TickContext {
+            commands: &mut commands,
+            strategy_entt,
+            strategy,
+            description: DescriptionContext {
+                boid_entt: strategy.boid_entt(),
+                dimensions: dim.0,
+                accel_limit: engine_config.actual_accel_limit(),
+                linvel_limit: engine_config.linvel_limit,
+            },
+            blackboard,
+            target: target.entt,
+            now_secs,
+            output: &mut *out,
+        };
+        let status = state.tick(&param.tree, &mut ctx);
+        if status != Status::Running {
+            tracing::trace!(
+                ?strategy_entt,
+                ?status,
+                "behavior tree finished, restarting"
+            );
+        }
+    }
+}
+
+#[test]
+fn behavior_tree_test() {
+    use crate::math::*;
+    use bevy::ecs::system::CommandQueue;
+
+    let desc: NodeDesc = ron::from_str(
+        "Selector([
+            Sequence([
+                Check(UnderFire),
+                Fire(groups: [0]),
+            ]),
+            Parallel(
+                children: [Wait(secs: 1.), WaitFor(HasTarget)],
+                success_threshold: Some(1),
+            ),
+        ])",
+    )
+    .unwrap();
+    let tree = Tree::new(&desc);
+    assert_eq!(tree.len(), 6);
+
+    let world = World::new();
+    let mut queue = CommandQueue::default();
+    let mut commands = Commands::new(&mut queue, &world);
+    let boid_entt = Entity::from_raw(0);
+    let strategy = BoidStrategy::new(boid_entt, std::any::TypeId::of::<BehaviorTree>());
+    let mut output = BoidStrategyOutput::default();
+    let mut state = BehaviorTreeState::new(&tree);
+    let mut tick = |state: &mut BehaviorTreeState, now_secs, target| {
+        let mut ctx = TickContext {
+            commands: &mut commands,
+            strategy_entt: Entity::from_raw(1),
+            strategy: &strategy,
+            description: DescriptionContext {
+                boid_entt,
+                dimensions: TVec3::ONE,
+                accel_limit: TVec3::ONE,
+                linvel_limit: TVec3::ONE,
+            },
+            blackboard: None,
+            target,
+            now_secs,
+            output: &mut output,
+        };
+        state.tick(&tree, &mut ctx)
+    };
+    // not under fire so we fall through to waiting
+    assert_eq!(tick(&mut state, 0., None), Status::Running);
+    assert_eq!(tick(&mut state, 0.5, None), Status::Running);
+    // the target shows up before the wait's over
+    assert_eq!(tick(&mut state, 0.6, Some(boid_entt)), Status::Success);
+    // and it starts over
+    assert!(!state.nodes[tree.root].running);
+    assert_eq!(tick(&mut state, 0.7, None), Status::Running);
+    assert_eq!(tick(&mut state, 1.8, None), Status::Success);
+}
+
+#[test]
+fn behavior_tree_cancellation_test() {
+    use crate::math::*;
+    use bevy::ecs::system::CommandQueue;
+
+    // ticks the tree checking the statuses then checks that nothing's left steering or firing
+    let run = |desc: &str, ticks: &[(f64, bool, Status)]| {
+        let desc: NodeDesc = ron::from_str(desc).unwrap();
+        let tree = Tree::new(&desc);
+        let mut world = World::new();
+        let boid_entt = world.spawn().id();
+        let strategy_entt = world.spawn().id();
+        let strategy = BoidStrategy::new(boid_entt, std::any::TypeId::of::<BehaviorTree>());
+        let mut output = BoidStrategyOutput::default();
+        let mut state = BehaviorTreeState::new(&tree);
+        let mut queue = CommandQueue::default();
+        let mut steered = None;
+        {
+            let mut commands = Commands::new(&mut queue, &world);
+            for (ii, (now_secs, has_target, expected)) in ticks.iter().enumerate() {
+                let status = {
+                    let mut ctx = TickContext {
+                        commands: &mut commands,
+                        strategy_entt,
+                        strategy: &strategy,
+                        description: DescriptionContext {
+                            boid_entt,
+                            dimensions: TVec3::ONE,
+                            accel_limit: TVec3::ONE,
+                            linvel_limit: TVec3::ONE,
+                        },
+                        blackboard: None,
+                        target: has_target.then_some(boid_entt),
+                        now_secs: *now_secs,
+                        output: &mut output,
+                    };
+                    state.tick(&tree, &mut ctx)
+                };
+                assert_eq!(status, *expected, "tick {ii}");
+                if status == Status::Running {
+                    assert!(output.steering_routine.is_some());
+                    assert!(!output.fire_orders.is_empty());
+                    steered = steered.or(output.steering_routine);
+                }
+            }
+        }
+        assert_eq!(output.steering_routine, None);
+        assert!(output.fire_orders.is_empty());
+        assert_eq!(output.fire_target, None);
+        assert!(state
+            .nodes
+            .iter()
+            .all(|node| !node.running && node.routine.is_none()));
+        queue.apply(&mut world);
+        let steered = steered.expect("never steered");
+        assert!(
+            world.get_entity(steered).is_none(),
+            "steer routine left behind"
+        );
+        assert_eq!(world.query::<&SteeringRoutine>().iter(&world).count(), 0);
+    };
+
+    // the guard's condition stops holding
+    run(
+        "Guard(
+            condition: HasTarget,
+            child: Parallel(children: [
+                Steer(composition: Single(Arrive(at_pos: (0., 0., 0.)))),
+                Fire(groups: [0], at_target: true),
+            ]),
+        )",
+        &[
+            (0., true, Status::Running),
+            (0.5, true, Status::Running),
+            (1., false, Status::Failure),
+        ],
+    );
+    // the child runs over
+    run(
+        "Timeout(
+            secs: 1.,
+            child: Parallel(children: [
+                Steer(composition: Single(Arrive(at_pos: (0., 0., 0.)))),
+                Fire(groups: [1]),
+            ]),
+        )",
+        &[
+            (0., false, Status::Running),
+            (0.5, false, Status::Running),
+            (1.5, false, Status::Failure),
+        ],
+    );
+}
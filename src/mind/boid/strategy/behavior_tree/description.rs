@@ -0,0 +1,244 @@
+use deps::*;
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::craft::arms::{WeaponGroupId, WeaponGroups};
+use crate::mind::{
+    blackboard::{self, Blackboard},
+    boid::{strategy::custom::description::CompositionDesc, BoidMindDirective},
+};
+
+/// Something the tree can check about the craft's situation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "deps::serde")]
+pub enum Condition {
+    /// There's an [`blackboard::UnderFire`] on the blackboard.
+    UnderFire,
+    MemberUnderFire,
+    AttackOrder,
+    /// The craft's got a [`crate::mind::boid::targeting::CurrentTarget`].
+    HasTarget,
+    Not(Box<Condition>),
+}
+
+impl Condition {
+    pub fn holds(
+        &self,
+        blackboard: Option<&Blackboard>,
+        target: Option<Entity>,
+        now_secs: f64,
+    ) -> bool {
+        let on_board = |contains: fn(&Blackboard, f64) -> bool| {
+            blackboard
+                .map(|board| contains(board, now_secs))
+                .unwrap_or(false)
+        };
+        match self {
+            Condition::UnderFire => on_board(Blackboard::contains::<blackboard::UnderFire>),
+            Condition::MemberUnderFire => {
+                on_board(Blackboard::contains::<blackboard::MemberUnderFire>)
+            }
+            Condition::AttackOrder => on_board(Blackboard::contains::<blackboard::AttackOrder>),
+            Condition::HasTarget => target.is_some(),
+            Condition::Not(condition) => !condition.holds(blackboard, target, now_secs),
+        }
+    }
+}
+
+/// A serializable description of a behavior tree. Author them in `.bt.ron` files and have
+/// crafts run them through [`BoidMindDirective::BehaviorTree`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "deps::serde")]
+pub enum NodeDesc {
+    /// Runs the children in order till one of them fails.
+    Sequence(Vec<NodeDesc>),
+    /// Runs the children in order till one of them succeeds.
+    Selector(Vec<NodeDesc>),
+    /// Runs all the children at once. Succeeds once `success_threshold` of them succeed,
+    /// all of them if not set, and fails once that's no longer possible.
+    Parallel {
+        children: Vec<NodeDesc>,
+        #[serde(default)]
+        success_threshold: Option<usize>,
+    },
+    Invert(Box<NodeDesc>),
+    /// Succeeds whenever the child's done.
+    Succeed(Box<NodeDesc>),
+    /// Runs the child till it fails or has succeeded `times`, forever if not set.
+    Repeat {
+        child: Box<NodeDesc>,
+        #[serde(default)]
+        times: Option<u32>,
+    },
+    /// Cancels the child and fails if it runs over.
+    Timeout {
+        child: Box<NodeDesc>,
+        secs: f64,
+    },
+    /// Cancels the child and fails as soon as the condition stops holding.
+    Guard {
+        condition: Condition,
+        child: Box<NodeDesc>,
+    },
+    /// Steers the craft with the composition while running. Runs for `secs` if set or till
+    /// cancelled otherwise.
+    Steer {
+        composition: CompositionDesc,
+        #[serde(default)]
+        secs: Option<f64>,
+    },
+    Wait {
+        secs: f64,
+    },
+    /// Succeeds if the condition holds, fails otherwise.
+    Check(Condition),
+    /// Runs till the condition holds.
+    WaitFor(Condition),
+    /// Fires the weapon groups while running. Runs for `secs` if set or till cancelled
+    /// otherwise.
+    Fire {
+        groups: Vec<WeaponGroupId>,
+        /// Only fire if the weapons have a solution on the craft's current target. On by
+        /// default. Untargeted fire still holds when anything's in front of the weapon.
+        #[serde(default = "default_at_target")]
+        at_target: bool,
+        #[serde(default)]
+        secs: Option<f64>,
+    },
+}
+
+fn default_at_target() -> bool {
+    true
+}
+
+/// What's wrong with a tree description that parsed fine.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvalidTree {
+    WeaponGroupOutOfBounds(WeaponGroupId),
+}
+
+impl std::fmt::Display for InvalidTree {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InvalidTree::WeaponGroupOutOfBounds(group) => write!(
+                f,
+                "weapon group {group} out of bounds, there are only {} groups",
+                WeaponGroups::MAX_GROUPS
+            ),
+        }
+    }
+}
+
+impl std::error::Error for InvalidTree {}
+
+impl NodeDesc {
+    pub fn children(&self) -> &[NodeDesc] {
+        match self {
+            NodeDesc::Sequence(children)
+            | NodeDesc::Selector(children)
+            | NodeDesc::Parallel { children, .. } => children,
+            NodeDesc::Invert(child)
+            | NodeDesc::Succeed(child)
+            | NodeDesc::Repeat { child, .. }
+            | NodeDesc::Timeout { child, .. }
+            | NodeDesc::Guard { child, .. } => std::slice::from_ref(&**child),
+            NodeDesc::Steer { .. }
+            | NodeDesc::Wait { .. }
+            | NodeDesc::Check(_)
+            | NodeDesc::WaitFor(_)
+            | NodeDesc::Fire { .. } => &[],
+        }
+    }
+
+    /// Catches what the parser can't, like weapon groups the crafts can't have.
+    pub fn validate(&self) -> Result<(), InvalidTree> {
+        if let NodeDesc::Fire { groups, .. } = self {
+            if let Some(group) = groups
+                .iter()
+                .find(|group| **group >= WeaponGroups::MAX_GROUPS)
+            {
+                return Err(InvalidTree::WeaponGroupOutOfBounds(*group));
+            }
+        }
+        self.children().iter().try_for_each(NodeDesc::validate)
+    }
+}
+
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "0b9e6c1a-5d3f-4e27-8a4c-7f2e9d1b6a35"]
+pub struct BehaviorTreeAsset(pub NodeDesc);
+
+#[derive(Default)]
+pub struct BehaviorTreeLoader;
+
+impl AssetLoader for BehaviorTreeLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let desc: NodeDesc = ron::de::from_bytes(bytes)?;
+            desc.validate()?;
+            load_context.set_default_asset(LoadedAsset::new(BehaviorTreeAsset(desc)));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["bt.ron"]
+    }
+}
+
+/// Has crafts running a tree respawn their strategy when it's (re)loaded.
+pub fn behavior_tree_reloader(
+    mut events: EventReader<AssetEvent<BehaviorTreeAsset>>,
+    mut boids: Query<&mut BoidMindDirective>,
+) {
+    for event in events.iter() {
+        let handle = match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+            AssetEvent::Removed { .. } => continue,
+        };
+        for mut directive in boids.iter_mut() {
+            if matches!(&*directive, BoidMindDirective::BehaviorTree { tree } if tree == handle) {
+                directive.set_changed();
+            }
+        }
+    }
+}
+
+#[test]
+fn behavior_tree_description_test() {
+    let desc: NodeDesc = ron::from_str(include_str!(
+        "../../../../../assets/behavior_trees/hold_and_evade.bt.ron"
+    ))
+    .unwrap();
+    assert_eq!(desc.validate(), Ok(()));
+    assert_eq!(super::Tree::new(&desc).len(), 9);
+
+    let desc: NodeDesc = ron::from_str(
+        "Sequence([
+            Wait(secs: 1.),
+            Guard(condition: HasTarget, child: Fire(groups: [0, 8])),
+        ])",
+    )
+    .unwrap();
+    assert_eq!(desc.validate(), Err(InvalidTree::WeaponGroupOutOfBounds(8)));
+
+    // fire's aimed unless asked otherwise
+    let desc: NodeDesc = ron::from_str("Fire(groups: [0])").unwrap();
+    assert!(matches!(
+        desc,
+        NodeDesc::Fire {
+            at_target: true,
+            ..
+        }
+    ));
+}
@@ -3,8 +3,11 @@ use deps::*;
 use bevy::prelude::*;
 
 use super::{ActiveBoidStrategy, BoidStrategy, BoidStrategyBundle, BoidStrategyOutput};
+use crate::math::*;
 use crate::mind::boid::steering::*;
 
+pub mod description;
+
 pub type RoutineSpawner =
     dyn FnOnce(&mut Commands, Entity, &BoidStrategy) -> Entity + Sync + 'static + Send;
 
@@ -20,11 +23,105 @@ pub enum Composition {
     PriorityOverride {
         routines: SVec<[Box<RoutineSpawner>; 4]>,
     },
+    AvoidCollisionHelper {
+        avoid_collision: SVec<[Box<RoutineSpawner>; 2]>,
+        routines: SVec<[(compose::SteeringRoutineWeight, Box<RoutineSpawner>); 2]>,
+    },
+    PrioritizedAllocation {
+        routines: SVec<[Box<RoutineSpawner>; 4]>,
+    },
+    ContextSteering {
+        interests: SVec<[(compose::SteeringRoutineWeight, Box<RoutineSpawner>); 4]>,
+        dangers: SVec<[Box<RoutineSpawner>; 2]>,
+        danger_threshold: TReal,
+    },
+}
+
+impl Composition {
+    /// Spawns the routines as children of `parent_entt` and composes them.
+    pub fn spawn(
+        self,
+        commands: &mut Commands,
+        parent_entt: Entity,
+        strategy: &BoidStrategy,
+    ) -> compose::SteeringRoutineComposer {
+        match self {
+            Composition::Single { routine_spawner } => {
+                let routine = routine_spawner(commands, parent_entt, strategy);
+
+                compose::SteeringRoutineComposer::Single { entt: routine }
+            }
+            Composition::WeightSummed { routines } => {
+                let routines: SVec<[(compose::SteeringRoutineWeight, Entity); 2]> = routines
+                    .into_iter()
+                    .map(|(weight, spawner)| (weight, spawner(commands, parent_entt, strategy)))
+                    .collect();
+
+                compose::SteeringRoutineComposer::WeightSummed { routines }
+            }
+            Composition::PriorityOverride { routines } => {
+                let routines: SVec<[Entity; 4]> = routines
+                    .into_iter()
+                    .map(|spawner| spawner(commands, parent_entt, strategy))
+                    .collect();
+
+                compose::SteeringRoutineComposer::PriorityOverride { routines }
+            }
+            Composition::AvoidCollisionHelper {
+                avoid_collision,
+                routines,
+            } => {
+                let avoid_collision: SVec<[Entity; 2]> = avoid_collision
+                    .into_iter()
+                    .map(|spawner| spawner(commands, parent_entt, strategy))
+                    .collect();
+                let routines: SVec<[(compose::SteeringRoutineWeight, Entity); 2]> = routines
+                    .into_iter()
+                    .map(|(weight, spawner)| (weight, spawner(commands, parent_entt, strategy)))
+                    .collect();
+
+                compose::SteeringRoutineComposer::AvoidCollisionHelper {
+                    avoid_collision,
+                    routines,
+                }
+            }
+            Composition::PrioritizedAllocation { routines } => {
+                let routines: SVec<[Entity; 4]> = routines
+                    .into_iter()
+                    .map(|spawner| spawner(commands, parent_entt, strategy))
+                    .collect();
+
+                compose::SteeringRoutineComposer::PrioritizedAllocation { routines }
+            }
+            Composition::ContextSteering {
+                interests,
+                dangers,
+                danger_threshold,
+            } => {
+                let interests: SVec<[(compose::SteeringRoutineWeight, Entity); 4]> = interests
+                    .into_iter()
+                    .map(|(weight, spawner)| (weight, spawner(commands, parent_entt, strategy)))
+                    .collect();
+                let dangers: SVec<[Entity; 2]> = dangers
+                    .into_iter()
+                    .map(|spawner| spawner(commands, parent_entt, strategy))
+                    .collect();
+
+                compose::SteeringRoutineComposer::ContextSteering {
+                    interests,
+                    dangers,
+                    danger_threshold,
+                }
+            }
+        }
+    }
 }
 
 // pub type Spawner = std::sync::Arc<std::sync::Mutex<dyn FnOnce(&mut Commands) -> Entity>>;
-#[derive(Component)]
+#[derive(Component, educe::Educe)]
+#[educe(Debug)]
 pub struct Custom {
+    #[educe(Debug(ignore))]
     composition: Option<Composition>,
 }
 
@@ -46,31 +143,12 @@ pub fn butler(
     >,
 ) {
     for (strategy_entt, mut param, strategy, mut out) in added_strategies.iter_mut() {
-        let composer = match param.composition.take().unwrap_or_log() {
-            Composition::Single { routine_spawner } => {
-                let routine = routine_spawner(&mut commands, strategy_entt, strategy);
-
-                compose::SteeringRoutineComposer::Single { entt: routine }
-            }
-            Composition::WeightSummed { routines } => {
-                let routines: SVec<[(compose::SteeringRoutineWeight, Entity); 2]> = routines
-                    .into_iter()
-                    .map(|(weight, spawner)| {
-                        (weight, spawner(&mut commands, strategy_entt, strategy))
-                    })
-                    .collect();
-
-                compose::SteeringRoutineComposer::WeightSummed { routines }
-            }
-            Composition::PriorityOverride { routines } => {
-                let routines: SVec<[Entity; 4]> = routines
-                    .into_iter()
-                    .map(|spawner| spawner(&mut commands, strategy_entt, strategy))
-                    .collect();
-
-                compose::SteeringRoutineComposer::PriorityOverride { routines }
-            }
-        };
+        let composer =
+            param
+                .composition
+                .take()
+                .unwrap_or_log()
+                .spawn(&mut commands, strategy_entt, strategy);
         let compose = commands.entity(strategy_entt).add_children(|p| {
             p.spawn()
                 .insert_bundle(compose::Bundle::new(
@@ -81,7 +159,8 @@ pub fn butler(
         });
         *out = BoidStrategyOutput {
             steering_routine: Some(compose),
-            fire_weapons: false,
+            fire_orders: default(),
+            fire_target: None,
         };
         commands.entity(strategy_entt).insert(ActiveBoidStrategy);
     }
@@ -0,0 +1,374 @@
+use deps::*;
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    ecs::system::EntityCommands,
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{Composition, RoutineSpawner};
+use crate::math::*;
+use crate::mind::boid::{steering::*, BoidMindDirective};
+
+/// What the descriptions need to know about the craft to size the routines.
+#[derive(Debug, Clone, Copy)]
+pub struct DescriptionContext {
+    pub boid_entt: Entity,
+    pub dimensions: TVec3,
+    pub accel_limit: TVec3,
+    pub linvel_limit: TVec3,
+}
+
+/// A serializable description of a steering routine.
+/// Vectors are in world basis.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "deps::serde")]
+pub enum RoutineDesc {
+    /// Sized to the craft.
+    AvoidCollision,
+    AvoidCrafts,
+    /// Keeps going where the nose is pointed.
+    GoForward,
+    Player,
+    Arrive {
+        at_pos: [TReal; 3],
+        #[serde(default)]
+        with_linvel: [TReal; 3],
+        #[serde(default = "default_arrival_tolerance")]
+        arrival_tolerance: TReal,
+    },
+    Seek {
+        pos: [TReal; 3],
+    },
+    Flee {
+        pos: [TReal; 3],
+    },
+    Face {
+        dir: [TReal; 3],
+    },
+    Jink {
+        intensity: TReal,
+        min_period_secs: f64,
+        max_period_secs: f64,
+    },
+    FollowSpline {
+        points: Vec<[TReal; 3]>,
+        #[serde(default)]
+        looped: bool,
+        speeds: Vec<TReal>,
+        #[serde(default)]
+        max_lateral_accel: Option<TReal>,
+    },
+}
+
+fn default_arrival_tolerance() -> TReal {
+    5.
+}
+
+impl RoutineDesc {
+    /// Inserts the routine's bundle into the entity.
+    pub fn insert(&self, entt: &mut EntityCommands, ctx: &DescriptionContext) {
+        let boid_entt = ctx.boid_entt;
+        match self {
+            RoutineDesc::AvoidCollision => {
+                let raycast_toi_modifier = ctx.dimensions.max_element();
+                entt.insert_bundle(avoid_collision::Bundle::new(
+                    avoid_collision::AvoidCollision::new(
+                        raycast_toi_modifier * 0.5,
+                        raycast_toi_modifier,
+                    ),
+                    boid_entt,
+                    default(),
+                ));
+            }
+            RoutineDesc::AvoidCrafts => {
+                entt.insert_bundle(avoid_crafts::Bundle::new(default(), boid_entt))
+                    .insert(context::ContextMap::default());
+            }
+            RoutineDesc::GoForward => {
+                entt.insert_bundle(closure::Bundle::new(
+                    closure::Closure {
+                        closure: Box::new(|xform, _, _| {
+                            (
+                                LinearRoutineOutput::Dir(xform.forward()),
+                                AngularRoutineOutput::LookDir(-TVec3::Z),
+                            )
+                        }),
+                    },
+                    boid_entt,
+                ));
+            }
+            RoutineDesc::Player => {
+                entt.insert_bundle(player::Bundle::new(player::Player, boid_entt));
+            }
+            RoutineDesc::Arrive {
+                at_pos,
+                with_linvel,
+                arrival_tolerance,
+            } => {
+                entt.insert_bundle(arrive::Bundle::new(
+                    arrive::Arrive {
+                        target: arrive::Target::Vector {
+                            at_pos: TVec3::from(*at_pos),
+                            with_linvel: TVec3::from(*with_linvel),
+                            pos_linvel: TVec3::ZERO,
+                        },
+                        arrival_tolerance: *arrival_tolerance,
+                        avail_accel: ctx.accel_limit,
+                        with_facing: None,
+                    },
+                    boid_entt,
+                ));
+            }
+            RoutineDesc::Seek { pos } => {
+                entt.insert_bundle(seek::Bundle::new(
+                    seek::Seek {
+                        target: seek::Target::Position {
+                            pos: TVec3::from(*pos),
+                        },
+                    },
+                    boid_entt,
+                ));
+            }
+            RoutineDesc::Flee { pos } => {
+                entt.insert_bundle(evade::Bundle::new(
+                    evade::Evade {
+                        target: evade::Target::Position {
+                            pos: TVec3::from(*pos),
+                        },
+                        speed: None,
+                        linvel_limit: ctx.linvel_limit,
+                    },
+                    boid_entt,
+                ));
+            }
+            RoutineDesc::Face { dir } => {
+                entt.insert_bundle(face::Bundle::new(
+                    face::Face {
+                        target: face::Target::Direction {
+                            dir: TVec3::from(*dir),
+                        },
+                    },
+                    boid_entt,
+                ));
+            }
+            RoutineDesc::Jink {
+                intensity,
+                min_period_secs,
+                max_period_secs,
+            } => {
+                entt.insert_bundle(jink::Bundle::new(
+                    jink::Jink {
+                        threat_rb: None,
+                        min_period_secs: *min_period_secs,
+                        max_period_secs: *max_period_secs,
+                        intensity: *intensity,
+                        avail_accel: ctx.accel_limit,
+                    },
+                    boid_entt,
+                    default(),
+                ));
+            }
+            RoutineDesc::FollowSpline {
+                points,
+                looped,
+                speeds,
+                max_lateral_accel,
+            } => {
+                entt.insert_bundle(follow_spline::Bundle::new(
+                    follow_spline::FollowSpline {
+                        path: crate::mind::navigation::SplinePath::new(
+                            points.iter().copied().map(TVec3::from).collect(),
+                            *looped,
+                        ),
+                        speeds: speeds.iter().copied().collect(),
+                        max_lateral_accel: *max_lateral_accel,
+                        prediction_secs: 0.5,
+                        lookahead_secs: 1.,
+                        min_lookahead: ctx.dimensions.max_element() * 2.,
+                        avail_accel: ctx.accel_limit,
+                        arrival_tolerance: 5.,
+                    },
+                    boid_entt,
+                    default(),
+                ));
+            }
+        }
+    }
+
+    /// A spawner that puts the routine under the strategy.
+    pub fn spawner(self, ctx: DescriptionContext) -> Box<RoutineSpawner> {
+        Box::new(move |commands, strategy_entt, _| {
+            commands.entity(strategy_entt).add_children(|p| {
+                let mut entt = p.spawn();
+                self.insert(&mut entt, &ctx);
+                entt.id()
+            })
+        })
+    }
+}
+
+/// Linear and angular weights.
+pub type WeightDesc = (TReal, TReal);
+
+/// A serializable description of a [`Composition`]. Author them in `.composition.ron`
+/// files and have crafts use them through [`BoidMindDirective::Custom`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(crate = "deps::serde")]
+pub enum CompositionDesc {
+    Single(RoutineDesc),
+    WeightSummed(Vec<(WeightDesc, RoutineDesc)>),
+    PriorityOverride(Vec<RoutineDesc>),
+    AvoidCollisionHelper {
+        avoid_collision: Vec<RoutineDesc>,
+        routines: Vec<(WeightDesc, RoutineDesc)>,
+    },
+    PrioritizedAllocation(Vec<RoutineDesc>),
+    ContextSteering {
+        interests: Vec<(WeightDesc, RoutineDesc)>,
+        dangers: Vec<RoutineDesc>,
+        danger_threshold: TReal,
+    },
+}
+
+impl CompositionDesc {
+    pub fn composition(&self, ctx: DescriptionContext) -> Composition {
+        match self {
+            CompositionDesc::Single(desc) => Composition::Single {
+                routine_spawner: desc.clone().spawner(ctx),
+            },
+            CompositionDesc::WeightSummed(routines) => Composition::WeightSummed {
+                routines: weighted(ctx, routines),
+            },
+            CompositionDesc::PriorityOverride(routines) => Composition::PriorityOverride {
+                routines: spawners(ctx, routines),
+            },
+            CompositionDesc::AvoidCollisionHelper {
+                avoid_collision,
+                routines,
+            } => Composition::AvoidCollisionHelper {
+                avoid_collision: spawners(ctx, avoid_collision),
+                routines: weighted(ctx, routines),
+            },
+            CompositionDesc::PrioritizedAllocation(routines) => {
+                Composition::PrioritizedAllocation {
+                    routines: spawners(ctx, routines),
+                }
+            }
+            CompositionDesc::ContextSteering {
+                interests,
+                dangers,
+                danger_threshold,
+            } => Composition::ContextSteering {
+                interests: weighted(ctx, interests),
+                dangers: spawners(ctx, dangers),
+                danger_threshold: *danger_threshold,
+            },
+        }
+    }
+}
+
+fn spawners<A>(ctx: DescriptionContext, routines: &[RoutineDesc]) -> smallvec::SmallVec<A>
+where
+    A: smallvec::Array<Item = Box<RoutineSpawner>>,
+{
+    routines
+        .iter()
+        .map(|desc| desc.clone().spawner(ctx))
+        .collect()
+}
+
+fn weighted<A>(
+    ctx: DescriptionContext,
+    routines: &[(WeightDesc, RoutineDesc)],
+) -> smallvec::SmallVec<A>
+where
+    A: smallvec::Array<Item = (compose::SteeringRoutineWeight, Box<RoutineSpawner>)>,
+{
+    routines
+        .iter()
+        .map(|(weight, desc)| {
+            (
+                compose::SteeringRoutineWeight::from(*weight),
+                desc.clone().spawner(ctx),
+            )
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, TypeUuid)]
+#[uuid = "6f1d3c0e-2b7a-4c55-9a8e-3f4d2e1b7c90"]
+pub struct CompositionAsset(pub CompositionDesc);
+
+#[derive(Default)]
+pub struct CompositionLoader;
+
+impl AssetLoader for CompositionLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let desc: CompositionDesc = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(CompositionAsset(desc)));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["composition.ron"]
+    }
+}
+
+/// Has crafts using a composition respawn their strategy when it's (re)loaded.
+pub fn composition_reloader(
+    mut events: EventReader<AssetEvent<CompositionAsset>>,
+    mut boids: Query<&mut BoidMindDirective>,
+) {
+    for event in events.iter() {
+        let handle = match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+            AssetEvent::Removed { .. } => continue,
+        };
+        for mut directive in boids.iter_mut() {
+            if matches!(&*directive, BoidMindDirective::Custom { composition } if composition == handle)
+            {
+                directive.set_changed();
+            }
+        }
+    }
+}
+
+#[test]
+fn composition_desc_test() {
+    let desc: CompositionDesc = ron::from_str(
+        "AvoidCollisionHelper(
+            avoid_collision: [AvoidCrafts, AvoidCollision],
+            routines: [
+                ((1., 0.), Arrive(at_pos: (0., 0., 100.))),
+                ((0., 1.), Face(dir: (0., 0., -1.))),
+            ],
+        )",
+    )
+    .unwrap();
+    match desc {
+        CompositionDesc::AvoidCollisionHelper {
+            avoid_collision,
+            routines,
+        } => {
+            assert_eq!(avoid_collision.len(), 2);
+            assert!(matches!(
+                routines[0].1,
+                RoutineDesc::Arrive {
+                    arrival_tolerance,
+                    ..
+                } if (arrival_tolerance - default_arrival_tolerance()).abs() < TReal::EPSILON
+            ));
+        }
+        _ => panic!("wrong variant"),
+    }
+}
@@ -0,0 +1,486 @@
+use deps::*;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use super::{ActiveBoidStrategy, BoidStrategy, BoidStrategyBundleExtra, BoidStrategyOutput};
+use crate::{
+    craft::*,
+    math::*,
+    mind::{boid::steering::*, sensors::*},
+};
+
+/// A spot on a body crafts can dock at. The approach corridor runs out from the port along
+/// its local `+Z` so crafts come in nose first, matching the port's orientation.
+#[derive(Debug, Clone, Component)]
+pub struct DockingPort {
+    /// The rigid body docked crafts get attached to. The port's usually its child.
+    pub body: Entity,
+    pub corridor_length: TReal,
+    /// Crafts get attached within this distance of the port...
+    pub capture_distance: TReal,
+    /// ...going slower than this relative to it...
+    pub capture_speed: TReal,
+    /// ...and turned less than this off its orientation, in radians.
+    pub capture_angle: TReal,
+    /// The craft that's docked or on the corridor.
+    pub occupant: Option<Entity>,
+}
+
+impl DockingPort {
+    pub fn new(body: Entity, corridor_length: TReal) -> Self {
+        Self {
+            body,
+            corridor_length,
+            capture_distance: 2.,
+            capture_speed: 2.,
+            capture_angle: 5. * (real::consts::PI / 180.),
+            occupant: None,
+        }
+    }
+
+    /// The mouth of the approach corridor in the port's local basis.
+    #[inline]
+    pub fn corridor_mouth(&self) -> TVec3 {
+        TVec3::Z * self.corridor_length
+    }
+
+    /// The position and orientation of the port at `port_entt` in its body's local basis.
+    /// None if either's gone.
+    pub fn pose_on_body(
+        &self,
+        port_entt: Entity,
+        xforms: &Query<&GlobalTransform>,
+    ) -> Option<(TVec3, TQuat)> {
+        let (_, body_rot, body_pos) = xforms.get(self.body).ok()?.to_scale_rotation_translation();
+        let (_, port_rot, port_pos) = xforms.get(port_entt).ok()?.to_scale_rotation_translation();
+        let inv_body_rot = body_rot.inverse();
+        Some((
+            inv_body_rot * (port_pos - body_pos),
+            inv_body_rot * port_rot,
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DockingManeuver {
+    Dock,
+    Undock,
+}
+
+/// Docks the craft at a [`DockingPort`] or undocks it from one.
+/// Either way, the craft's left keeping station at the corridor's mouth if it's not docked.
+#[derive(Debug, Clone, Component)]
+pub struct Dock {
+    /// Must have a [`DockingPort`] and a [`GlobalTransform`].
+    pub port: Entity,
+    pub maneuver: DockingManeuver,
+    /// How fast to be going along the corridor.
+    pub corridor_speed: TReal,
+    /// How much of the craft's acceleration to use on the corridor.
+    pub corridor_accel_fraction: TReal,
+}
+
+impl Dock {
+    pub fn new(port: Entity, maneuver: DockingManeuver) -> Self {
+        Self {
+            port,
+            maneuver,
+            corridor_speed: 10.,
+            corridor_accel_fraction: 0.25,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, educe::Educe)]
+#[educe(Default)]
+pub enum DockingPhase {
+    /// Heading for the corridor's mouth.
+    #[educe(Default)]
+    Approach,
+    /// Closing in on the port along the corridor.
+    Corridor,
+    /// Attached to the port's body.
+    Docked,
+    /// Backing out along the corridor.
+    Departing,
+    /// Holding at the corridor's mouth, matching the port's motion.
+    StationKeeping,
+}
+
+impl DockingPhase {
+    /// The phase to move on to. `captured` is whether the craft's close, slow and aligned
+    /// enough to the port to be attached.
+    pub fn next(
+        self,
+        maneuver: DockingManeuver,
+        at_mouth: bool,
+        port_free: bool,
+        captured: bool,
+    ) -> Self {
+        use DockingPhase::*;
+        match self {
+            Approach if at_mouth && port_free => Corridor,
+            // wait our turn
+            Approach if !port_free => StationKeeping,
+            Corridor if captured => Docked,
+            Departing if at_mouth => StationKeeping,
+            StationKeeping if maneuver == DockingManeuver::Dock && port_free => Approach,
+            phase => phase,
+        }
+    }
+}
+
+/// Whether the craft's attached to the port's body.
+#[inline]
+fn jointed_to(joints: &Query<&ImpulseJoint>, boid_entt: Entity, body: Entity) -> bool {
+    joints
+        .get(boid_entt)
+        .map(|joint| joint.parent == body)
+        .unwrap_or(false)
+}
+
+#[derive(Debug, Clone, Component, Default)]
+pub struct DockState {
+    pub phase: DockingPhase,
+    pub composer_routine: Option<Entity>,
+    pub avoid_collision_routine: Option<Entity>,
+    pub arrive_routine: Option<Entity>,
+    pub face_routine: Option<Entity>,
+}
+
+impl DockState {
+    /// The port's pose on its body is from [`DockingPort::pose_on_body`].
+    fn arrive_target(
+        &self,
+        param: &Dock,
+        port: &DockingPort,
+        (port_offset, port_rot): (TVec3, TQuat),
+    ) -> arrive::Target {
+        use DockingPhase::*;
+        let (offset, with_linvel) = match self.phase {
+            Approach => (port.corridor_mouth(), -TVec3::Z * param.corridor_speed),
+            Corridor | Docked => (TVec3::ZERO, TVec3::ZERO),
+            Departing => (port.corridor_mouth(), TVec3::Z * param.corridor_speed),
+            StationKeeping => (port.corridor_mouth(), TVec3::ZERO),
+        };
+        // the port's got no Velocity of its own so track it on its body
+        arrive::Target::Object {
+            entt: port.body,
+            offset: port_offset + (port_rot * offset),
+            with_linvel: port_rot * with_linvel,
+        }
+    }
+
+    fn avail_accel(&self, param: &Dock, engine_config: &engine::EngineConfig) -> TVec3 {
+        match self.phase {
+            DockingPhase::Corridor | DockingPhase::Departing => {
+                engine_config.actual_accel_limit() * param.corridor_accel_fraction
+            }
+            _ => engine_config.actual_accel_limit(),
+        }
+    }
+
+    fn composer(&self) -> compose::SteeringRoutineComposer {
+        use DockingPhase::*;
+        let routines = smallvec::smallvec![
+            ((1., 0.).into(), self.arrive_routine.unwrap_or_log()),
+            ((0., 1.).into(), self.face_routine.unwrap_or_log()),
+        ];
+        match self.phase {
+            Approach | StationKeeping => compose::SteeringRoutineComposer::AvoidCollisionHelper {
+                avoid_collision: smallvec::smallvec![self.avoid_collision_routine.unwrap_or_log()],
+                routines,
+            },
+            // we're meant to get close on the corridor
+            Corridor | Departing => compose::SteeringRoutineComposer::WeightSummed { routines },
+            // the joint's holding us
+            Docked => compose::SteeringRoutineComposer::Empty,
+        }
+    }
+}
+
+pub type Bundle = BoidStrategyBundleExtra<Dock, DockState>;
+
+pub fn butler(
+    mut commands: Commands,
+    mut added_strategies: Query<
+        (
+            Entity,
+            &Dock,
+            &BoidStrategy,
+            &mut DockState,
+            &mut BoidStrategyOutput,
+        ),
+        Added<Dock>,
+    >,
+    mut ports: Query<&mut DockingPort>,
+    joints: Query<&ImpulseJoint>,
+    xforms: Query<&GlobalTransform>,
+    crafts: Query<(
+        &SteeringRoutinesIndex,
+        &engine::EngineConfig,
+        &CraftDimensions,
+    )>,
+    shared_avoid_collision: SharedRoutines<avoid_collision::AvoidCollision>,
+) {
+    for (strategy_entt, param, strategy, mut state, mut out) in added_strategies.iter_mut() {
+        let (routine_idx, engine_config, dim) = crafts
+            .get(strategy.boid_entt())
+            .expect_or_log("craft not found for BoidStrategy");
+        let mut port = match ports.get_mut(param.port) {
+            Ok(port) => port,
+            Err(err) => {
+                tracing::error!(?err, port = ?param.port, "DockingPort not found for Dock strategy");
+                continue;
+            }
+        };
+        let port_pose = match port.pose_on_body(param.port, &xforms) {
+            Some(pose) => pose,
+            None => {
+                tracing::error!(port = ?param.port, body = ?port.body, "DockingPort body not found");
+                continue;
+            }
+        };
+
+        state.phase = match param.maneuver {
+            DockingManeuver::Dock => DockingPhase::Approach,
+            DockingManeuver::Undock => {
+                // leave any other joints be
+                if jointed_to(&joints, strategy.boid_entt(), port.body) {
+                    commands
+                        .entity(strategy.boid_entt())
+                        .remove::<ImpulseJoint>();
+                }
+                // keep others off the corridor till we're out
+                port.occupant = Some(strategy.boid_entt());
+                DockingPhase::Departing
+            }
+        };
+
+        let raycast_toi_modifier = dim.max_element();
+        let cast_shape_radius = raycast_toi_modifier * 0.5;
+        let avoid_collision = routine_idx
+            .reusable(&shared_avoid_collision)
+            .unwrap_or_else(|| {
+                commands.entity(strategy.boid_entt()).add_children(|par| {
+                    par.spawn()
+                        .insert_bundle(avoid_collision::Bundle::new(
+                            avoid_collision::AvoidCollision::new(
+                                cast_shape_radius,
+                                raycast_toi_modifier,
+                            ),
+                            strategy.boid_entt(),
+                            default(),
+                        ))
+                        .insert(SharedSteeringRoutine)
+                        .id()
+                })
+            });
+        let (arrive, face) = commands.entity(strategy_entt).add_children(|par| {
+            (
+                par.spawn()
+                    .insert_bundle(arrive::Bundle::new(
+                        arrive::Arrive {
+                            target: state.arrive_target(param, &port, port_pose),
+                            arrival_tolerance: port.capture_distance,
+                            avail_accel: state.avail_accel(param, engine_config),
+                            with_facing: None,
+                        },
+                        strategy.boid_entt(),
+                    ))
+                    // they sit out the docked phase
+                    .insert(PinnedSteeringRoutine)
+                    .id(),
+                par.spawn()
+                    .insert_bundle(face::Bundle::new(
+                        face::Face {
+                            target: face::Target::Align {
+                                entt: param.port,
+                                rot: TQuat::IDENTITY,
+                            },
+                        },
+                        strategy.boid_entt(),
+                    ))
+                    .insert(PinnedSteeringRoutine)
+                    .id(),
+            )
+        });
+        state.avoid_collision_routine = Some(avoid_collision);
+        state.arrive_routine = Some(arrive);
+        state.face_routine = Some(face);
+
+        let compose = commands.entity(strategy_entt).add_children(|par| {
+            par.spawn()
+                .insert_bundle(compose::Bundle::new(
+                    compose::Compose {
+                        composer: state.composer(),
+                    },
+                    strategy.boid_entt(),
+                ))
+                .id()
+        });
+        state.composer_routine = Some(compose);
+
+        *out = BoidStrategyOutput {
+            steering_routine: Some(compose),
+            fire_orders: default(),
+            fire_target: None,
+        };
+        commands.entity(strategy_entt).insert(ActiveBoidStrategy);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn update(
+    mut commands: Commands,
+    mut strategies: Query<(Entity, &Dock, &BoidStrategy, &mut DockState), With<ActiveBoidStrategy>>,
+    mut ports: Query<&mut DockingPort>,
+    xforms: Query<&GlobalTransform>,
+    mut objects: TrackedObjects,
+    crafts: Query<(
+        &Transform,
+        &Velocity,
+        &CraftDimensions,
+        &engine::EngineConfig,
+    )>,
+    mut composers: Query<&mut compose::Compose>,
+    mut arrive_routines: Query<&mut arrive::Arrive>,
+) {
+    for (strategy_entt, param, strategy, mut state) in strategies.iter_mut() {
+        let boid_entt = strategy.boid_entt();
+        let (xform, vel, dim, engine_config) = crafts
+            .get(boid_entt)
+            .expect_or_log("craft not found for BoidStrategy");
+        let mut port = match ports.get_mut(param.port) {
+            Ok(port) => port,
+            Err(err) => {
+                tracing::error!(?err, port = ?param.port, "DockingPort not found for Dock strategy");
+                continue;
+            }
+        };
+        let (port_offset, port_rot) = match port.pose_on_body(param.port, &xforms) {
+            Some(pose) => pose,
+            None => continue,
+        };
+        // moving with the body, its spin included
+        let port_point = match track_object(&mut objects, port.body, port_offset, strategy_entt) {
+            Some(point) => TrackedPoint {
+                rotation: point.rotation * port_rot,
+                ..point
+            },
+            None => continue,
+        };
+        // where we are relative to the port, in its basis
+        let local_pos = port_point.rotation.inverse() * (xform.translation - port_point.pos);
+        let at_mouth = local_pos.distance(port.corridor_mouth()) < dim.max_element();
+        let port_free = port.occupant.is_none() || port.occupant == Some(boid_entt);
+
+        let captured = local_pos.length() < port.capture_distance
+            && (vel.linvel - port_point.linvel).length() < port.capture_speed
+            && orientation_error(xform.rotation, port_point.rotation).length() < port.capture_angle;
+
+        use DockingPhase::*;
+        let next_phase = state
+            .phase
+            .next(param.maneuver, at_mouth, port_free, captured);
+        match (state.phase, next_phase) {
+            (Approach, Corridor) => port.occupant = Some(boid_entt),
+            (Corridor, Docked) => {
+                commands.entity(boid_entt).insert(ImpulseJoint::new(
+                    port.body,
+                    FixedJointBuilder::new()
+                        .local_anchor1(port_offset)
+                        .local_basis1(port_rot),
+                ));
+                tracing::info!(?boid_entt, port = ?param.port, "craft docked");
+            }
+            (Departing, StationKeeping) => {
+                if port.occupant == Some(boid_entt) {
+                    port.occupant = None;
+                }
+                tracing::info!(?boid_entt, port = ?param.port, "craft undocked");
+            }
+            _ => {}
+        }
+        if next_phase == state.phase {
+            continue;
+        }
+        state.phase = next_phase;
+
+        let mut arrive_param = arrive_routines
+            .get_mut(state.arrive_routine.unwrap_or_log())
+            .unwrap_or_log();
+        arrive_param.target = state.arrive_target(param, &port, (port_offset, port_rot));
+        arrive_param.avail_accel = state.avail_accel(param, engine_config);
+        composers
+            .get_mut(state.composer_routine.unwrap_or_log())
+            .unwrap_or_log()
+            .composer = state.composer();
+    }
+}
+
+/// Frees up the port and detaches the craft when a [`Dock`] strategy goes away, however it's
+/// left. Run it after the strategies are despawned in the frame.
+pub fn janitor(
+    mut commands: Commands,
+    added: Query<(Entity, &Dock, &BoidStrategy), Added<Dock>>,
+    removed: RemovedComponents<Dock>,
+    mut ports: Query<&mut DockingPort>,
+    joints: Query<&ImpulseJoint>,
+    // strategy -> (boid, port)
+    mut docking: Local<bevy::utils::HashMap<Entity, (Entity, Entity)>>,
+) {
+    for (strategy_entt, param, strategy) in added.iter() {
+        docking.insert(strategy_entt, (strategy.boid_entt(), param.port));
+    }
+    for strategy_entt in removed.iter() {
+        let (boid_entt, port_entt) = match docking.remove(&strategy_entt) {
+            Some(pair) => pair,
+            None => continue,
+        };
+        // it's been replaced by another for the same port, e.g. to undock, which takes over
+        if docking.values().any(|pair| *pair == (boid_entt, port_entt)) {
+            continue;
+        }
+        let mut port = match ports.get_mut(port_entt) {
+            Ok(port) => port,
+            // it's gone along with its body
+            Err(_) => continue,
+        };
+        if port.occupant == Some(boid_entt) {
+            port.occupant = None;
+        }
+        if jointed_to(&joints, boid_entt, port.body) {
+            commands.entity(boid_entt).remove::<ImpulseJoint>();
+        }
+    }
+}
+
+#[test]
+fn docking_phase_test() {
+    use DockingManeuver::*;
+    use DockingPhase::*;
+    // head for the mouth till we're there and the port's free
+    assert_eq!(Approach.next(Dock, false, true, false), Approach);
+    assert_eq!(Approach.next(Dock, true, true, false), Corridor);
+    assert_eq!(Approach.next(Dock, true, false, false), StationKeeping);
+    // and go in when it frees up
+    assert_eq!(
+        StationKeeping.next(Dock, true, false, false),
+        StationKeeping
+    );
+    assert_eq!(StationKeeping.next(Dock, true, true, false), Approach);
+    // only attach once captured
+    assert_eq!(Corridor.next(Dock, false, true, false), Corridor);
+    assert_eq!(Corridor.next(Dock, false, true, true), Docked);
+    // stays docked till undocked
+    assert_eq!(Docked.next(Dock, false, true, true), Docked);
+    assert_eq!(Departing.next(Undock, false, true, false), Departing);
+    assert_eq!(Departing.next(Undock, true, true, false), StationKeeping);
+    // undocked crafts hang around
+    assert_eq!(
+        StationKeeping.next(Undock, true, true, false),
+        StationKeeping
+    );
+}
@@ -22,6 +22,7 @@ pub struct Form {
 pub struct FormState {
     pub composer_routine: Option<Entity>,
     pub avoid_collision_routine: Option<Entity>,
+    pub avoid_crafts_routine: Option<Entity>,
     pub arrive_routine: Option<Entity>,
     pub face_routine: Option<Entity>,
 }
@@ -46,6 +47,8 @@ pub fn butler(
         &engine::EngineConfig,
         &CraftDimensions,
     )>,
+    shared_avoid_collision: SharedRoutines<avoid_collision::AvoidCollision>,
+    shared_avoid_crafts: SharedRoutines<avoid_crafts::AvoidCrafts>,
 ) {
     for (strategy_entt, param, strategy, mut state, mut out) in added_strategies.iter_mut() {
         let (routine_idx, engine_config, dim) = crafts.get(strategy.boid_entt()).unwrap_or_log();
@@ -63,35 +66,46 @@ pub fn butler(
         let raycast_toi_modifier = dim.max_element();
         let cast_shape_radius = raycast_toi_modifier * 0.5;
 
-        let (avoid_collision, arrive, face) = commands.entity(strategy_entt).add_children(|par| {
+        let avoid_collision = routine_idx
+            .reusable(&shared_avoid_collision)
+            .unwrap_or_else(|| {
+                commands.entity(strategy.boid_entt()).add_children(|par| {
+                    par.spawn()
+                        .insert_bundle(avoid_collision::Bundle::new(
+                            avoid_collision::AvoidCollision::new(
+                                cast_shape_radius,
+                                raycast_toi_modifier,
+                            ),
+                            strategy.boid_entt(),
+                            default(),
+                        ))
+                        .insert(SharedSteeringRoutine)
+                        .id()
+                })
+            });
+        let avoid_crafts = routine_idx
+            .reusable(&shared_avoid_crafts)
+            .unwrap_or_else(|| {
+                commands.entity(strategy.boid_entt()).add_children(|par| {
+                    par.spawn()
+                        .insert_bundle(avoid_crafts::Bundle::new(default(), strategy.boid_entt()))
+                        .insert(SharedSteeringRoutine)
+                        .id()
+                })
+            });
+        let (arrive, face) = commands.entity(strategy_entt).add_children(|par| {
             (
-                // routine_idx
-                //     .kind::<avoid_collision::AvoidCollision>()
-                //     .map(|v| v[0])
-                //     .unwrap_or_else(|| {
-                //     })
-                par.spawn()
-                    .insert_bundle(avoid_collision::Bundle::new(
-                        avoid_collision::AvoidCollision::new(
-                            cast_shape_radius,
-                            raycast_toi_modifier,
-                        ),
-                        strategy.boid_entt(),
-                        default(),
-                    ))
-                    .id(),
                 par.spawn()
                     .insert_bundle(arrive::Bundle::new(
                         arrive::Arrive {
                             target: arrive::Target::Vector {
                                 at_pos: form_out.pos,
-                                // with_linvel: form_out.linvel,
+                                with_linvel: form_out.linvel,
                                 pos_linvel: form_out.pos_linvel,
-                                with_speed: form_out.linvel.length(),
                             },
                             arrival_tolerance: 5.,
-                            deceleration_radius: None,
                             avail_accel: engine_config.actual_accel_limit(),
+                            with_facing: None,
                         },
                         strategy.boid_entt(),
                     ))
@@ -113,7 +127,7 @@ pub fn butler(
                 .insert_bundle(compose::Bundle::new(
                     compose::Compose {
                         composer: compose::SteeringRoutineComposer::AvoidCollisionHelper {
-                            avoid_collision,
+                            avoid_collision: smallvec::smallvec![avoid_crafts, avoid_collision],
                             routines: smallvec::smallvec![
                                 ((1., 0.).into(), arrive),
                                 ((0., 1.).into(), face),
@@ -127,12 +141,14 @@ pub fn butler(
 
         state.composer_routine = Some(compose);
         state.avoid_collision_routine = Some(avoid_collision);
+        state.avoid_crafts_routine = Some(avoid_crafts);
         state.arrive_routine = Some(arrive);
         state.face_routine = Some(face);
 
         *out = BoidStrategyOutput {
             steering_routine: Some(compose),
-            fire_weapons: false,
+            fire_orders: default(),
+            fire_target: None,
         };
 
         commands.entity(strategy_entt).insert(ActiveBoidStrategy);
@@ -163,9 +179,8 @@ pub fn update(
                 .unwrap_or_log();
             arrive_param.target = arrive::Target::Vector {
                 at_pos: form_out.pos,
-                // with_linvel: form_out.linvel,
+                with_linvel: form_out.linvel,
                 pos_linvel: form_out.pos_linvel,
-                with_speed: form_out.linvel.length(),
             };
             let mut face_param = face_routines
                 .get_mut(state.face_routine.unwrap_or_log())
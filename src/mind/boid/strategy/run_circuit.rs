@@ -15,6 +15,11 @@ pub struct RunCircuit {
     pub initial_point: Entity,
 }
 
+impl RunCircuit {
+    /// How fast to be going when passing through waypoints.
+    pub const WAYPOINT_SPEED: TReal = 100.;
+}
+
 #[derive(Debug, Clone, Component)]
 pub struct CircuitWaypoint {
     pub next_point: Entity,
@@ -47,57 +52,58 @@ pub fn butler(
         &engine::EngineConfig,
         &CraftDimensions,
     )>,
+    shared_avoid_collision: sensors::SharedRoutines<avoid_collision::AvoidCollision>,
 ) {
     for (strategy_entt, param, strategy, mut state, mut out) in added_strategies.iter_mut() {
         let (routine_idx, engine_config, dim) = crafts
             .get(strategy.boid_entt())
             .expect_or_log("craft not found for BoidStrategy");
-        let (_, waypoint1_xform) = waypoints.get(param.initial_point).unwrap_or_log();
-        // let (_, waypoint2_xform) = waypoints.get(waypoint1.next_point).unwrap_or_log();
+        let (waypoint1, waypoint1_xform) = waypoints.get(param.initial_point).unwrap_or_log();
+        let (_, waypoint2_xform) = waypoints.get(waypoint1.next_point).unwrap_or_log();
 
         let raycast_toi_modifier = dim.max_element();
         let cast_shape_radius = raycast_toi_modifier * 0.5;
-        let (avoid_collision, arrive) = commands.entity(strategy_entt).add_children(|p| {
-            (
-                // routine_idx
-                //     .kind::<avoid_collision::AvoidCollision>()
-                //     .map(|v| v[0])
-                //     .unwrap_or_else(|| {
-                //     }),
-                p.spawn()
-                    .insert_bundle(avoid_collision::Bundle::new(
-                        avoid_collision::AvoidCollision::new(
-                            cast_shape_radius,
-                            raycast_toi_modifier,
-                        ),
-                        strategy.boid_entt(),
-                        default(),
-                    ))
-                    .id(),
-                p.spawn()
-                    .insert_bundle(arrive::Bundle::new(
-                        arrive::Arrive {
-                            target: arrive::Target::Vector {
-                                at_pos: waypoint1_xform.translation(),
-                                pos_linvel: default(),
-                                // with_linvel: default(),
-                                /* with_linvel: (waypoint2_xform.translation - waypoint1_xform.translation)
-                                .normalize()
-                                * engine_config.linvel_limit, */
-                                with_speed: 100.,
-                            },
-                            arrival_tolerance: 5.,
-                            deceleration_radius: None,
-                            // linvel_limit: engine_config.linvel_limit,
-                            avail_accel: engine_config.avail_lin_accel().clamp(
-                                -engine_config.actual_accel_limit(),
-                                engine_config.actual_accel_limit(),
+        let avoid_collision = routine_idx
+            .reusable(&shared_avoid_collision)
+            .unwrap_or_else(|| {
+                commands.entity(strategy.boid_entt()).add_children(|par| {
+                    par.spawn()
+                        .insert_bundle(avoid_collision::Bundle::new(
+                            avoid_collision::AvoidCollision::new(
+                                cast_shape_radius,
+                                raycast_toi_modifier,
                             ),
+                            strategy.boid_entt(),
+                            default(),
+                        ))
+                        .insert(SharedSteeringRoutine)
+                        .id()
+                })
+            });
+        let arrive = commands.entity(strategy_entt).add_children(|p| {
+            p.spawn()
+                .insert_bundle(arrive::Bundle::new(
+                    arrive::Arrive {
+                        target: arrive::Target::Vector {
+                            at_pos: waypoint1_xform.translation(),
+                            pos_linvel: default(),
+                            // pass through heading for the next one
+                            with_linvel: (waypoint2_xform.translation()
+                                - waypoint1_xform.translation())
+                            .normalize_or_zero()
+                                * RunCircuit::WAYPOINT_SPEED,
                         },
-                        strategy.boid_entt(),
-                    ))
-                    .id(),
-            )
+                        arrival_tolerance: 5.,
+                        with_facing: None,
+                        // linvel_limit: engine_config.linvel_limit,
+                        avail_accel: engine_config.avail_lin_accel().clamp(
+                            -engine_config.actual_accel_limit(),
+                            engine_config.actual_accel_limit(),
+                        ),
+                    },
+                    strategy.boid_entt(),
+                ))
+                .id()
         });
         let compose = commands.entity(strategy_entt).add_children(|p| {
             p.spawn()
@@ -117,7 +123,8 @@ pub fn butler(
         state.composer_routine = Some(compose);
         *out = BoidStrategyOutput {
             steering_routine: Some(compose),
-            fire_weapons: false,
+            fire_orders: default(),
+            fire_target: None,
         };
 
         commands.entity(strategy_entt).insert(ActiveBoidStrategy);
@@ -166,7 +173,7 @@ pub fn update(
                         match arrive_param.target {
                             arrive::Target::Vector {
                                 at_pos: prev_pos,
-                                with_speed,
+                                with_linvel,
                                 ..
                             } => {
                                 if prev_pos.distance_squared(checkopoint_xform.translation())
@@ -181,24 +188,22 @@ pub fn update(
                                         ?cur_spd,
                                         "craft arrived at waypoint {prev_pos:?}",
                                     );
-                                    let (_, _, next_waypoint_xform) =
+                                    let (_, next_waypoint, next_waypoint_xform) =
                                         waypoints.get(waypoint.next_point).unwrap_or_log();
-
-                                    /* let (_, _, next_next_waypoint_xform) = waypoints
-                                    .get(next_waypoint.next_point)
-                                    unwrap_or_log(); */
+                                    let (_, _, next_next_waypoint_xform) =
+                                        waypoints.get(next_waypoint.next_point).unwrap_or_log();
                                     arrive_param.target = arrive::Target::Vector {
                                         at_pos: next_waypoint_xform.translation(),
                                         pos_linvel: TVec3::ZERO,
-                                        // with_linvel: TVec3::ZERO,
-                                        /* with_linvel: (next_next_waypoint_xform.translation
-                                        - next_waypoint_xform.translation)
-                                        .normalize()
-                                        * engine_config.linvel_limit, */
-                                        with_speed,
+                                        with_linvel: (next_next_waypoint_xform.translation()
+                                            - next_waypoint_xform.translation())
+                                        .normalize_or_zero()
+                                            * with_linvel.length(),
                                     }
                                 }
                             }
+                            // we only ever set it to a vector
+                            arrive::Target::Object { .. } => {}
                         }
                     }
                 }
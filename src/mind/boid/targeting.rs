@@ -0,0 +1,406 @@
+use deps::*;
+
+use bevy::prelude::*;
+use bevy_inspector_egui::Inspectable;
+use bevy_rapier3d::prelude::*;
+
+use crate::craft::{arms::*, attire::AttireProfile, Faction};
+use crate::math::*;
+use crate::mind::*;
+
+/// Weights for scoring [`sensors::Contacts`] as targets.
+/// Boid mind component
+#[derive(Debug, Clone, Component, Inspectable)]
+pub struct TargetingConfig {
+    /// Ignore contacts further than this.
+    pub max_range: TReal,
+    pub distance_weight: TReal,
+    /// Contacts closing in are preferred.
+    pub closing_speed_weight: TReal,
+    /// Closing speed at which the closing score's at half its weight.
+    pub closing_speed_norm: TReal,
+    /// Contacts with weapons pointed at us or that've been shooting at us.
+    pub threat_weight: TReal,
+    /// Damaged contacts are preferred.
+    pub damage_weight: TReal,
+    /// Contacts we've been ordered to attack.
+    pub order_weight: TReal,
+    /// How much better, as a fraction, a contact has to score to replace the current target.
+    pub hysteresis: TReal,
+}
+
+impl Default for TargetingConfig {
+    fn default() -> Self {
+        Self {
+            max_range: 2000.,
+            distance_weight: 1.,
+            closing_speed_weight: 0.5,
+            closing_speed_norm: 100.,
+            threat_weight: 1.5,
+            damage_weight: 0.5,
+            order_weight: 10.,
+            hysteresis: 0.25,
+        }
+    }
+}
+
+/// The inputs for scoring a single contact.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TargetFactors {
+    pub distance: TReal,
+    /// Negative if opening.
+    pub closing_speed: TReal,
+    /// Zero to one.
+    pub threat: TReal,
+    /// Fraction of its integrity the contact's lost.
+    pub damage: TReal,
+    pub ordered: bool,
+}
+
+impl TargetingConfig {
+    pub fn score(&self, factors: &TargetFactors) -> TReal {
+        let closing = factors.closing_speed / self.closing_speed_norm;
+        (self.distance_weight * (1. - (factors.distance / self.max_range).clamp(0., 1.)))
+            + (self.closing_speed_weight * (closing / (1. + closing.abs())))
+            + (self.threat_weight * factors.threat.clamp(0., 1.))
+            + (self.damage_weight * factors.damage.clamp(0., 1.))
+            + if factors.ordered {
+                self.order_weight
+            } else {
+                0.
+            }
+    }
+
+    /// Whether a contact scoring `challenger` should replace a target scoring `incumbent`.
+    #[inline]
+    pub fn should_switch(&self, incumbent: TReal, challenger: TReal) -> bool {
+        challenger > incumbent + (incumbent.abs() * self.hysteresis)
+    }
+}
+
+/// The contact the boid's chosen to fight.
+/// Boid mind component
+#[derive(Debug, Clone, Default, Component, Inspectable)]
+pub struct CurrentTarget {
+    #[inspectable(ignore)]
+    pub entt: Option<Entity>,
+    pub score: TReal,
+    /// When the current target was picked.
+    pub since_secs: f64,
+}
+
+/// How much a craft threatens the boid at `pos`: whether it's armed, within range and
+/// pointing at it.
+fn threat_of(
+    target_xform: &GlobalTransform,
+    target_wpns: &sensors::CraftWeaponsIndex,
+    pos: TVec3,
+) -> TReal {
+    let range = target_wpns
+        .entt_to_desc
+        .values()
+        .map(|desc| desc.range)
+        .fold(0., TReal::max);
+    let offset = pos - target_xform.translation();
+    let dst = offset.length();
+    if dst > range || dst < TReal::EPSILON {
+        return 0.;
+    }
+    target_xform.forward().dot(offset / dst).max(0.)
+}
+
+/// Same faction or same flock.
+#[inline]
+pub(super) fn is_friendly(
+    faction: Faction,
+    flock: Option<&Entity>,
+    other_faction: Faction,
+    other_flock: Option<&Entity>,
+) -> bool {
+    faction == other_faction || (flock.is_some() && flock == other_flock)
+}
+
+/// Fills `member_to_flock` afresh from the [`flock::FlockMembers`] of every flock.
+pub(super) fn map_members_to_flocks(
+    flocks: &Query<(Entity, &flock::FlockMembers)>,
+    member_to_flock: &mut bevy::utils::HashMap<Entity, Entity>,
+) {
+    member_to_flock.clear();
+    for (flock_entt, members) in flocks.iter() {
+        member_to_flock.extend(members.iter().map(|member| (*member, flock_entt)));
+    }
+}
+
+/// The fraction of integrity lost by all the attires of the craft.
+fn damage_of(colliders: &crate::Colliders, attires: &Query<&AttireProfile>) -> TReal {
+    let (remaining, factory) = colliders
+        .set
+        .iter()
+        .filter_map(|entt| attires.get(*entt).ok())
+        .flat_map(|profile| profile.members.iter())
+        .fold((0., 0.), |(remaining, factory), attire| {
+            (
+                remaining + attire.remaining_integrity,
+                factory + attire.factory_integrity,
+            )
+        });
+    if factory > 0. {
+        1. - (remaining / factory)
+    } else {
+        0.
+    }
+}
+
+/// Scores the craft [`sensors::Contacts`] of each boid and picks its [`CurrentTarget`].
+/// Crafts of the same [`Faction`] or flock are never picked.
+#[allow(clippy::too_many_arguments)]
+pub fn target_selection(
+    mut boids: Query<(
+        Entity,
+        &GlobalTransform,
+        &Velocity,
+        &sensors::Contacts,
+        &TargetingConfig,
+        &mut CurrentTarget,
+        Option<&blackboard::Blackboard>,
+        Option<&Faction>,
+    )>,
+    crafts: Query<(
+        &GlobalTransform,
+        Option<&sensors::CraftWeaponsIndex>,
+        Option<&crate::Colliders>,
+        Option<&Faction>,
+    )>,
+    attires: Query<&AttireProfile>,
+    flocks: Query<(Entity, &flock::FlockMembers)>,
+    mut member_to_flock: Local<bevy::utils::HashMap<Entity, Entity>>,
+    time: Res<Time>,
+) {
+    map_members_to_flocks(&flocks, &mut member_to_flock);
+    let now_secs = time.seconds_since_startup();
+    for (boid_entt, xform, vel, contacts, config, mut target, board, faction) in boids.iter_mut() {
+        let pos = xform.translation();
+        let flock = member_to_flock.get(&boid_entt);
+        let mut best: Option<(Entity, TReal)> = None;
+        let mut incumbent: Option<TReal> = None;
+        for (entt, contact) in contacts.of_class(sensors::ContactClass::Craft) {
+            if entt == boid_entt {
+                continue;
+            }
+            let (contact_xform, contact_wpns, contact_colliders, contact_faction) =
+                match crafts.get(entt) {
+                    Ok(craft) => craft,
+                    // not a craft anymore
+                    Err(_) => continue,
+                };
+            if is_friendly(
+                faction.copied().unwrap_or_default(),
+                flock,
+                contact_faction.copied().unwrap_or_default(),
+                member_to_flock.get(&entt),
+            ) {
+                continue;
+            }
+            let offset = contact.estimated_pos(&time) - pos;
+            let distance = offset.length();
+            if distance > config.max_range {
+                continue;
+            }
+            let closing_speed = if distance > TReal::EPSILON {
+                -offset.dot(contact.last_vel - vel.linvel) / distance
+            } else {
+                0.
+            };
+            let threat = contact_wpns
+                .map(|wpns| threat_of(contact_xform, wpns, pos))
+                .unwrap_or_default();
+            let damage = contact_colliders
+                .map(|colliders| damage_of(colliders, &attires))
+                .unwrap_or_default();
+            // having been shot by them is as threatening as it gets
+            let shot_at = board
+                .map(|board| {
+                    board
+                        .read::<blackboard::UnderFire>(now_secs)
+                        .any(|fact| fact.attacker == Some(entt))
+                })
+                .unwrap_or(false);
+            let ordered = board
+                .map(|board| {
+                    board
+                        .read::<blackboard::AttackOrder>(now_secs)
+                        .any(|fact| fact.quarry == entt)
+                })
+                .unwrap_or(false);
+            let score = config.score(&TargetFactors {
+                distance,
+                closing_speed,
+                threat: if shot_at { 1. } else { threat },
+                damage,
+                ordered,
+            });
+            if target.entt == Some(entt) {
+                incumbent = Some(score);
+            }
+            if best.map(|(_, best)| score > best).unwrap_or(true) {
+                best = Some((entt, score));
+            }
+        }
+        match (incumbent, best) {
+            (Some(incumbent), Some((entt, score))) => {
+                if target.entt != Some(entt) && config.should_switch(incumbent, score) {
+                    *target = CurrentTarget {
+                        entt: Some(entt),
+                        score,
+                        since_secs: now_secs,
+                    };
+                } else {
+                    target.score = incumbent;
+                }
+            }
+            (None, Some((entt, score))) => {
+                *target = CurrentTarget {
+                    entt: Some(entt),
+                    score,
+                    since_secs: now_secs,
+                };
+            }
+            (_, None) => {
+                if target.entt.is_some() {
+                    *target = default();
+                }
+            }
+        }
+    }
+}
+
+/// Has [`Turret`]s track and engage their craft's [`CurrentTarget`].
+pub fn turret_mind(
+    mut turrets: Query<(
+        Entity,
+        &Turret,
+        &CraftWeapon,
+        &WeaponActivationState,
+        &GlobalTransform,
+        &mut Transform,
+    )>,
+    crafts: Query<(
+        &GlobalTransform,
+        &Velocity,
+        &CurrentTarget,
+        &sensors::Contacts,
+        &sensors::CraftWeaponsIndex,
+        &boid::BoidMindConfig,
+        &crate::Colliders,
+    )>,
+    mut los: sensors::los::LineOfSight,
+    mut activate_wpn_events: EventWriter<ActivateWeaponEvent>,
+    time: Res<Time>,
+) {
+    for (wpn_entt, turret, wpn, activation_state, xform, mut local_xform) in turrets.iter_mut() {
+        let craft_entt = wpn.boid_entt();
+        let (craft_xform, craft_vel, target, contacts, wpn_index, config, craft_colliders) =
+            match crafts.get(craft_entt) {
+                Ok(craft) => craft,
+                Err(_) => continue,
+            };
+        // might not be indexed yet
+        let desc = match wpn_index.entt_to_desc.get(&wpn_entt) {
+            Some(desc) => desc,
+            None => continue,
+        };
+        let (target_entt, contact) = match target
+            .entt
+            .and_then(|entt| contacts.get(entt).map(|contact| (entt, contact)))
+        {
+            Some(target) => target,
+            None => continue,
+        };
+        let target_pos = contact.estimated_pos(&time);
+        let wpn_pos = xform.translation();
+        let lead_pos = match desc.ballistics.solve_lead(
+            wpn_pos,
+            craft_vel.linvel,
+            target_pos,
+            contact.last_vel,
+        ) {
+            Some(solution) => solution.aim_pos,
+            None => continue,
+        };
+        boid::defence::swivel(
+            xform,
+            &mut local_xform,
+            lead_pos - wpn_pos,
+            craft_xform.up(),
+            turret.traverse_rate_radians * time.delta_seconds(),
+        );
+
+        if activation_state.can_activate(&time)
+            && boid::strategy::firing_solution(
+                wpn_pos,
+                xform.forward(),
+                craft_vel.linvel,
+                desc,
+                target_pos,
+                contact.last_vel,
+                config.aim_tolerance_radians,
+            )
+            .is_some()
+            && los.between(
+                craft_entt,
+                craft_xform.translation(),
+                target_entt,
+                target_pos,
+            )
+            && los.line_of_fire(wpn_entt, wpn_pos, Some(target_entt), lead_pos, &|entt| {
+                craft_colliders.set.contains(&entt)
+            })
+        {
+            activate_wpn_events.send(ActivateWeaponEvent {
+                weapon_id: wpn_entt,
+            });
+        }
+    }
+}
+
+#[test]
+fn is_friendly_test() {
+    let (flock_a, flock_b) = (Entity::from_raw(0), Entity::from_raw(1));
+    assert!(is_friendly(Faction(0), None, Faction(0), None));
+    assert!(!is_friendly(Faction(0), None, Faction(1), None));
+    // flock-mates are friendly regardless
+    assert!(is_friendly(
+        Faction(0),
+        Some(&flock_a),
+        Faction(1),
+        Some(&flock_a)
+    ));
+    assert!(!is_friendly(
+        Faction(0),
+        Some(&flock_a),
+        Faction(1),
+        Some(&flock_b)
+    ));
+}
+
+#[test]
+fn target_hysteresis_test() {
+    let config = TargetingConfig::default();
+    let near = config.score(&TargetFactors {
+        distance: 100.,
+        ..default()
+    });
+    let nearer = config.score(&TargetFactors {
+        distance: 90.,
+        ..default()
+    });
+    assert!(nearer > near);
+    // marginally better isn't enough to switch
+    assert!(!config.should_switch(near, nearer));
+    let ordered = config.score(&TargetFactors {
+        distance: 1500.,
+        ordered: true,
+        ..default()
+    });
+    assert!(config.should_switch(near, ordered));
+}
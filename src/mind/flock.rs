@@ -18,6 +18,7 @@ pub struct FlockMindBundle {
     pub active_strategy: CurrentFlockStrategy,
     pub active_formation: CurrentFlockFormation,
     pub directive: FlockMindDirective,
+    pub blackboard: blackboard::Blackboard,
 }
 
 impl FlockMindBundle {
@@ -30,6 +31,7 @@ impl FlockMindBundle {
             },
             directive: default(),
             change_events: default(),
+            blackboard: default(),
         }
     }
 }
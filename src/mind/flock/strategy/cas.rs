@@ -19,7 +19,6 @@ pub struct CASState {
     pub avg_vel: TVec3,
     pub center_sum: TVec3,
     pub center: TVec3,
-    pub craft_positions: Vec<TVec3>,
     pub member_count: usize,
 }
 
@@ -86,12 +85,10 @@ pub fn update(
         let members = flocks
             .get(strategy.flock_entt)
             .expect_or_log("unable to find FlockMind for new strategy");
-        state.craft_positions.clear();
         state.vel_sum = TVec3::ZERO;
         state.center_sum = TVec3::ZERO;
         for craft in members.iter() {
             if let Ok((xform, vel)) = crafts.get(*craft) {
-                state.craft_positions.push(xform.translation());
                 state.vel_sum += vel.linvel;
                 state.center_sum += xform.translation();
             } else {
@@ -0,0 +1,759 @@
+use deps::*;
+
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashMap};
+use bevy_rapier3d::prelude::*;
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use crate::{craft::attire::ColliderGroups, math::*};
+
+/// A sparse voxel map of the space blocked by [`ColliderGroups::SOLID`] colliders.
+/// Voxels are only probed when a search visits them and the results are cached per
+/// craft radius for [`Self::voxel_ttl_secs`] since obstacles move.
+/// Query it through [`Navigation`].
+#[derive(Debug)]
+pub struct NavigationGrid {
+    pub voxel_size: TReal,
+    pub voxel_ttl_secs: f64,
+    /// Searches that visit more voxels than this give up.
+    pub max_expansions: usize,
+    /// How many voxels all the [`Navigation::request_path`] searches get to visit in a
+    /// frame, between them.
+    pub expansions_per_frame: usize,
+    expansions_left: usize,
+    /// `(voxel, radius_key) -> (blocked, probed_secs)`
+    voxels: HashMap<(IVec3, u32), (bool, f64)>,
+    /// Searches spread across frames, by requester.
+    searches: HashMap<Entity, PendingSearch>,
+}
+
+impl Default for NavigationGrid {
+    fn default() -> Self {
+        Self {
+            voxel_size: 25.,
+            voxel_ttl_secs: 2.,
+            max_expansions: 10_000,
+            expansions_per_frame: 500,
+            expansions_left: 500,
+            voxels: default(),
+            searches: default(),
+        }
+    }
+}
+
+impl NavigationGrid {
+    #[inline]
+    fn voxel_of(&self, pos: TVec3) -> IVec3 {
+        (pos / self.voxel_size).floor().as_ivec3()
+    }
+
+    #[inline]
+    fn center_of(&self, voxel: IVec3) -> TVec3 {
+        (voxel.as_vec3() + TVec3::splat(0.5)) * self.voxel_size
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.voxels.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.voxels.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.voxels.clear();
+        self.searches.clear();
+    }
+}
+
+/// A planned route, start and end points included.
+#[derive(Debug, Clone, Default)]
+pub struct NavPath {
+    pub points: Vec<TVec3>,
+}
+
+impl NavPath {
+    pub fn length(&self) -> TReal {
+        self.points
+            .windows(2)
+            .map(|pair| pair[0].distance(pair[1]))
+            .sum()
+    }
+}
+
+/// A Catmull-Rom spline through the given points for smooth racing lines and patrol routes.
+/// Positions on it are addressed either by the spline parameter, which goes from zero to
+/// [`Self::segment_count`], or by the distance along it.
+#[derive(Debug, Clone, Default)]
+pub struct SplinePath {
+    points: Vec<TVec3>,
+    looped: bool,
+    /// `(param, distance along, position)` at regular param intervals.
+    samples: Vec<(TReal, TReal, TVec3)>,
+}
+
+impl SplinePath {
+    const SAMPLES_PER_SEGMENT: usize = 16;
+
+    /// `looped` connects the last point back to the first.
+    pub fn new(points: Vec<TVec3>, looped: bool) -> Self {
+        let mut path = Self {
+            points,
+            looped,
+            samples: default(),
+        };
+        let mut dist = 0.;
+        let mut prev = path.position(0.);
+        path.samples.push((0., 0., prev));
+        for ii in 1..=(path.segment_count() * Self::SAMPLES_PER_SEGMENT) {
+            let t = ii as TReal / Self::SAMPLES_PER_SEGMENT as TReal;
+            let pos = path.position(t);
+            dist += pos.distance(prev);
+            prev = pos;
+            path.samples.push((t, dist, pos));
+        }
+        path
+    }
+
+    #[inline]
+    pub fn points(&self) -> &[TVec3] {
+        &self.points
+    }
+
+    #[inline]
+    pub fn looped(&self) -> bool {
+        self.looped
+    }
+
+    #[inline]
+    pub fn segment_count(&self) -> usize {
+        match self.points.len() {
+            0 | 1 => 0,
+            len if self.looped => len,
+            len => len - 1,
+        }
+    }
+
+    #[inline]
+    pub fn length(&self) -> TReal {
+        self.samples
+            .last()
+            .map(|(_, dist, _)| *dist)
+            .unwrap_or_default()
+    }
+
+    /// Wraps around for looped paths and clamps otherwise.
+    #[inline]
+    pub fn wrap_distance(&self, dist: TReal) -> TReal {
+        let length = self.length();
+        if self.looped && length > TReal::EPSILON {
+            dist.rem_euclid(length)
+        } else {
+            dist.clamp(0., length)
+        }
+    }
+
+    #[inline]
+    fn control(&self, ii: isize) -> TVec3 {
+        let len = self.points.len() as isize;
+        let ii = if self.looped {
+            ii.rem_euclid(len)
+        } else {
+            ii.clamp(0, len - 1)
+        };
+        self.points[ii as usize]
+    }
+
+    /// The segment `t` falls in and how far along it it is.
+    #[inline]
+    fn locate(&self, t: TReal) -> (isize, TReal) {
+        let count = self.segment_count() as TReal;
+        let t = if self.looped {
+            t.rem_euclid(count)
+        } else {
+            t.clamp(0., count)
+        };
+        let seg = t.floor().min(count - 1.).max(0.);
+        (seg as isize, t - seg)
+    }
+
+    /// The polynomial coefficients of the segment `t` falls in along with how far along it it is.
+    #[inline]
+    fn coefficients(&self, t: TReal) -> ([TVec3; 4], TReal) {
+        let (seg, u) = self.locate(t);
+        let (p0, p1, p2, p3) = (
+            self.control(seg - 1),
+            self.control(seg),
+            self.control(seg + 1),
+            self.control(seg + 2),
+        );
+        (
+            [
+                p1,
+                (p2 - p0) * 0.5,
+                ((p0 * 2.) - (p1 * 5.) + (p2 * 4.) - p3) * 0.5,
+                ((p1 * 3.) - p0 - (p2 * 3.) + p3) * 0.5,
+            ],
+            u,
+        )
+    }
+
+    pub fn position(&self, t: TReal) -> TVec3 {
+        match self.points.len() {
+            0 => TVec3::ZERO,
+            1 => self.points[0],
+            _ => {
+                let ([c0, c1, c2, c3], u) = self.coefficients(t);
+                c0 + (c1 * u) + (c2 * u * u) + (c3 * u * u * u)
+            }
+        }
+    }
+
+    /// Derivative of the position with respect to the param.
+    pub fn velocity(&self, t: TReal) -> TVec3 {
+        if self.points.len() < 2 {
+            return TVec3::ZERO;
+        }
+        let ([_, c1, c2, c3], u) = self.coefficients(t);
+        c1 + (c2 * 2. * u) + (c3 * 3. * u * u)
+    }
+
+    /// Unit tangent.
+    #[inline]
+    pub fn direction(&self, t: TReal) -> TVec3 {
+        self.velocity(t).normalize_or_zero()
+    }
+
+    /// One over the radius of the turn at `t`.
+    pub fn curvature(&self, t: TReal) -> TReal {
+        if self.points.len() < 2 {
+            return 0.;
+        }
+        let ([_, c1, c2, c3], u) = self.coefficients(t);
+        let vel = c1 + (c2 * 2. * u) + (c3 * 3. * u * u);
+        let accel = (c2 * 2.) + (c3 * 6. * u);
+        let speed = vel.length();
+        if speed < TReal::EPSILON {
+            return 0.;
+        }
+        vel.cross(accel).length() / (speed * speed * speed)
+    }
+
+    /// Linearly interpolates `values` given per point of the path. A single value's used
+    /// for the whole of it.
+    pub fn lerp_per_point(&self, values: &[TReal], t: TReal) -> TReal {
+        match values.len() {
+            0 => 0.,
+            1 => values[0],
+            len => {
+                let (seg, u) = self.locate(t);
+                let at = |ii: isize| {
+                    let ii = if self.looped {
+                        ii.rem_euclid(len as isize)
+                    } else {
+                        ii.clamp(0, len as isize - 1)
+                    };
+                    values[ii as usize]
+                };
+                at(seg) + ((at(seg + 1) - at(seg)) * u)
+            }
+        }
+    }
+
+    /// The param at `dist` along the path.
+    pub fn param_at(&self, dist: TReal) -> TReal {
+        let dist = self.wrap_distance(dist);
+        let idx = self
+            .samples
+            .partition_point(|(_, sample_dist, _)| *sample_dist < dist);
+        match (
+            idx.checked_sub(1).and_then(|ii| self.samples.get(ii)),
+            self.samples.get(idx),
+        ) {
+            (Some((t0, d0, _)), Some((t1, d1, _))) => {
+                let span = d1 - d0;
+                if span > TReal::EPSILON {
+                    t0 + ((t1 - t0) * ((dist - d0) / span))
+                } else {
+                    *t0
+                }
+            }
+            (None, Some((t, _, _))) | (Some((t, _, _)), None) => *t,
+            (None, None) => 0.,
+        }
+    }
+
+    /// The distance along the path of the closest point on it to `pos`.
+    /// Only looks within `window` of the `hint` distance if given so that it doesn't jump
+    /// across where the path comes close to itself.
+    pub fn nearest_distance(&self, pos: TVec3, hint: Option<(TReal, TReal)>) -> TReal {
+        let length = self.length();
+        let mut best = (TReal::INFINITY, 0.);
+        for pair in self.samples.windows(2) {
+            let ((_, d0, a), (_, d1, b)) = (pair[0], pair[1]);
+            if let Some((hint, window)) = hint {
+                let off = if self.looped && length > TReal::EPSILON {
+                    ((d0 - hint) + (length * 0.5)).rem_euclid(length) - (length * 0.5)
+                } else {
+                    d0 - hint
+                };
+                if off.abs() > window + (d1 - d0) {
+                    continue;
+                }
+            }
+            let ab = b - a;
+            let len_squared = ab.length_squared();
+            let frac = if len_squared > TReal::EPSILON {
+                ((pos - a).dot(ab) / len_squared).clamp(0., 1.)
+            } else {
+                0.
+            };
+            let dist_squared = (a + (ab * frac)).distance_squared(pos);
+            if dist_squared < best.0 {
+                best = (dist_squared, d0 + ((d1 - d0) * frac));
+            }
+        }
+        if best.0.is_infinite() && hint.is_some() {
+            return self.nearest_distance(pos, None);
+        }
+        best.1
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct OpenVoxel {
+    estimate: TReal,
+    /// Cost so far when it was pushed. Entries whose voxel's since been reached cheaper are stale.
+    cost: TReal,
+    voxel: IVec3,
+}
+
+impl Eq for OpenVoxel {}
+
+impl Ord for OpenVoxel {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // min heap
+        other
+            .estimate
+            .partial_cmp(&self.estimate)
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for OpenVoxel {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchStatus {
+    Pending,
+    /// The voxels from the start to the goal.
+    Found(Vec<IVec3>),
+    Failed,
+}
+
+/// A* through a voxel lattice that can be advanced a few voxels at a time.
+#[derive(Debug, Clone)]
+pub struct PathSearch {
+    start: IVec3,
+    goal: IVec3,
+    open: BinaryHeap<OpenVoxel>,
+    /// voxel -> (cost so far, came from)
+    visited: HashMap<IVec3, (TReal, IVec3)>,
+    expansions: usize,
+}
+
+impl PathSearch {
+    pub fn new(start: IVec3, goal: IVec3) -> Self {
+        let mut search = Self {
+            start,
+            goal,
+            open: default(),
+            visited: default(),
+            expansions: 0,
+        };
+        search.visited.insert(start, (0., start));
+        search.open.push(OpenVoxel {
+            estimate: search.heuristic(start),
+            cost: 0.,
+            voxel: start,
+        });
+        search
+    }
+
+    #[inline]
+    fn heuristic(&self, voxel: IVec3) -> TReal {
+        (self.goal - voxel).as_vec3().length()
+    }
+
+    /// How many voxels have been visited so far.
+    #[inline]
+    pub fn expansions(&self) -> usize {
+        self.expansions
+    }
+
+    /// Visits at most `budget` more voxels, giving up once it's visited `max_expansions`
+    /// in total.
+    pub fn step(
+        &mut self,
+        budget: usize,
+        max_expansions: usize,
+        mut blocked: impl FnMut(IVec3) -> bool,
+    ) -> SearchStatus {
+        for _ in 0..budget {
+            let (voxel, cost) = match self.open.pop() {
+                Some(OpenVoxel { voxel, cost, .. }) => (voxel, cost),
+                None => return SearchStatus::Failed,
+            };
+            // superseded by a cheaper push of the same voxel
+            if self.visited[&voxel].0 < cost {
+                continue;
+            }
+            if voxel == self.goal {
+                let mut voxels = vec![self.goal];
+                let mut cur = self.goal;
+                while cur != self.start {
+                    cur = self.visited[&cur].1;
+                    voxels.push(cur);
+                }
+                voxels.reverse();
+                return SearchStatus::Found(voxels);
+            }
+            self.expansions += 1;
+            if self.expansions > max_expansions {
+                return SearchStatus::Failed;
+            }
+            for x in -1..=1 {
+                for y in -1..=1 {
+                    for z in -1..=1 {
+                        let step = IVec3::new(x, y, z);
+                        if step == IVec3::ZERO {
+                            continue;
+                        }
+                        let next = voxel + step;
+                        let next_cost = cost + step.as_vec3().length();
+                        if self
+                            .visited
+                            .get(&next)
+                            .map(|(prev_cost, _)| *prev_cost <= next_cost)
+                            .unwrap_or(false)
+                        {
+                            continue;
+                        }
+                        if blocked(next) {
+                            continue;
+                        }
+                        self.visited.insert(next, (next_cost, voxel));
+                        self.open.push(OpenVoxel {
+                            estimate: next_cost + self.heuristic(next),
+                            cost: next_cost,
+                            voxel: next,
+                        });
+                    }
+                }
+            }
+        }
+        SearchStatus::Pending
+    }
+}
+
+/// Skips any points of the `lattice` that can be gone straight past so that the path's not
+/// stuck to it.
+pub fn string_pull(lattice: &[TVec3], mut clear: impl FnMut(TVec3, TVec3) -> bool) -> Vec<TVec3> {
+    let mut points = Vec::with_capacity(lattice.len());
+    if let Some(first) = lattice.first() {
+        points.push(*first);
+    }
+    let mut anchor = 0;
+    while anchor + 1 < lattice.len() {
+        let mut next = anchor + 1;
+        for ii in ((anchor + 2)..lattice.len()).rev() {
+            if clear(lattice[anchor], lattice[ii]) {
+                next = ii;
+                break;
+            }
+        }
+        points.push(lattice[next]);
+        anchor = next;
+    }
+    points
+}
+
+#[derive(Debug)]
+struct PendingSearch {
+    search: PathSearch,
+    from: TVec3,
+    to: TVec3,
+    radius: TReal,
+    touched_secs: f64,
+}
+
+#[derive(Debug, Clone)]
+pub enum PathRequest {
+    /// Still searching, ask again next frame.
+    Pending,
+    Ready(NavPath),
+    /// There's no route or the search gave up.
+    Failed,
+}
+
+/// Path planning through the [`NavigationGrid`].
+#[derive(SystemParam)]
+pub struct Navigation<'w, 's> {
+    rapier: Res<'w, RapierContext>,
+    grid: ResMut<'w, NavigationGrid>,
+    time: Res<'w, Time>,
+    #[system_param(ignore)]
+    _phantom: std::marker::PhantomData<&'s ()>,
+}
+
+impl Navigation<'_, '_> {
+    #[inline]
+    fn solid_filter() -> QueryFilter<'static> {
+        QueryFilter {
+            groups: Some(InteractionGroups::new(
+                ColliderGroups::SOLID.bits(),
+                ColliderGroups::SOLID.bits(),
+            )),
+            ..default()
+        }
+    }
+
+    /// Whether a ball of `radius` can travel from `from` to `to` unobstructed.
+    pub fn clear_between(&self, from: TVec3, to: TVec3, radius: TReal) -> bool {
+        let offset = to - from;
+        let dst = offset.length();
+        if dst < TReal::EPSILON {
+            return true;
+        }
+        self.rapier
+            .cast_shape(
+                from,
+                TQuat::IDENTITY,
+                offset / dst,
+                &Collider::ball(radius),
+                dst,
+                Self::solid_filter(),
+            )
+            .is_none()
+    }
+
+    fn blocked(&mut self, voxel: IVec3, radius: TReal) -> bool {
+        let now_secs = self.time.seconds_since_startup();
+        let key = (voxel, radius.ceil() as u32);
+        if let Some((blocked, probed_secs)) = self.grid.voxels.get(&key) {
+            if now_secs - probed_secs < self.grid.voxel_ttl_secs {
+                return *blocked;
+            }
+        }
+        // pad it out so that moving between neighbouring centers is safe
+        let probe_radius = radius + (self.grid.voxel_size * 0.5);
+        let blocked = self
+            .rapier
+            .intersection_with_shape(
+                self.grid.center_of(voxel),
+                TQuat::IDENTITY,
+                &Collider::ball(probe_radius),
+                Self::solid_filter(),
+            )
+            .is_some();
+        self.grid.voxels.insert(key, (blocked, now_secs));
+        blocked
+    }
+
+    /// Starts a search unless it's a straight shot or the goal's blocked.
+    fn begin(
+        &mut self,
+        from: TVec3,
+        to: TVec3,
+        radius: TReal,
+    ) -> Result<PendingSearch, PathRequest> {
+        if self.clear_between(from, to, radius) {
+            return Err(PathRequest::Ready(NavPath {
+                points: vec![from, to],
+            }));
+        }
+        let goal = self.grid.voxel_of(to);
+        if self.blocked(goal, radius) {
+            return Err(PathRequest::Failed);
+        }
+        Ok(PendingSearch {
+            search: PathSearch::new(self.grid.voxel_of(from), goal),
+            from,
+            to,
+            radius,
+            touched_secs: self.time.seconds_since_startup(),
+        })
+    }
+
+    fn finish(&self, pending: &PendingSearch, voxels: &[IVec3]) -> NavPath {
+        let mut lattice = Vec::with_capacity(voxels.len() + 2);
+        lattice.push(pending.from);
+        lattice.extend(voxels.iter().map(|voxel| self.grid.center_of(*voxel)));
+        lattice.push(pending.to);
+        NavPath {
+            points: string_pull(&lattice, |a, b| self.clear_between(a, b, pending.radius)),
+        }
+    }
+
+    /// A* through the voxels between `from` and `to` for a craft of `radius`, with the
+    /// result pulled taut so that it's not stuck to the voxel lattice.
+    /// The search is spread across frames, sharing [`NavigationGrid::expansions_per_frame`]
+    /// with everyone else's. Keep asking with the same `requester` till it's done. Asking
+    /// for a different destination or radius starts over.
+    pub fn request_path(
+        &mut self,
+        requester: Entity,
+        from: TVec3,
+        to: TVec3,
+        radius: TReal,
+    ) -> PathRequest {
+        let mut pending = match self.grid.searches.remove(&requester) {
+            Some(pending) if pending.to == to && pending.radius == radius => pending,
+            _ => match self.begin(from, to, radius) {
+                Ok(pending) => pending,
+                Err(request) => return request,
+            },
+        };
+        let (budget, max_expansions) = (self.grid.expansions_left, self.grid.max_expansions);
+        let before = pending.search.expansions();
+        let status = pending
+            .search
+            .step(budget, max_expansions, |voxel| self.blocked(voxel, radius));
+        self.grid.expansions_left -= (pending.search.expansions() - before).min(budget);
+        match status {
+            SearchStatus::Pending => {
+                pending.touched_secs = self.time.seconds_since_startup();
+                self.grid.searches.insert(requester, pending);
+                PathRequest::Pending
+            }
+            SearchStatus::Found(voxels) => PathRequest::Ready(self.finish(&pending, &voxels)),
+            SearchStatus::Failed => {
+                tracing::debug!(?from, ?to, "navigation search gave up");
+                PathRequest::Failed
+            }
+        }
+    }
+
+    /// Like [`Self::request_path`] but runs the whole search right away. Prefer that in
+    /// systems that run every frame.
+    pub fn find_path(&mut self, from: TVec3, to: TVec3, radius: TReal) -> Option<NavPath> {
+        let mut pending = match self.begin(from, to, radius) {
+            Ok(pending) => pending,
+            Err(PathRequest::Ready(path)) => return Some(path),
+            Err(_) => return None,
+        };
+        let max_expansions = self.grid.max_expansions;
+        match pending.search.step(usize::MAX, max_expansions, |voxel| {
+            self.blocked(voxel, radius)
+        }) {
+            SearchStatus::Found(voxels) => Some(self.finish(&pending, &voxels)),
+            _ => {
+                tracing::debug!(?from, ?to, "navigation search gave up");
+                None
+            }
+        }
+    }
+}
+
+pub(super) fn navigation_grid_janitor(mut grid: ResMut<NavigationGrid>, time: Res<Time>) {
+    let now_secs = time.seconds_since_startup();
+    let ttl_secs = grid.voxel_ttl_secs;
+    grid.voxels
+        .retain(|_, (_, probed_secs)| now_secs - *probed_secs < ttl_secs);
+    // the voxels an abandoned search has seen are stale by now anyways
+    grid.searches
+        .retain(|_, pending| now_secs - pending.touched_secs < ttl_secs);
+    grid.expansions_left = grid.expansions_per_frame;
+}
+
+#[test]
+fn spline_path_test() {
+    let path = SplinePath::new(
+        vec![
+            TVec3::ZERO,
+            TVec3::new(100., 0., 0.),
+            TVec3::new(200., 0., 0.),
+        ],
+        false,
+    );
+    assert_eq!(path.segment_count(), 2);
+    // goes through the points
+    assert!(path.position(1.).distance(TVec3::new(100., 0., 0.)) < 1e-3);
+    assert!((path.length() - 200.).abs() < 1e-2);
+    assert!((path.nearest_distance(TVec3::new(50., 10., 0.), None) - 50.).abs() < 1.);
+    assert!(
+        path.position(path.param_at(150.))
+            .distance(TVec3::new(150., 0., 0.))
+            < 1.
+    );
+    assert!(path.curvature(0.5) < 1e-3);
+
+    let square = SplinePath::new(
+        vec![
+            TVec3::ZERO,
+            TVec3::new(100., 0., 0.),
+            TVec3::new(100., 0., 100.),
+            TVec3::new(0., 0., 100.),
+        ],
+        true,
+    );
+    assert_eq!(square.segment_count(), 4);
+    // comes back round
+    assert!(square.position(4.).distance(TVec3::ZERO) < 1e-3);
+    assert!((square.wrap_distance(square.length() + 10.) - 10.).abs() < 1e-3);
+    assert!(square.curvature(1.) > 0.);
+}
+
+#[test]
+fn path_search_test() {
+    // a wall across the way with a gap up top
+    let wall = |voxel: IVec3| voxel.x == 2 && voxel.y <= 3 && voxel.z.abs() <= 3;
+    let mut search = PathSearch::new(IVec3::ZERO, IVec3::new(4, 0, 0));
+    // spread across calls
+    assert_eq!(search.step(1, 10_000, wall), SearchStatus::Pending);
+    let voxels = match search.step(10_000, 10_000, wall) {
+        SearchStatus::Found(voxels) => voxels,
+        status => panic!("no path found: {status:?}"),
+    };
+    assert_eq!(voxels.first(), Some(&IVec3::ZERO));
+    assert_eq!(voxels.last(), Some(&IVec3::new(4, 0, 0)));
+    assert!(voxels.iter().all(|voxel| !wall(*voxel)));
+    assert!(voxels.windows(2).all(|pair| {
+        let step = (pair[1] - pair[0]).abs();
+        step.max_element() == 1
+    }));
+
+    // boxed in
+    let cage = |voxel: IVec3| voxel.abs().max_element() == 2;
+    let mut search = PathSearch::new(IVec3::ZERO, IVec3::new(4, 0, 0));
+    assert_eq!(search.step(10_000, 10_000, cage), SearchStatus::Failed);
+    let mut search = PathSearch::new(IVec3::ZERO, IVec3::new(40, 0, 0));
+    assert_eq!(search.step(10_000, 5, |_| false), SearchStatus::Failed);
+}
+
+#[test]
+fn string_pull_test() {
+    let lattice = [
+        TVec3::ZERO,
+        TVec3::new(10., 0., 0.),
+        TVec3::new(20., 0., 0.),
+        TVec3::new(20., 10., 0.),
+        TVec3::new(20., 20., 0.),
+    ];
+    // nothing in the way
+    assert_eq!(
+        string_pull(&lattice, |_, _| true),
+        vec![lattice[0], lattice[4]]
+    );
+    // can't cut the corner
+    let corner = |a: TVec3, b: TVec3| a.x == b.x || a.y == b.y;
+    assert_eq!(
+        string_pull(&lattice, corner),
+        vec![lattice[0], lattice[2], lattice[4]]
+    );
+    assert!(string_pull(&[], |_, _| true).is_empty());
+}
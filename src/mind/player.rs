@@ -291,23 +291,26 @@ pub fn wpn_input(
     k_input: Res<Input<KeyCode>>,
     m_button_input: Res<Input<MouseButton>>,
     cur_craft: Res<CurrentCraft>,
-    crafts: Query<(&sensors::CraftWeaponsIndex,)>,
+    crafts: Query<(&sensors::CraftWeaponsIndex, &WeaponGroups)>,
     weapons: Query<&WeaponActivationState>,
     mut activate_wpn_events: EventWriter<ActivateWeaponEvent>,
     time: Res<Time>,
 ) {
     if let Some(entt) = &cur_craft.entt {
-        let (index,) = crafts.get(*entt).unwrap_or_log();
-        if k_input.pressed(KeyCode::Space) || m_button_input.pressed(MouseButton::Left) {
-            for wpn in index.entt_to_desc.keys() {
-                if weapons
-                    .get(*wpn)
-                    .expect_or_log("CurrentWeapon has no WeaponActivationState")
-                    .can_activate(&time)
-                {
-                    activate_wpn_events.send(ActivateWeaponEvent { weapon_id: *wpn });
-                }
-            }
+        let (index, groups) = crafts.get(*entt).unwrap_or_log();
+        let mut orders = FireOrders::NONE;
+        // primary trigger
+        orders.set(
+            WeaponGroups::PRIMARY,
+            k_input.pressed(KeyCode::Space) || m_button_input.pressed(MouseButton::Left),
+        );
+        // secondary trigger
+        orders.set(
+            WeaponGroups::SECONDARY,
+            k_input.pressed(KeyCode::LControl) || m_button_input.pressed(MouseButton::Right),
+        );
+        if !orders.is_empty() {
+            index.fire(groups, orders, &weapons, &time, &mut activate_wpn_events);
         }
     }
 }
@@ -375,6 +378,7 @@ pub fn engine_input(
         &engine::LinearEngineState,
         &crate::Colliders,
         &boid::steering::CraftControllerConsts,
+        &boid::BoidMindConfig,
     )>,
     cameras: Query<(&GlobalTransform, &CraftCamera)>,
     rapier: Res<RapierContext>,
@@ -449,7 +453,7 @@ pub fn engine_input(
         angular_input *= 10.;
     }
 
-    let (xform, lin_state, craft_colliders, consts) = crafts
+    let (xform, lin_state, craft_colliders, consts, mind_config) = crafts
         .get(cur_craft)
         .expect_or_log("unable to find current craft entity");
     let xform = xform.compute_transform();
@@ -464,7 +468,6 @@ pub fn engine_input(
                 ),
         )
     };
-    player_input.engine_ang = angular_input.into();
 
     if let Some((cam_xform, craft_cam)) = cameras
         .iter()
@@ -496,11 +499,14 @@ pub fn engine_input(
                 None => 5_000.,
             };
             let hit = orig + (dir * toi);
-            player_input.engine_ang.0 += boid::steering::look_to(
+            angular_input += boid::steering::look_to(
                 xform.rotation.inverse() * (hit - xform.translation).normalize(),
             );
         }
     }
+    player_input.engine_ang = boid::steering::AngularRoutineOutput::AngVel(
+        angular_input * mind_config.angular_input_multiplier,
+    );
 }
 
 #[derive(Component)]
@@ -0,0 +1,129 @@
+use deps::*;
+
+use bevy::{ecs::schedule::ParallelSystemDescriptorCoercion, prelude::*, utils::HashMap};
+use bevy_inspector_egui::InspectableRegistry;
+
+use super::{
+    boid::{steering::RoutineKind, strategy::BoidStrategyKind},
+    CraftMindSystems,
+};
+
+/// Names of the registered steering routine and boid strategy kinds for debugging.
+/// Filled in through [`MindAppExt`].
+#[derive(Debug, Default)]
+pub struct MindRegistry {
+    routines: HashMap<RoutineKind, &'static str>,
+    strategies: HashMap<BoidStrategyKind, &'static str>,
+}
+
+impl MindRegistry {
+    #[inline]
+    pub fn routine_name(&self, kind: RoutineKind) -> Option<&'static str> {
+        self.routines.get(&kind).copied()
+    }
+
+    #[inline]
+    pub fn strategy_name(&self, kind: BoidStrategyKind) -> Option<&'static str> {
+        self.strategies.get(&kind).copied()
+    }
+
+    #[inline]
+    pub fn routines(&self) -> impl Iterator<Item = (RoutineKind, &'static str)> + '_ {
+        self.routines.iter().map(|(kind, name)| (*kind, *name))
+    }
+
+    #[inline]
+    pub fn strategies(&self) -> impl Iterator<Item = (BoidStrategyKind, &'static str)> + '_ {
+        self.strategies.iter().map(|(kind, name)| (*kind, *name))
+    }
+}
+
+/// Wires up steering routines and boid strategies so that they can be added from outside
+/// of the [`super::MindPlugin`].
+pub trait MindAppExt {
+    /// Adds the routine's update system to the [`CraftMindSystems::SteeringRoutine`] set
+    /// and registers its parameter component with the inspector.
+    fn register_steering_routine<P, Params>(
+        &mut self,
+        name: &'static str,
+        update: impl ParallelSystemDescriptorCoercion<Params>,
+    ) -> &mut Self
+    where
+        P: Component + std::fmt::Debug;
+
+    /// Adds the strategy's butler to the [`CraftMindSystems::BoidStrategyButler`] set and its
+    /// update system to the [`CraftMindSystems::BoidStrategy`] set.
+    fn register_boid_strategy<P, ButlerParams, UpdateParams>(
+        &mut self,
+        name: &'static str,
+        butler: impl ParallelSystemDescriptorCoercion<ButlerParams>,
+        update: impl ParallelSystemDescriptorCoercion<UpdateParams>,
+    ) -> &mut Self
+    where
+        P: Component + std::fmt::Debug;
+
+    /// For strategies that do all their work setting up their routines in the butler.
+    fn register_boid_strategy_butler<P, ButlerParams>(
+        &mut self,
+        name: &'static str,
+        butler: impl ParallelSystemDescriptorCoercion<ButlerParams>,
+    ) -> &mut Self
+    where
+        P: Component + std::fmt::Debug;
+}
+
+impl MindAppExt for App {
+    fn register_steering_routine<P, Params>(
+        &mut self,
+        name: &'static str,
+        update: impl ParallelSystemDescriptorCoercion<Params>,
+    ) -> &mut Self
+    where
+        P: Component + std::fmt::Debug,
+    {
+        self.world
+            .get_resource_or_insert_with(MindRegistry::default)
+            .routines
+            .insert(RoutineKind::of::<P>(), name);
+        self.world
+            .get_resource_or_insert_with(InspectableRegistry::default)
+            .register_debug::<P>();
+        self.add_system(update.label(CraftMindSystems::SteeringRoutine))
+    }
+
+    fn register_boid_strategy<P, ButlerParams, UpdateParams>(
+        &mut self,
+        name: &'static str,
+        butler: impl ParallelSystemDescriptorCoercion<ButlerParams>,
+        update: impl ParallelSystemDescriptorCoercion<UpdateParams>,
+    ) -> &mut Self
+    where
+        P: Component + std::fmt::Debug,
+    {
+        self.register_boid_strategy_butler::<P, _>(name, butler)
+            .add_system(update.label(CraftMindSystems::BoidStrategy))
+    }
+
+    fn register_boid_strategy_butler<P, ButlerParams>(
+        &mut self,
+        name: &'static str,
+        butler: impl ParallelSystemDescriptorCoercion<ButlerParams>,
+    ) -> &mut Self
+    where
+        P: Component + std::fmt::Debug,
+    {
+        self.world
+            .get_resource_or_insert_with(MindRegistry::default)
+            .strategies
+            .insert(BoidStrategyKind::of::<P>(), name);
+        self.world
+            .get_resource_or_insert_with(InspectableRegistry::default)
+            .register_debug::<P>();
+        self.add_system_to_stage(
+            CoreStage::PreUpdate,
+            butler
+                .label(CraftMindSystems::BoidStrategyButler)
+                .after(CraftMindSystems::FlockChangeListener),
+        )
+    }
+}
@@ -1,11 +1,18 @@
 use deps::*;
 
 use bevy::{prelude::*, utils::HashMap};
+use bevy_inspector_egui::Inspectable;
+
+pub mod los;
+pub mod spatial;
 
 use crate::{
-    craft::{arms::*, attire::DamageType},
+    craft::{arms::*, attire::DamageType, countermeasures::PointDefence},
     math::*,
-    mind::boid::{steering::*, strategy::*},
+    mind::{
+        boid::{steering::*, strategy::*},
+        registry,
+    },
 };
 
 /// Used to store entity data for [`RemovedComponents`] usage.
@@ -32,7 +39,9 @@ impl<P> CrossReferenceIndex<P> {
     }
 }
 
-/// This'll track all the steering routines currently attached to the craft
+/// This'll track all the steering routines currently attached to the craft,
+/// active or not. Inactive, shared, pinned and ones waiting on the garbage collector included
+/// so filter on [`ActiveSteeringRoutine`] when only the ones in use matter.
 /// Craft mind component
 #[derive(Debug, Clone, Component, Default)]
 pub struct SteeringRoutinesIndex {
@@ -40,10 +49,22 @@ pub struct SteeringRoutinesIndex {
     pub kind_to_entt: HashMap<RoutineKind, SVec<[Entity; 3]>>,
 }
 
+/// The craft's routines of kind `P` that strategies can share.
+pub type SharedRoutines<'w, 's, P> =
+    Query<'w, 's, (), (With<P>, With<SharedSteeringRoutine>, With<SteeringRoutine>)>;
+
 impl SteeringRoutinesIndex {
     pub fn kind<P: Component>(&self) -> Option<&SVec<[Entity; 3]>> {
         self.kind_to_entt.get(&RoutineKind::of::<P>())
     }
+    /// A [`SharedSteeringRoutine`] of kind `P` for a new strategy to reuse.
+    /// The query weeds out any that were despawned since the index was updated.
+    pub fn reusable<P: Component>(&self, shared: &SharedRoutines<P>) -> Option<Entity> {
+        self.kind::<P>()?
+            .iter()
+            .copied()
+            .find(|entt| shared.contains(*entt))
+    }
     pub fn insert(&mut self, entt: Entity, kind: RoutineKind) {
         self.entt_to_kind.insert(entt, kind);
         self.kind_to_entt.entry(kind).or_default().push(entt);
@@ -68,68 +89,153 @@ impl SteeringRoutinesIndex {
 /// Used to handle index maintainance for the case of [`SteeringRoutine`] entity despawns.
 pub type SteeringRoutineCrossRefIndex = CrossReferenceIndex<Entity>;
 
-/// This is used to store what craft a previously active routine was pointing to
-/// in order to remove the routine from the indice after deactivation.
-#[derive(Debug, Clone, Component)]
-#[component(storage = "SparseSet")]
-pub struct PreviouslyActiveRoutine;
+/// The [`Name`]s the steering routine bundles give routines unless told otherwise.
+const ROUTINE_DEFAULT_NAMES: [&str; 3] = [
+    LinOnlyRoutineBundle::<SteeringRoutine>::DEFAULT_NAME,
+    AngOnlyRoutineBundle::<SteeringRoutine>::DEFAULT_NAME,
+    LinAngRoutineBundle::<SteeringRoutine>::DEFAULT_NAME,
+];
 
+/// Also swaps the generic bundle [`Name`]s of new routines for the names their kinds were
+/// registered under so that they can be told apart in the inspector.
 pub(super) fn craft_routine_index_butler(
-    mut commands: Commands,
-    mut routines: ParamSet<(
-        // activated
-        Query<(Entity, &SteeringRoutine), Added<ActiveSteeringRoutine>>,
-        // deactivated
-        Query<
-            (Entity, &SteeringRoutine),
-            (
-                Without<ActiveSteeringRoutine>,
-                With<PreviouslyActiveRoutine>,
-            ),
-        >,
-    )>,
+    mut new: Query<(Entity, &SteeringRoutine, Option<&mut Name>), Added<SteeringRoutine>>,
     mut indices: Query<&mut SteeringRoutinesIndex>,
-    removed_components: RemovedComponents<SteeringRoutine>,
+    removed: RemovedComponents<SteeringRoutine>,
     mut cross_ref_index: ResMut<SteeringRoutineCrossRefIndex>,
+    registry: Res<registry::MindRegistry>,
 ) {
-    for (entt, routine) in routines.p0().iter() {
-        commands.entity(entt).insert(PreviouslyActiveRoutine);
-
-        // add them to the index
+    for (entt, routine, name) in new.iter_mut() {
         let mut index = indices
             .get_mut(routine.boid_entt())
-            .expect_or_log("craft not foud SteeringRoutine");
+            .expect_or_log("SteeringRoutine's boid_entt not found in world");
         index.insert(entt, routine.kind());
+        cross_ref_index.insert(entt, routine.boid_entt());
+        let kind_name = registry.routine_name(routine.kind());
+        if let (Some(kind_name), Some(mut name)) = (kind_name, name) {
+            // leave names given on purpose be
+            if ROUTINE_DEFAULT_NAMES.contains(&name.as_str()) {
+                *name = Name::new(kind_name);
+            }
+        }
+        tracing::trace!(
+            routine = ?entt,
+            boid = ?routine.boid_entt(),
+            kind = kind_name.unwrap_or("unregistered"),
+            "steering routine added"
+        );
     }
-    for (entt, routine) in routines.p1().iter() {
-        let mut index = indices
-            .get_mut(routine.boid_entt())
-            .expect_or_log("boid_entt not found for ActiveRoutine");
-        index.remove(entt);
-        commands.entity(entt).remove::<PreviouslyActiveRoutine>();
-    }
-    for routine in removed_components.iter() {
+    for routine in removed.iter() {
+        // avoid panicing since the entire craft (and its indices) might be gone
         if let Some(Ok(mut index)) = cross_ref_index.remove(&routine).map(|e| indices.get_mut(e)) {
             index.remove(routine);
         }
     }
 }
 
+/// How a weapon's projectiles travel.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Ballistics {
+    /// Muzzle speed.
+    pub speed: TReal,
+    pub lifespan_secs: TReal,
+    /// Constant acceleration the projectile's under. Gravity, mostly.
+    pub drop: TVec3,
+    /// Whether the shooter's velocity's added onto the projectile's.
+    pub inherits_velocity: bool,
+}
+
+/// Where to point a weapon in order to hit a target.
+#[derive(Debug, Clone, Copy)]
+pub struct LeadSolution {
+    /// The point to aim the barrel at.
+    pub aim_pos: TVec3,
+    /// Where the target's expected to be hit.
+    pub impact_pos: TVec3,
+    pub time_secs: TReal,
+}
+
+impl Ballistics {
+    const LEAD_ITERATIONS: usize = 4;
+
+    #[inline]
+    pub fn range(&self) -> TReal {
+        self.speed * self.lifespan_secs
+    }
+
+    /// Assumes the target keeps its current velocity.
+    /// `None` if the projectile can't reach the target within its lifespan.
+    pub fn solve_lead(
+        &self,
+        wpn_pos: TVec3,
+        shooter_vel: TVec3,
+        target_pos: TVec3,
+        target_vel: TVec3,
+    ) -> Option<LeadSolution> {
+        let rel_pos = target_pos - wpn_pos;
+        let rel_vel = if self.inherits_velocity {
+            target_vel - shooter_vel
+        } else {
+            target_vel
+        };
+        // solve the drag free case first
+        // |rel_pos + rel_vel * t| = speed * t
+        let a = rel_vel.length_squared() - (self.speed * self.speed);
+        let b = 2. * rel_pos.dot(rel_vel);
+        let c = rel_pos.length_squared();
+        let mut time = if a.abs() < TReal::EPSILON {
+            if b.abs() < TReal::EPSILON {
+                return None;
+            }
+            -c / b
+        } else {
+            let discriminant = (b * b) - (4. * a * c);
+            if discriminant < 0. {
+                return None;
+            }
+            let sqrt = discriminant.sqrt();
+            let (t1, t2) = ((-b - sqrt) / (2. * a), (-b + sqrt) / (2. * a));
+            match (t1 > 0., t2 > 0.) {
+                (true, true) => t1.min(t2),
+                (true, false) => t1,
+                (false, true) => t2,
+                (false, false) => return None,
+            }
+        };
+        // then refine for drop
+        let offset_at = |time: TReal| rel_pos + (rel_vel * time) - (0.5 * self.drop * time * time);
+        if self.drop != TVec3::ZERO {
+            for _ in 0..Self::LEAD_ITERATIONS {
+                time = offset_at(time).length() / self.speed;
+            }
+        }
+        if time <= 0. || time > self.lifespan_secs || !time.is_finite() {
+            return None;
+        }
+        Some(LeadSolution {
+            aim_pos: wpn_pos + offset_at(time),
+            impact_pos: target_pos + (target_vel * time),
+            time_secs: time,
+        })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct WeaponDesc {
     pub kind: WeaponKind,
-    pub speed: TReal,
+    pub ballistics: Ballistics,
     pub range: TReal,
     pub class: WeaponClass,
     pub damage_type: DamageType,
+    /// Under automatic control, e.g. [`PointDefence`] or [`Turret`]. Left out of the fallback
+    /// [`WeaponGroups::PRIMARY`] group.
+    pub automatic: bool,
 }
 
 /// This'll track all the weapons currently attached to the craft
 /// Craft mind component
 #[derive(Debug, Clone, Component, Default)]
 pub struct CraftWeaponsIndex {
-    pub avg_projectile_speed: TReal,
-    mean_value_size: usize,
     pub entt_to_desc: HashMap<Entity, WeaponDesc>,
     pub class_to_entt: HashMap<WeaponClass, SVec<[Entity; 3]>>,
     pub kind_to_entt: HashMap<WeaponKind, SVec<[Entity; 3]>>,
@@ -139,6 +245,109 @@ impl CraftWeaponsIndex {
     pub fn kind<P: Component>(&self) -> Option<&SVec<[Entity; 3]>> {
         self.kind_to_entt.get(&WeaponKind::of::<P>())
     }
+    /// All the weapons that belong to the given group.
+    /// See [`WeaponGroups::is_unconfigured`] for crafts that haven't set up any groups.
+    pub fn group<'a>(
+        &'a self,
+        groups: &'a WeaponGroups,
+        group: WeaponGroupId,
+    ) -> impl Iterator<Item = Entity> + 'a {
+        let everything = if group == WeaponGroups::PRIMARY && groups.is_unconfigured() {
+            Some(
+                self.entt_to_desc
+                    .iter()
+                    .filter(|(_, desc)| !desc.automatic)
+                    .map(|(entt, _)| *entt),
+            )
+        } else {
+            None
+        };
+        groups
+            .classes(group)
+            .iter()
+            .filter_map(|class| self.class_to_entt.get(class))
+            .flat_map(|entts| entts.iter().copied())
+            .chain(everything.into_iter().flatten())
+    }
+    /// All the weapons in the ordered groups, each only once even if its class is in more
+    /// than one of them.
+    pub fn ordered(&self, groups: &WeaponGroups, orders: FireOrders) -> SVec<[Entity; 8]> {
+        let mut wpns = SVec::<[Entity; 8]>::new();
+        for group in orders.iter() {
+            for wpn in self.group(groups, group) {
+                if !wpns.contains(&wpn) {
+                    wpns.push(wpn);
+                }
+            }
+        }
+        wpns
+    }
+    /// The shortest [`WeaponDesc::range`] of the weapons in the group.
+    /// `None` if the group has no weapons.
+    pub fn group_range(&self, groups: &WeaponGroups, group: WeaponGroupId) -> Option<TReal> {
+        self.group(groups, group)
+            .filter_map(|entt| self.entt_to_desc.get(&entt))
+            .map(|desc| desc.range)
+            .reduce(TReal::min)
+    }
+    /// See [`Ballistics::solve_lead`].
+    pub fn lead(
+        &self,
+        wpn: Entity,
+        wpn_pos: TVec3,
+        shooter_vel: TVec3,
+        target_pos: TVec3,
+        target_vel: TVec3,
+    ) -> Option<LeadSolution> {
+        self.entt_to_desc.get(&wpn)?.ballistics.solve_lead(
+            wpn_pos,
+            shooter_vel,
+            target_pos,
+            target_vel,
+        )
+    }
+    /// The mean of the aim points of all the weapons in the group that can reach the target,
+    /// solved from the `origin`. Weapons on the same group are assumed to be close together.
+    pub fn group_lead(
+        &self,
+        groups: &WeaponGroups,
+        group: WeaponGroupId,
+        origin: TVec3,
+        shooter_vel: TVec3,
+        target_pos: TVec3,
+        target_vel: TVec3,
+    ) -> Option<TVec3> {
+        let (sum, count) = self
+            .group(groups, group)
+            .filter_map(|entt| self.lead(entt, origin, shooter_vel, target_pos, target_vel))
+            .fold((TVec3::ZERO, 0), |(sum, count), solution| {
+                (sum + solution.aim_pos, count + 1)
+            });
+        if count > 0 {
+            Some(sum / count as TReal)
+        } else {
+            None
+        }
+    }
+    /// Sends [`ActivateWeaponEvent`]s for all the ready weapons in the ordered groups.
+    pub fn fire(
+        &self,
+        groups: &WeaponGroups,
+        orders: FireOrders,
+        weapons: &Query<&WeaponActivationState>,
+        time: &Time,
+        events: &mut EventWriter<ActivateWeaponEvent>,
+    ) {
+        for wpn in self.ordered(groups, orders) {
+            if weapons
+                .get(wpn)
+                .expect_or_log("Indexed weapon has no WeaponActivationState")
+                .can_activate(time)
+            {
+                events.send(ActivateWeaponEvent { weapon_id: wpn });
+            }
+        }
+    }
     pub fn insert(&mut self, entt: Entity, desc: WeaponDesc) {
         self.kind_to_entt.entry(desc.kind).or_default().push(entt);
         self.class_to_entt.entry(desc.class).or_default().push(entt);
@@ -175,6 +384,8 @@ pub(super) fn craft_wpn_index_butler(
     removed: RemovedComponents<CraftWeapon>,
     mut cross_ref_index: ResMut<CraftWeaponCrossRefIndex>,
     projectile_wpns: Query<&ProjectileWeapon>,
+    automatic: Query<(), Or<(With<PointDefence>, With<Turret>)>>,
+    rapier_config: Res<bevy_rapier3d::prelude::RapierConfiguration>,
 ) {
     for (entt, wpn) in new_wpns.iter() {
         // add them to the per craft
@@ -186,17 +397,20 @@ pub(super) fn craft_wpn_index_butler(
             let param = projectile_wpns
                 .get(entt)
                 .expect_or_log("ProjectileWeapon component not found");
-            let speed = param.proj_velocity.length();
-
-            index.avg_projectile_speed +=
-                (speed - index.avg_projectile_speed) / (index.mean_value_size + 1) as TReal;
-            index.mean_value_size += 1;
+            let ballistics = Ballistics {
+                speed: param.proj_velocity.length(),
+                lifespan_secs: param.proj_lifespan_secs as TReal,
+                // projectiles are dynamic bodies
+                drop: rapier_config.gravity,
+                inherits_velocity: param.proj_inherit_velocity,
+            };
             WeaponDesc {
                 kind: wpn.kind(),
                 class: wpn.class(),
-                range: speed * param.proj_lifespan_secs as f32,
+                range: ballistics.range(),
                 damage_type: param.proj_damage.damage_type,
-                speed,
+                ballistics,
+                automatic: automatic.contains(entt),
             }
         } else {
             unreachable!()
@@ -208,18 +422,174 @@ pub(super) fn craft_wpn_index_butler(
     }
     for removed_wpn in removed.iter() {
         // avoid panicing since the entire craft (and its indices) might be gone
-        if let Some((Ok(mut index), WeaponDesc { speed, .. })) = cross_ref_index
+        if let Some(Ok(mut index)) = cross_ref_index
             .remove(&removed_wpn)
-            .map(|(e, desc)| (indices.get_mut(e), desc))
+            .map(|(e, _)| indices.get_mut(e))
         {
             index.remove(removed_wpn);
-            index.avg_projectile_speed +=
-                (speed - index.avg_projectile_speed) / (index.mean_value_size - 1) as TReal;
-            index.mean_value_size -= 1;
         }
     }
 }
 
+/// The craft's means of perceiving its surroundings. Only what's picked up here
+/// ends up in the craft's [`Contacts`].
+/// Craft mind component
+#[derive(Debug, Clone, Component, Inspectable)]
+pub struct Radar {
+    pub range: TReal,
+    /// Half angle of the detection cone about the craft's forward.
+    /// Anything at or above PI is omnidirectional.
+    pub fov_half_angle_radians: TReal,
+    /// Sweeps per second.
+    pub update_rate: f64,
+    /// How long a [`Contact`] is remembered after it's lost.
+    pub memory_secs: f64,
+    #[inspectable(ignore)]
+    pub last_sweep_secs: f64,
+}
+
+impl Default for Radar {
+    fn default() -> Self {
+        Self {
+            range: 2_000.,
+            fov_half_angle_radians: real::consts::PI,
+            update_rate: 4.,
+            memory_secs: 5.,
+            last_sweep_secs: f64::NEG_INFINITY,
+        }
+    }
+}
+
+impl Radar {
+    #[inline]
+    pub fn can_see(&self, pos: TVec3, fwd: TVec3, obj_pos: TVec3) -> bool {
+        let offset = obj_pos - pos;
+        offset.length_squared() <= self.range * self.range
+            && (self.fov_half_angle_radians >= real::consts::PI
+                || fwd.angle_between(offset) <= self.fov_half_angle_radians)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContactClass {
+    Craft,
+    Projectile,
+    Decoy,
+    Unknown,
+}
+
+impl ContactClass {
+    #[inline]
+    pub fn classify(is_craft: bool, is_projectile: bool, is_decoy: bool) -> Self {
+        if is_craft {
+            Self::Craft
+        } else if is_projectile {
+            Self::Projectile
+        } else if is_decoy {
+            Self::Decoy
+        } else {
+            Self::Unknown
+        }
+    }
+}
+
+/// What the craft knows about some object.
+#[derive(Debug, Clone)]
+pub struct Contact {
+    pub last_pos: TVec3,
+    pub last_vel: TVec3,
+    pub last_seen_secs: f64,
+    pub class: ContactClass,
+}
+
+impl Contact {
+    /// Seconds since the contact was last picked up.
+    #[inline]
+    pub fn age_secs(&self, time: &Time) -> f64 {
+        time.seconds_since_startup() - self.last_seen_secs
+    }
+
+    /// Dead reckoned from the last known position and velocity.
+    #[inline]
+    pub fn estimated_pos(&self, time: &Time) -> TVec3 {
+        self.last_pos + (self.last_vel * self.age_secs(time) as TReal)
+    }
+}
+
+/// Everything the craft's [`Radar`] has picked up recently.
+/// Minds should look things up here instead of querying the world directly.
+/// Craft mind component
+#[derive(Debug, Clone, Component, Default)]
+pub struct Contacts {
+    pub contacts: HashMap<Entity, Contact>,
+}
+
+impl Contacts {
+    #[inline]
+    pub fn get(&self, entt: Entity) -> Option<&Contact> {
+        self.contacts.get(&entt)
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &Contact)> {
+        self.contacts.iter().map(|(entt, contact)| (*entt, contact))
+    }
+
+    pub fn of_class(&self, class: ContactClass) -> impl Iterator<Item = (Entity, &Contact)> {
+        self.iter()
+            .filter(move |(_, contact)| contact.class == class)
+    }
+}
+
+pub(super) fn radar_sweep(
+    mut crafts: Query<(Entity, &GlobalTransform, &mut Radar, &mut Contacts)>,
+    index: Res<spatial::SpatialIndex>,
+    signatures: Query<&crate::craft::stealth::RadarSignature>,
+    mut los: los::LineOfSight,
+    time: Res<Time>,
+) {
+    let now = time.seconds_since_startup();
+    for (craft_entt, xform, mut radar, mut contacts) in crafts.iter_mut() {
+        if now - radar.last_sweep_secs < 1. / radar.update_rate {
+            continue;
+        }
+        radar.last_sweep_secs = now;
+
+        let pos = xform.translation();
+        let fwd = xform.forward();
+        for item in index.within_radius(pos, radar.range) {
+            // quiet and small crafts have to be closer to be picked up
+            let detection_range = signatures
+                .get(item.entt)
+                .map(|sig| radar.range * sig.detection_range_multiplier())
+                .unwrap_or(radar.range);
+            if item.entt == craft_entt
+                || item.pos.distance_squared(pos) > detection_range * detection_range
+                || !radar.can_see(pos, fwd, item.pos)
+                // short lived contacts would only churn the cache so only crafts get occluded
+                || (item.class == ContactClass::Craft
+                    && !los.between(craft_entt, pos, item.entt, item.pos))
+            {
+                continue;
+            }
+            contacts.contacts.insert(
+                item.entt,
+                Contact {
+                    last_pos: item.pos,
+                    last_vel: item.vel,
+                    last_seen_secs: now,
+                    class: item.class,
+                },
+            );
+        }
+        // forget the stale ones
+        let memory_secs = radar.memory_secs;
+        contacts
+            .contacts
+            .retain(|_, contact| now - contact.last_seen_secs <= memory_secs);
+    }
+}
+
 /// This'll track all the strategies currently attached to the craft
 /// Craft mind component
 #[derive(Debug, Clone, Component, Default)]
@@ -253,13 +623,15 @@ impl BoidStrategyIndex {
 /// Used to handle index maintainance for the case of [`CraftStrategy`] entity despawns.
 pub type BoidStrategyCrossRefIndex = CrossReferenceIndex<Entity>;
 
+/// Names new strategies after their registered kinds like [`craft_routine_index_butler`].
 pub(super) fn craft_strategy_index_butler(
-    new: Query<(Entity, &BoidStrategy), Added<BoidStrategy>>,
+    mut new: Query<(Entity, &BoidStrategy, Option<&mut Name>), Added<BoidStrategy>>,
     mut indices: Query<&mut BoidStrategyIndex>,
     removed: RemovedComponents<BoidStrategy>,
     mut cross_ref_index: ResMut<BoidStrategyCrossRefIndex>,
+    registry: Res<registry::MindRegistry>,
 ) {
-    for (entt, strategy) in new.iter() {
+    for (entt, strategy, name) in new.iter_mut() {
         // add them to the per craft
         let mut index = indices
             .get_mut(strategy.boid_entt())
@@ -267,6 +639,19 @@ pub(super) fn craft_strategy_index_butler(
         index.insert(entt, strategy.kind());
         // add them to the global index
         cross_ref_index.insert(entt, strategy.boid_entt());
+        let kind_name = registry.strategy_name(strategy.kind());
+        if let (Some(kind_name), Some(mut name)) = (kind_name, name) {
+            // leave names given on purpose be
+            if name.as_str() == BoidStrategyBundle::<BoidStrategy>::DEFAULT_NAME {
+                *name = Name::new(kind_name);
+            }
+        }
+        tracing::debug!(
+            strategy = ?entt,
+            boid = ?strategy.boid_entt(),
+            kind = kind_name.unwrap_or("unregistered"),
+            "boid strategy added"
+        );
     }
     for removed_wpn in removed.iter() {
         // avoid panicing since the entire craft (and its indices) might be gone
@@ -278,3 +663,104 @@ pub(super) fn craft_strategy_index_butler(
         }
     }
 }
+
+#[test]
+fn solve_lead_test() {
+    let ballistics = Ballistics {
+        speed: 100.,
+        lifespan_secs: 10.,
+        ..default()
+    };
+    // stationary target
+    let solution = ballistics
+        .solve_lead(TVec3::ZERO, TVec3::ZERO, TVec3::Z * -100., TVec3::ZERO)
+        .unwrap();
+    assert!((solution.time_secs - 1.).abs() < 0.001);
+    assert!(solution.aim_pos.distance(TVec3::Z * -100.) < 0.001);
+
+    // crossing target
+    let target_vel = TVec3::X * 50.;
+    let solution = ballistics
+        .solve_lead(TVec3::ZERO, TVec3::ZERO, TVec3::Z * -100., target_vel)
+        .unwrap();
+    let proj_pos = solution.aim_pos.normalize() * ballistics.speed * solution.time_secs;
+    assert!(proj_pos.distance(solution.impact_pos) < 0.001);
+
+    // out of reach
+    assert!(ballistics
+        .solve_lead(TVec3::ZERO, TVec3::ZERO, TVec3::Z * -100., TVec3::Z * -200.)
+        .is_none());
+
+    // with drop
+    let ballistics = Ballistics {
+        drop: TVec3::Y * -9.81,
+        ..ballistics
+    };
+    let solution = ballistics
+        .solve_lead(TVec3::ZERO, TVec3::ZERO, TVec3::Z * -100., TVec3::ZERO)
+        .unwrap();
+    assert!(solution.aim_pos.y > 0.);
+}
+
+#[test]
+fn weapon_group_resolution_test() {
+    let mut index = CraftWeaponsIndex::default();
+    let desc = |class| WeaponDesc {
+        kind: WeaponKind::of::<ProjectileWeapon>(),
+        ballistics: default(),
+        range: 100.,
+        class,
+        damage_type: default(),
+        automatic: false,
+    };
+    let (cannon, launcher, turret) = (
+        Entity::from_raw(0),
+        Entity::from_raw(1),
+        Entity::from_raw(2),
+    );
+    index.insert(cannon, desc("cannon"));
+    index.insert(launcher, desc("launcher"));
+    index.insert(
+        turret,
+        WeaponDesc {
+            automatic: true,
+            ..desc("point_defence")
+        },
+    );
+    let sorted = |mut wpns: Vec<Entity>| {
+        wpns.sort();
+        wpns
+    };
+
+    // everything but the turret's primary until groups are set up
+    let groups = WeaponGroups::default();
+    assert_eq!(
+        sorted(index.group(&groups, WeaponGroups::PRIMARY).collect()),
+        vec![cannon, launcher]
+    );
+    assert_eq!(index.group(&groups, WeaponGroups::SECONDARY).count(), 0);
+
+    let groups = WeaponGroups::default()
+        .with(WeaponGroups::PRIMARY, "cannon")
+        .with(WeaponGroups::PRIMARY, "cannon")
+        .with(WeaponGroups::SECONDARY, "launcher")
+        .with(WeaponGroups::SECONDARY, "cannon");
+    assert_eq!(
+        index
+            .group(&groups, WeaponGroups::PRIMARY)
+            .collect::<Vec<_>>(),
+        vec![cannon]
+    );
+    assert_eq!(
+        sorted(index.group(&groups, WeaponGroups::SECONDARY).collect()),
+        vec![cannon, launcher]
+    );
+
+    // the cannon's in both groups but only gets fired once
+    let mut orders = FireOrders::single(WeaponGroups::PRIMARY);
+    orders.set(WeaponGroups::SECONDARY, true);
+    assert_eq!(
+        sorted(index.ordered(&groups, orders).into_vec()),
+        vec![cannon, launcher]
+    );
+}
@@ -0,0 +1,208 @@
+use deps::*;
+
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashMap};
+use bevy_rapier3d::prelude::*;
+
+use crate::{craft::attire::ColliderGroups, math::*};
+
+#[derive(Debug, Clone, Copy)]
+struct LosEntry {
+    clear: bool,
+    checked_secs: f64,
+}
+
+/// Line of sight results between pairs of entities. Each pair's ray cast at most
+/// once every [`Self::refresh_secs`]. Access it through [`LineOfSight`].
+#[derive(Debug)]
+pub struct LineOfSightCache {
+    pub refresh_secs: f64,
+    /// Entries that haven't been asked about for this long are dropped.
+    pub forget_secs: f64,
+    entries: HashMap<(Entity, Entity), LosEntry>,
+    /// Keyed by weapon then target. Not symmetric.
+    fire_entries: HashMap<(Entity, Option<Entity>), LosEntry>,
+}
+
+impl Default for LineOfSightCache {
+    fn default() -> Self {
+        Self {
+            refresh_secs: 0.25,
+            forget_secs: 5.,
+            entries: default(),
+            fire_entries: default(),
+        }
+    }
+}
+
+impl LineOfSightCache {
+    #[inline]
+    fn key(a: Entity, b: Entity) -> (Entity, Entity) {
+        if a.to_bits() <= b.to_bits() {
+            (a, b)
+        } else {
+            (b, a)
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.fire_entries.clear();
+    }
+}
+
+/// Whether anything [`ColliderGroups::SOLID`] lies between `from` and `to`.
+/// Colliders of the `ends`, or ones whose parent is one of them, don't count.
+pub fn solid_between(rapier: &RapierContext, from: TVec3, to: TVec3, ends: &[Entity]) -> bool {
+    let offset = to - from;
+    let dst = offset.length();
+    if dst < TReal::EPSILON {
+        return false;
+    }
+    rapier
+        .cast_ray(
+            from,
+            offset / dst,
+            dst,
+            false,
+            QueryFilter {
+                groups: Some(InteractionGroups::new(
+                    ColliderGroups::SOLID.bits(),
+                    ColliderGroups::SOLID.bits(),
+                )),
+                predicate: Some(&|handle| {
+                    !ends.contains(&handle)
+                        && rapier
+                            .collider_parent(handle)
+                            .map(|parent| !ends.contains(&parent))
+                            .unwrap_or(true)
+                }),
+                ..default()
+            },
+        )
+        .is_some()
+}
+
+/// Whether a shot from `from` towards `to` would reach `target` before anything solid or any
+/// other craft. Colliders for which `ignore` returns true, usually the shooter's, are skipped.
+/// Any other craft in the way counts, whatever its [`crate::craft::Faction`].
+/// Without a `target`, e.g. for untargeted fire, anything hit at all blocks the shot.
+pub fn line_of_fire_clear(
+    rapier: &RapierContext,
+    from: TVec3,
+    to: TVec3,
+    target: Option<Entity>,
+    ignore: &dyn Fn(Entity) -> bool,
+) -> bool {
+    let offset = to - from;
+    let dst = offset.length();
+    if dst < TReal::EPSILON {
+        return true;
+    }
+    match rapier.cast_ray(
+        from,
+        offset / dst,
+        dst,
+        false,
+        QueryFilter {
+            groups: Some(InteractionGroups::new(
+                ColliderGroups::SOLID.bits(),
+                (ColliderGroups::SOLID | ColliderGroups::CRAFT_SOLID).bits(),
+            )),
+            predicate: Some(&|handle| !ignore(handle)),
+            ..default()
+        },
+    ) {
+        Some((hit, _)) => target.is_some() && rapier.collider_parent(hit) == target,
+        None => true,
+    }
+}
+
+/// Occlusion queries against the physics world that only consider
+/// [`ColliderGroups::SOLID`] colliders, i.e. obstacles. Crafts don't block sight.
+#[derive(SystemParam)]
+pub struct LineOfSight<'w, 's> {
+    rapier: Res<'w, RapierContext>,
+    cache: ResMut<'w, LineOfSightCache>,
+    time: Res<'w, Time>,
+    #[system_param(ignore)]
+    _phantom: std::marker::PhantomData<&'s ()>,
+}
+
+impl LineOfSight<'_, '_> {
+    /// Whether `a` at `a_pos` can see `b` at `b_pos`. Symmetric.
+    /// Results are reused till they're older than [`LineOfSightCache::refresh_secs`].
+    pub fn between(&mut self, a: Entity, a_pos: TVec3, b: Entity, b_pos: TVec3) -> bool {
+        let now_secs = self.time.seconds_since_startup();
+        let key = LineOfSightCache::key(a, b);
+        if let Some(entry) = self.cache.entries.get(&key) {
+            if now_secs - entry.checked_secs < self.cache.refresh_secs {
+                return entry.clear;
+            }
+        }
+        let clear = !solid_between(&self.rapier, a_pos, b_pos, &[a, b]);
+        self.cache.entries.insert(
+            key,
+            LosEntry {
+                clear,
+                checked_secs: now_secs,
+            },
+        );
+        clear
+    }
+
+    /// Cached per weapon and target like [`Self::between`] since weapons fire every frame.
+    /// Keyed by the weapon rather than its craft as each weapon has its own muzzle and aim point.
+    /// See [`line_of_fire_clear`].
+    pub fn line_of_fire(
+        &mut self,
+        wpn: Entity,
+        from: TVec3,
+        target: Option<Entity>,
+        to: TVec3,
+        ignore: &dyn Fn(Entity) -> bool,
+    ) -> bool {
+        let now_secs = self.time.seconds_since_startup();
+        let key = (wpn, target);
+        if let Some(entry) = self.cache.fire_entries.get(&key) {
+            if now_secs - entry.checked_secs < self.cache.refresh_secs {
+                return entry.clear;
+            }
+        }
+        let clear = line_of_fire_clear(&self.rapier, from, to, target, ignore);
+        self.cache.fire_entries.insert(
+            key,
+            LosEntry {
+                clear,
+                checked_secs: now_secs,
+            },
+        );
+        clear
+    }
+
+    /// Uncached. Whether `target` at `target_pos` is exposed to a point, e.g. a blast.
+    #[inline]
+    pub fn exposed_to(&self, point: TVec3, target: Entity, target_pos: TVec3) -> bool {
+        !solid_between(&self.rapier, point, target_pos, &[target])
+    }
+}
+
+pub(in crate::mind) fn line_of_sight_janitor(mut cache: ResMut<LineOfSightCache>, time: Res<Time>) {
+    let now_secs = time.seconds_since_startup();
+    let forget_secs = cache.forget_secs;
+    cache
+        .entries
+        .retain(|_, entry| now_secs - entry.checked_secs < forget_secs);
+    cache
+        .fire_entries
+        .retain(|_, entry| now_secs - entry.checked_secs < forget_secs);
+}
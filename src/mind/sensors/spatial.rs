@@ -0,0 +1,188 @@
+use deps::*;
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_rapier3d::prelude::*;
+
+use super::ContactClass;
+use crate::{
+    craft::{arms::Projectile, countermeasures::Decoy, CraftDimensions},
+    math::*,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct SpatialItem {
+    pub entt: Entity,
+    pub pos: TVec3,
+    pub vel: TVec3,
+    pub class: ContactClass,
+}
+
+/// A spatial hash of all the rigid bodies in the world, rebuilt every frame.
+/// Use it for neighbour queries instead of iterating over everything.
+#[derive(Debug)]
+pub struct SpatialIndex {
+    pub cell_size: TReal,
+    cells: HashMap<IVec3, SVec<[usize; 4]>>,
+    items: Vec<SpatialItem>,
+    entt_to_item: HashMap<Entity, usize>,
+}
+
+impl Default for SpatialIndex {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CELL_SIZE)
+    }
+}
+
+impl SpatialIndex {
+    pub const DEFAULT_CELL_SIZE: TReal = 50.;
+
+    pub fn new(cell_size: TReal) -> Self {
+        Self {
+            cell_size,
+            cells: default(),
+            items: default(),
+            entt_to_item: default(),
+        }
+    }
+
+    #[inline]
+    fn cell_of(&self, pos: TVec3) -> IVec3 {
+        (pos / self.cell_size).floor().as_ivec3()
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.items.clear();
+        self.entt_to_item.clear();
+    }
+
+    pub fn insert(&mut self, item: SpatialItem) {
+        let idx = self.items.len();
+        self.cells
+            .entry(self.cell_of(item.pos))
+            .or_default()
+            .push(idx);
+        self.entt_to_item.insert(item.entt, idx);
+        self.items.push(item);
+    }
+
+    #[inline]
+    pub fn get(&self, entt: Entity) -> Option<&SpatialItem> {
+        self.entt_to_item.get(&entt).map(|idx| &self.items[*idx])
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// All the items within `radius` of `pos`, in no particular order.
+    pub fn within_radius(
+        &self,
+        pos: TVec3,
+        radius: TReal,
+    ) -> impl Iterator<Item = &SpatialItem> + '_ {
+        let radius_squared = radius * radius;
+        let (min, max) = (
+            self.cell_of(pos - TVec3::splat(radius)),
+            self.cell_of(pos + TVec3::splat(radius)),
+        );
+        let span = |a: i32, b: i32| (b as i64 - a as i64 + 1) as u64;
+        let cell_count = span(min.x, max.x)
+            .saturating_mul(span(min.y, max.y))
+            .saturating_mul(span(min.z, max.z));
+        // visiting the cells is more expensive than a scan for large radii
+        let candidates: Box<dyn Iterator<Item = &SpatialItem> + '_> = if cell_count
+            > self.items.len() as u64
+        {
+            Box::new(self.items.iter())
+        } else {
+            Box::new(
+                (min.x..=max.x)
+                    .flat_map(move |x| {
+                        (min.y..=max.y)
+                            .flat_map(move |y| (min.z..=max.z).map(move |z| IVec3::new(x, y, z)))
+                    })
+                    .filter_map(move |cell| self.cells.get(&cell))
+                    .flat_map(move |idxs| idxs.iter().map(move |idx| &self.items[*idx])),
+            )
+        };
+        candidates.filter(move |item| item.pos.distance_squared(pos) <= radius_squared)
+    }
+
+    /// The `k` items closest to `pos` within `max_radius`, closest first.
+    pub fn k_nearest(
+        &self,
+        pos: TVec3,
+        k: usize,
+        max_radius: TReal,
+        mut filter: impl FnMut(&SpatialItem) -> bool,
+    ) -> SVec<[SpatialItem; 8]> {
+        let mut found: SVec<[(TReal, SpatialItem); 16]> = self
+            .within_radius(pos, max_radius)
+            .filter(|item| filter(item))
+            .map(|item| (item.pos.distance_squared(pos), *item))
+            .collect();
+        found.sort_unstable_by(|(a, _), (b, _)| {
+            a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal)
+        });
+        found.into_iter().take(k).map(|(_, item)| item).collect()
+    }
+}
+
+pub(in crate::mind) fn rebuild(
+    mut index: ResMut<SpatialIndex>,
+    objects: Query<(
+        Entity,
+        &GlobalTransform,
+        &Velocity,
+        Option<&CraftDimensions>,
+        Option<&Projectile>,
+        Option<&Decoy>,
+    )>,
+) {
+    index.clear();
+    for (entt, xform, vel, craft, proj, decoy) in objects.iter() {
+        index.insert(SpatialItem {
+            entt,
+            pos: xform.translation(),
+            vel: vel.linvel,
+            class: ContactClass::classify(craft.is_some(), proj.is_some(), decoy.is_some()),
+        });
+    }
+}
+
+#[test]
+fn spatial_index_test() {
+    let mut index = SpatialIndex::new(10.);
+    for ii in 0..100 {
+        index.insert(SpatialItem {
+            entt: Entity::from_raw(ii),
+            pos: TVec3::X * ii as TReal,
+            vel: TVec3::ZERO,
+            class: ContactClass::Unknown,
+        });
+    }
+    let mut within: Vec<_> = index
+        .within_radius(TVec3::X * 50., 5.5)
+        .map(|item| item.entt.id())
+        .collect();
+    within.sort_unstable();
+    assert_eq!(within, (45..=55).collect::<Vec<_>>());
+
+    let nearest = index.k_nearest(TVec3::X * 20.2, 3, 100., |_| true);
+    assert_eq!(
+        nearest
+            .iter()
+            .map(|item| item.entt.id())
+            .collect::<Vec<_>>(),
+        vec![20, 21, 19]
+    );
+    // large radii fall back to a scan
+    assert_eq!(index.within_radius(TVec3::ZERO, 10_000.).count(), 100);
+}